@@ -0,0 +1,134 @@
+use crate::internal_comms::BgpPathInfo;
+
+use std::net::IpAddr;
+
+use crate::fetch_internet::https_get;
+
+const RIPESTAT_HOST: &str = "stat.ripe.net";
+
+/// Asks RIPEstat which routes its collectors hold for the public IP
+pub fn lookup_bgp_path(interface_ip: IpAddr, public_ip: IpAddr) -> BgpPathInfo {
+    // RIPEstat asks callers to name themselves with sourceapp
+    let path = format!("/data/bgp-state/data.json?resource={}&sourceapp=netcheck", public_ip);
+
+    let body = match https_get(interface_ip, RIPESTAT_HOST, 443, &path) {
+        Ok(body) => body,
+        Err(_) => {
+            return BgpPathInfo {
+                error: Some("Couldn't reach RIPEstat.".to_string()),
+                ..Default::default()
+            };
+        }
+    };
+
+    parse_bgp_state(&body)
+}
+
+/// Picks the most specific prefix out of a bgp-state response, and the shortest path any collector
+/// has to it. Paths repeat an ASN when it's prepended, which says nothing about the route, so
+/// repeats are collapsed. AS sets (arrays in the path) can't be pinned to one ASN and are skipped.
+fn parse_bgp_state(body: &str) -> BgpPathInfo {
+    let mut bgp_path_info = BgpPathInfo {
+        complete: true,
+        ..Default::default()
+    };
+
+    let state = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(state) => state,
+        Err(_) => {
+            bgp_path_info.error = Some("Unexpected response from RIPEstat.".to_string());
+            return bgp_path_info;
+        }
+    };
+
+    let routes = match state["data"]["bgp_state"].as_array() {
+        Some(routes) => routes,
+        None => {
+            bgp_path_info.error = Some("Unexpected response from RIPEstat.".to_string());
+            return bgp_path_info;
+        }
+    };
+
+    // (prefix length, prefix, path)
+    let mut best: Option<(u8, String, Vec<u32>)> = None;
+
+    for route in routes {
+        let prefix = match route["target_prefix"].as_str() {
+            Some(prefix) => prefix,
+            None => continue,
+        };
+
+        let prefix_len = match prefix.split_once('/').map(|(_, len)| len.parse::<u8>()) {
+            Some(Ok(prefix_len)) => prefix_len,
+            _ => continue,
+        };
+
+        let mut path: Vec<u32> = route["path"].as_array()
+            .map(|path| path.iter().filter_map(|asn| asn.as_u64()).filter_map(|asn| u32::try_from(asn).ok()).collect())
+            .unwrap_or_default();
+
+        path.dedup();
+
+        if path.is_empty() {
+            continue;
+        }
+
+        let better = match best {
+            Some((best_len, _, ref best_path)) => prefix_len > best_len || (prefix_len == best_len && path.len() < best_path.len()),
+            None => true,
+        };
+
+        if better {
+            best = Some((prefix_len, prefix.to_string(), path));
+        }
+    }
+
+    match best {
+        Some((_, prefix, path)) => {
+            bgp_path_info.prefix = Some(prefix);
+            bgp_path_info.origin_asn = path.last().copied();
+            bgp_path_info.as_path = path;
+        }
+        // Addresses behind carrier-grade NAT or otherwise unannounced
+        None => bgp_path_info.error = Some("No BGP route seen for this address.".to_string()),
+    }
+
+    bgp_path_info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_the_most_specific_prefix_and_shortest_path() {
+        let body = r#"{"data": {"resource": "1.1.1.1", "bgp_state": [
+            {"target_prefix": "1.0.0.0/8", "source_id": "00-195.66.224.175", "path": [6939, 13335]},
+            {"target_prefix": "1.1.1.0/24", "source_id": "00-195.66.224.175", "path": [3356, 1299, 13335]},
+            {"target_prefix": "1.1.1.0/24", "source_id": "01-80.249.208.1", "path": [6939, 13335, 13335, 13335]}
+        ]}}"#;
+
+        let bgp_path_info = parse_bgp_state(body);
+
+        assert_eq!(bgp_path_info.error, None);
+        assert_eq!(bgp_path_info.prefix.as_deref(), Some("1.1.1.0/24"));
+        assert_eq!(bgp_path_info.origin_asn, Some(13335));
+        assert_eq!(bgp_path_info.as_path, vec![6939, 13335]);
+    }
+
+    #[test]
+    fn as_sets_are_skipped() {
+        let body = r#"{"data": {"bgp_state": [{"target_prefix": "203.0.113.0/24", "path": [174, 64500, [64501, 64502]]}]}}"#;
+
+        assert_eq!(parse_bgp_state(body).as_path, vec![174, 64500]);
+    }
+
+    #[test]
+    fn no_routes_is_an_error() {
+        let bgp_path_info = parse_bgp_state(r#"{"data": {"bgp_state": []}}"#);
+
+        assert!(bgp_path_info.complete);
+        assert!(bgp_path_info.error.is_some());
+        assert!(parse_bgp_state("<html>").error.is_some());
+    }
+}
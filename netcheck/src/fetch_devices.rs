@@ -0,0 +1,234 @@
+use crate::internal_comms::{DeviceType, FetchedDataMessage, NetworkDevice, NetworkDevices, send_or_stop};
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rustdns::Message;
+
+use crate::fetch_local::get_interface_ip;
+use crate::fetch_mdns::{build_mdns_query, open_mdns_listener, MDNS_GROUP, MDNS_PORT};
+
+const SSDP_GROUP: Ipv4Addr = Ipv4Addr::new(239, 255, 255, 250);
+const SSDP_PORT: u16 = 1900;
+
+const LISTEN_DURATION: Duration = Duration::from_secs(2);
+
+// Zeroconf services we ask for by name, and what offering them says about the device
+const MDNS_SERVICES: [(&str, DeviceType); 6] = [
+    ("_ipp._tcp.local", DeviceType::Printer),
+    ("_ipps._tcp.local", DeviceType::Printer),
+    ("_airplay._tcp.local", DeviceType::AppleTV),
+    ("_googlecast._tcp.local", DeviceType::Chromecast),
+    ("_smb._tcp.local", DeviceType::Nas),
+    ("_afpovertcp._tcp.local", DeviceType::Nas),
+];
+
+pub fn fetch_and_return_network_devices(tx: Sender<FetchedDataMessage>, interface: String) {
+    // Both SSDP and mDNS discovery here are IPv4 only
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(IpAddr::V4(interface_ip)) => interface_ip,
+        _ => {
+            send_or_stop(&tx, FetchedDataMessage::NetworkDevices(NetworkDevices {
+                error: Some("No IPv4 address.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let ssdp_thread = thread::spawn(move || discover_ssdp_devices(interface_ip));
+
+    let mut devices = discover_mdns_devices(interface_ip);
+
+    if !send_or_stop(&tx, FetchedDataMessage::NetworkDevices(NetworkDevices {
+        devices: devices.clone(),
+        ..Default::default()
+    })) {
+        return;
+    }
+
+    if let Ok(ssdp_devices) = ssdp_thread.join() {
+        for device in ssdp_devices {
+            add_device(&mut devices, device);
+        }
+    }
+
+    send_or_stop(&tx, FetchedDataMessage::NetworkDevices(NetworkDevices {
+        devices,
+        discovery_complete: true,
+        ..Default::default()
+    }));
+}
+
+/// Records a device unless we already know about it. A more specific type
+/// replaces "Other" when the same service shows up through the other protocol.
+fn add_device(devices: &mut Vec<NetworkDevice>, device: NetworkDevice) {
+    if let Some(existing) = devices.iter_mut().find(|existing| existing.ip == device.ip && existing.service_name == device.service_name) {
+        if existing.device_type == DeviceType::Other {
+            existing.device_type = device.device_type;
+        }
+        return;
+    }
+
+    devices.push(device);
+}
+
+/// Asks for each interesting service type and collects the instances that answer
+fn discover_mdns_devices(interface_ip: Ipv4Addr) -> Vec<NetworkDevice> {
+    let mut devices = Vec::new();
+
+    let socket = match open_mdns_listener(interface_ip) {
+        Ok(socket) => socket,
+        Err(_) => return devices
+    };
+
+    for (service, _) in MDNS_SERVICES {
+        if let Some(query) = build_mdns_query(service, rustdns::Type::PTR, false) {
+            let _ = socket.send_to(&query, SocketAddr::new(IpAddr::V4(MDNS_GROUP), MDNS_PORT));
+        }
+    }
+
+    let started = Instant::now();
+    let mut buf = [0u8; 9000];
+
+    while started.elapsed() < LISTEN_DURATION {
+        let (len, source) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(_) => continue
+        };
+
+        let resp = match Message::from_slice(&buf[..len]) {
+            Ok(resp) => resp,
+            Err(_) => continue
+        };
+
+        // Responders often put the PTR in the additional section when answering someone else
+        for record in resp.answers.iter().chain(resp.additionals.iter()) {
+            let instance = match record.resource {
+                rustdns::Resource::PTR(ref instance) => instance,
+                _ => continue
+            };
+
+            let service = record.name.trim_end_matches('.');
+
+            let device_type = match MDNS_SERVICES.iter().find(|(name, _)| name.eq_ignore_ascii_case(service)) {
+                Some((_, device_type)) => *device_type,
+                None => continue
+            };
+
+            // Instance names are "<friendly name>.<service>", only the friendly part is interesting
+            let service_name = instance.trim_end_matches('.').strip_suffix(service).unwrap_or(instance).trim_end_matches('.');
+
+            add_device(&mut devices, NetworkDevice {
+                device_type,
+                ip: source.ip().to_string(),
+                service_name: service_name.to_string(),
+            });
+        }
+    }
+
+    devices
+}
+
+/// Sends an SSDP M-SEARCH for everything and collects the UPnP devices that reply
+fn discover_ssdp_devices(interface_ip: Ipv4Addr) -> Vec<NetworkDevice> {
+    let mut devices = Vec::new();
+
+    let socket = match UdpSocket::bind(SocketAddr::new(IpAddr::V4(interface_ip), 0)) {
+        Ok(socket) => socket,
+        Err(_) => return devices
+    };
+
+    if socket.set_read_timeout(Some(Duration::from_millis(250))).is_err() {
+        return devices;
+    }
+
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}:{}\r\nMAN: \"ssdp:discover\"\r\nMX: 1\r\nST: ssdp:all\r\n\r\n",
+        SSDP_GROUP, SSDP_PORT
+    );
+
+    if socket.send_to(search.as_bytes(), SocketAddr::new(IpAddr::V4(SSDP_GROUP), SSDP_PORT)).is_err() {
+        return devices;
+    }
+
+    let started = Instant::now();
+    let mut buf = [0u8; 2048];
+
+    while started.elapsed() < LISTEN_DURATION {
+        let (len, source) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(_) => continue
+        };
+
+        let response = String::from_utf8_lossy(&buf[..len]);
+
+        let search_target = match get_ssdp_header(&response, "ST") {
+            Some(search_target) => search_target,
+            None => continue
+        };
+
+        let device_type = categorise_ssdp_device(&search_target);
+
+        // A router answers for each of its IGD services, one entry is plenty
+        if device_type != DeviceType::Other && devices.iter().any(|device| device.ip == source.ip().to_string() && device.device_type == device_type) {
+            continue;
+        }
+
+        // Devices answer once per service they offer, the server string tells them apart better
+        let service_name = match device_type {
+            DeviceType::Other => get_ssdp_header(&response, "SERVER").unwrap_or(search_target),
+            _ => search_target,
+        };
+
+        add_device(&mut devices, NetworkDevice {
+            device_type,
+            ip: source.ip().to_string(),
+            service_name,
+        });
+    }
+
+    // Anything that identified itself properly makes its generic entries redundant
+    let identified: Vec<String> = devices.iter()
+        .filter(|device| device.device_type != DeviceType::Other)
+        .map(|device| device.ip.clone())
+        .collect();
+
+    devices.retain(|device| device.device_type != DeviceType::Other || !identified.contains(&device.ip));
+
+    devices
+}
+
+fn get_ssdp_header(response: &str, name: &str) -> Option<String> {
+    for line in response.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some(header) => header,
+            None => continue
+        };
+
+        if key.trim().eq_ignore_ascii_case(name) {
+            return Some(value.trim().to_string());
+        }
+    }
+
+    None
+}
+
+fn categorise_ssdp_device(search_target: &str) -> DeviceType {
+    if search_target.contains("InternetGatewayDevice") || search_target.contains("WANIPConnection") {
+        return DeviceType::Router;
+    }
+
+    // Google Cast devices advertise themselves through DIAL
+    if search_target.contains("dial-multiscreen-org") {
+        return DeviceType::Chromecast;
+    }
+
+    if search_target.contains("Printer") {
+        return DeviceType::Printer;
+    }
+
+    DeviceType::Other
+}
@@ -0,0 +1,645 @@
+use crate::internal_comms::{DHCPInfo, FetchedDataMessage, send_or_stop};
+use crate::fetch_ndp::get_neighbour_entries;
+
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use pnet::ipnetwork::IpNetwork;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+const DHCLIENT_LEASE_FILES: [&str; 2] = [
+    "/var/lib/dhclient/dhclient.leases",
+    "/var/lib/dhcp/dhclient.leases",
+];
+const NETWORKD_LEASE_DIR: &str = "/run/systemd/netif/leases";
+const NETWORKMANAGER_LEASE_DIR: &str = "/var/lib/NetworkManager";
+
+// How long to give the kernel to resolve the router's MAC address after prodding it
+const ARP_RESOLVE_WAIT: Duration = Duration::from_secs(1);
+// Every server on the link gets the DHCPINFORM, so keep listening this long for all their answers
+const DHCP_INFORM_TIMEOUT: Duration = Duration::from_secs(2);
+
+const DHCP_SERVER_PORT: u16 = 67;
+const DHCP_CLIENT_PORT: u16 = 68;
+const DHCP_MAGIC_COOKIE: [u8; 4] = [99, 130, 83, 99];
+const DHCP_OPTION_MESSAGE_TYPE: u8 = 53;
+const DHCP_OPTION_SERVER_IDENTIFIER: u8 = 54;
+const DHCP_OPTION_PARAMETER_REQUEST_LIST: u8 = 55;
+const DHCP_OPTION_END: u8 = 255;
+const DHCPACK: u8 = 5;
+const DHCPINFORM: u8 = 8;
+
+pub fn fetch_and_return_dhcp_info(tx: Sender<FetchedDataMessage>, interface: String, admin: bool) {
+    let mut dhcp_info = match read_lease(&interface) {
+        Some(dhcp_info) => dhcp_info,
+        None => {
+            send_or_stop(&tx, FetchedDataMessage::DHCPInfo(DHCPInfo {
+                error: Some("No DHCP lease found.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let iface = pnet::datalink::interfaces().into_iter().find(|iface| iface.name == interface);
+    let ipv4 = iface.as_ref().and_then(|iface| iface.ips.iter().find(|ip| ip.is_ipv4()).copied());
+
+    if let Some(ref server) = dhcp_info.dhcp_server {
+        dhcp_info.server_on_local_subnet = is_on_local_subnet(server, ipv4);
+    }
+
+    // The lease is worth showing straight away, the rest takes a few seconds
+    if !send_or_stop(&tx, FetchedDataMessage::DHCPInfo(dhcp_info.clone())) {
+        return;
+    }
+
+    let local_ip = match ipv4.map(|ip| ip.ip()) {
+        Some(IpAddr::V4(local_ip)) => Some(local_ip),
+        _ => None,
+    };
+
+    if let (Some(router), Some(local_ip)) = (dhcp_info.dhcp_router.clone(), local_ip) {
+        dhcp_info.router_resolved = check_router_resolves(&interface, local_ip, &router);
+    }
+
+    // Port 68 is privileged, so only root can listen for the answers
+    let mac = iface.as_ref().and_then(|iface| iface.mac);
+
+    if let (true, Some(local_ip), Some(mac)) = (admin, local_ip, mac) {
+        dhcp_info.inform_servers = send_dhcp_inform(&interface, local_ip, [mac.0, mac.1, mac.2, mac.3, mac.4, mac.5]).ok();
+    }
+
+    dhcp_info.complete = true;
+    send_or_stop(&tx, FetchedDataMessage::DHCPInfo(dhcp_info));
+}
+
+/// Whether what was found suggests the lease came from a server that shouldn't be handing them out:
+/// one that's off the local subnet, a router that isn't actually on the link, or a different or
+/// extra server answering now. Legitimate networks can trip the first with a DHCP relay, hence "possible".
+pub fn possible_rogue_server(dhcp_info: &DHCPInfo) -> bool {
+    let unexpected_answer = match (&dhcp_info.inform_servers, &dhcp_info.dhcp_server) {
+        (Some(servers), Some(lease_server)) => servers.iter().any(|server| server != lease_server),
+        _ => false,
+    };
+
+    dhcp_info.server_on_local_subnet == Some(false) || dhcp_info.router_resolved == Some(false) || unexpected_answer
+}
+
+fn is_on_local_subnet(server: &str, local_network: Option<IpNetwork>) -> Option<bool> {
+    let server = match server.parse::<IpAddr>() {
+        Ok(server) => server,
+        Err(_) => return None
+    };
+
+    local_network.map(|network| network.contains(server))
+}
+
+/// Looks the router up in the ARP cache. If it isn't there yet, a datagram sent its way makes the
+/// kernel resolve it, and a router that's really on the link will have answered within a second.
+fn check_router_resolves(interface: &str, local_ip: Ipv4Addr, router: &str) -> Option<bool> {
+    let resolved = |interface: &str| -> Result<bool, ()> {
+        let entries = get_neighbour_entries(interface, "-4")?;
+        Ok(entries.iter().any(|entry| entry.ip == router && !entry.mac.is_empty()))
+    };
+
+    match resolved(interface) {
+        Ok(true) => return Some(true),
+        Ok(false) => {}
+        Err(_) => return None
+    }
+
+    let router_ip = match router.parse::<Ipv4Addr>() {
+        Ok(router_ip) => router_ip,
+        Err(_) => return None
+    };
+
+    // The discard port, nothing needs to be listening for the ARP request to go out
+    let prodded = UdpSocket::bind(SocketAddr::new(IpAddr::V4(local_ip), 0))
+        .and_then(|socket| socket.send_to(&[0], SocketAddr::new(IpAddr::V4(router_ip), 9)));
+
+    if prodded.is_err() {
+        return None;
+    }
+
+    thread::sleep(ARP_RESOLVE_WAIT);
+
+    resolved(interface).ok()
+}
+
+/// Broadcasts a DHCPINFORM, which asks for configuration without taking a lease, and returns the
+/// server identifier of everyone who answered. On a healthy network that's just the lease's server.
+fn send_dhcp_inform(interface: &str, local_ip: Ipv4Addr, mac: [u8; 6]) -> Result<Vec<String>, ()> {
+    let socket = match Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)) {
+        Ok(socket) => socket,
+        Err(_) => return Err(())
+    };
+
+    // The DHCP client may already have the port, and answers come straight back to our address
+    if socket.set_reuse_address(true).is_err() || socket.set_broadcast(true).is_err() {
+        return Err(());
+    }
+
+    // Otherwise the broadcast leaves by whichever interface has the default route
+    #[cfg(target_os = "linux")]
+    {
+        if socket.bind_device(Some(interface.as_bytes())).is_err() {
+            return Err(());
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    let _ = interface;
+
+    if socket.bind(&SockAddr::from(SocketAddrV4::new(local_ip, DHCP_CLIENT_PORT))).is_err() {
+        return Err(());
+    }
+
+    if socket.set_read_timeout(Some(Duration::from_millis(200))).is_err() {
+        return Err(());
+    }
+
+    let xid = SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.subsec_nanos()).unwrap_or(0) ^ std::process::id();
+    let request = build_dhcp_inform(xid, local_ip, mac);
+
+    if socket.send_to(&request, &SockAddr::from(SocketAddrV4::new(Ipv4Addr::BROADCAST, DHCP_SERVER_PORT))).is_err() {
+        return Err(());
+    }
+
+    let socket: UdpSocket = socket.into();
+    let started = Instant::now();
+    let mut buf = [0u8; 1500];
+    let mut servers: Vec<String> = Vec::new();
+
+    while started.elapsed() < DHCP_INFORM_TIMEOUT {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(_) => continue
+        };
+
+        if let Some(server) = parse_dhcp_ack(&buf[..len], xid) {
+            let server = server.to_string();
+
+            if !servers.contains(&server) {
+                servers.push(server);
+            }
+        }
+    }
+
+    Ok(servers)
+}
+
+/// A BOOTP request carrying a DHCPINFORM for the address we already have (RFC 2131 section 4.4.3)
+fn build_dhcp_inform(xid: u32, local_ip: Ipv4Addr, mac: [u8; 6]) -> Vec<u8> {
+    let mut packet = vec![0u8; 236];
+
+    // Request, over Ethernet, with 6 byte hardware addresses
+    packet[0] = 1;
+    packet[1] = 1;
+    packet[2] = 6;
+    packet[4..8].copy_from_slice(&xid.to_be_bytes());
+    packet[12..16].copy_from_slice(&local_ip.octets());
+    packet[28..34].copy_from_slice(&mac);
+
+    packet.extend_from_slice(&DHCP_MAGIC_COOKIE);
+    packet.extend_from_slice(&[DHCP_OPTION_MESSAGE_TYPE, 1, DHCPINFORM]);
+    // Subnet mask, router and DNS servers, which is what a rogue server would want to change
+    packet.extend_from_slice(&[DHCP_OPTION_PARAMETER_REQUEST_LIST, 3, 1, 3, 6]);
+    packet.push(DHCP_OPTION_END);
+
+    packet
+}
+
+/// The server identifier from a DHCPACK answering our request, None for anything else
+fn parse_dhcp_ack(packet: &[u8], xid: u32) -> Option<Ipv4Addr> {
+    // A reply, to our transaction, with DHCP options after the BOOTP header
+    if packet.len() < 240 || packet[0] != 2 || packet[4..8] != xid.to_be_bytes() || packet[236..240] != DHCP_MAGIC_COOKIE {
+        return None;
+    }
+
+    let mut options = &packet[240..];
+    let mut message_type = None;
+    let mut server = None;
+
+    while let Some((&code, rest)) = options.split_first() {
+        match code {
+            // Padding has no length byte
+            0 => {
+                options = rest;
+                continue;
+            }
+            DHCP_OPTION_END => break,
+            _ => {}
+        }
+
+        let (&len, rest) = rest.split_first()?;
+        let value = rest.get(..len as usize)?;
+
+        match (code, value) {
+            (DHCP_OPTION_MESSAGE_TYPE, [kind]) => message_type = Some(*kind),
+            (DHCP_OPTION_SERVER_IDENTIFIER, [a, b, c, d]) => server = Some(Ipv4Addr::new(*a, *b, *c, *d)),
+            _ => {}
+        }
+
+        options = &rest[len as usize..];
+    }
+
+    match message_type {
+        Some(DHCPACK) => server,
+        _ => None
+    }
+}
+
+/// Tries each DHCP client's lease store in turn, taking the first one that knows about the interface
+fn read_lease(interface: &str) -> Option<DHCPInfo> {
+    for path in DHCLIENT_LEASE_FILES {
+        if let Some(dhcp_info) = read_dhclient_lease(Path::new(path), interface) {
+            return Some(dhcp_info);
+        }
+    }
+
+    if let Some(path) = get_networkd_lease_path(interface) {
+        if let Some(dhcp_info) = read_key_value_lease(&path) {
+            return Some(dhcp_info);
+        }
+    }
+
+    // NetworkManager names its leases "<client>-<connection uuid>-<interface>.lease", and
+    // uses dhclient's format or networkd's depending on which client it's built with
+    let entries = match fs::read_dir(NETWORKMANAGER_LEASE_DIR) {
+        Ok(entries) => entries,
+        Err(_) => return None
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+
+        if !file_name.ends_with(&format!("-{}.lease", interface)) {
+            continue;
+        }
+
+        let dhcp_info = match file_name.starts_with("dhclient") {
+            true => read_dhclient_lease(&path, interface),
+            false => read_key_value_lease(&path),
+        };
+
+        if dhcp_info.is_some() {
+            return dhcp_info;
+        }
+    }
+
+    None
+}
+
+fn get_networkd_lease_path(interface: &str) -> Option<PathBuf> {
+    let ifindex = match fs::read_to_string(format!("/sys/class/net/{}/ifindex", interface)) {
+        Ok(ifindex) => ifindex,
+        Err(_) => return None
+    };
+
+    Some(Path::new(NETWORKD_LEASE_DIR).join(ifindex.trim()))
+}
+
+/// networkd's lease is rewritten whenever the lease is renewed, so its mtime is when that last happened
+fn get_modified_time(path: &Path) -> Option<u64> {
+    let modified = match fs::metadata(path).and_then(|metadata| metadata.modified()) {
+        Ok(modified) => modified,
+        Err(_) => return None
+    };
+
+    modified.duration_since(UNIX_EPOCH).ok().map(|duration| duration.as_secs())
+}
+
+fn read_dhclient_lease(path: &Path, interface: &str) -> Option<DHCPInfo> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return None
+    };
+
+    parse_dhclient_lease(&contents, interface)
+}
+
+/// Parses dhclient's "lease { ... }" blocks. The file is appended to on every
+/// renewal, so the last block for the interface is the current one.
+fn parse_dhclient_lease(contents: &str, interface: &str) -> Option<DHCPInfo> {
+    let interface_line = format!("interface \"{}\";", interface);
+
+    let lease = contents
+        .split("lease {")
+        .skip(1)
+        .filter(|lease| lease.lines().any(|line| line.trim() == interface_line))
+        .last()?;
+
+    let mut dhcp_info = DHCPInfo::default();
+
+    let mut starts = None;
+    let mut renew = None;
+    let mut expire = None;
+    let mut renewal_time = None;
+
+    for line in lease.lines() {
+        let line = line.trim().trim_end_matches(';');
+
+        let (name, value) = match line.split_once(' ') {
+            Some(statement) => statement,
+            None => continue
+        };
+
+        match name {
+            "starts" => starts = parse_dhclient_time(value),
+            "renew" => renew = parse_dhclient_time(value),
+            "expire" => expire = parse_dhclient_time(value),
+            _ => {}
+        }
+
+        let (name, value) = match name {
+            "option" => match value.split_once(' ') {
+                Some(option) => option,
+                None => continue
+            },
+            _ => continue
+        };
+
+        match name {
+            "dhcp-server-identifier" => dhcp_info.dhcp_server = Some(value.to_string()),
+            "dhcp-lease-time" => dhcp_info.lease_time = value.parse().ok(),
+            // A comma separated list, the first is the default gateway
+            "routers" => dhcp_info.dhcp_router = value.split(',').next().map(|router| router.trim().to_string()),
+            "dhcp-renewal-time" => renewal_time = value.parse::<u64>().ok(),
+            "domain-name-servers" => {
+                dhcp_info.dhcp_declared_dns = Some(value.split(',').map(|server| server.trim().to_string()).collect());
+            }
+            _ => {}
+        }
+    }
+
+    // dhclient doesn't write when it got the lease, only when it's due to renew and expire. Both are
+    // counted from that moment, by the lease time and the renewal time (half the lease unless the
+    // server says otherwise). Some clients do write "starts", which is the moment itself
+    dhcp_info.last_renewed = match (starts, expire, renew, dhcp_info.lease_time) {
+        (Some(starts), _, _, _) => Some(starts),
+        (None, Some(expire), _, Some(lease_time)) => expire.checked_sub(lease_time),
+        (None, None, Some(renew), Some(lease_time)) => renew.checked_sub(renewal_time.unwrap_or(lease_time / 2)),
+        _ => None,
+    };
+
+    Some(dhcp_info)
+}
+
+/// Reads a dhclient lease time as seconds since the Unix epoch. They're written as
+/// "4 2026/10/15 12:34:56" (weekday, then the date and time in UTC), or as "epoch 1760531696;"
+/// with db-time-format local. A lease that never expires has "never".
+fn parse_dhclient_time(value: &str) -> Option<u64> {
+    let mut fields = value.split_whitespace();
+
+    match fields.next()? {
+        "epoch" => return fields.next()?.parse().ok(),
+        "never" => return None,
+        _ => {}
+    }
+
+    let mut date = fields.next()?.split('/').map(|field| field.parse::<u32>().ok());
+    let mut time = fields.next()?.split(':').map(|field| field.parse::<u32>().ok());
+
+    let (year, month, day) = (date.next()??, date.next()??, date.next()??);
+    let (hour, minute, second) = (time.next()??, time.next()??, time.next()??);
+
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let seconds = days_since_epoch(year as i64, month, day) * 86400 + (hour * 3600 + minute * 60 + second) as i64;
+
+    u64::try_from(seconds).ok()
+}
+
+/// Days from 1970-01-01 to the given date in the proleptic Gregorian calendar
+/// (Howard Hinnant's days_from_civil)
+fn days_since_epoch(year: i64, month: u32, day: u32) -> i64 {
+    // Counting years from March puts the leap day at the end
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month_from_march = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_from_march + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146097 + day_of_era - 719468
+}
+
+fn read_key_value_lease(path: &Path) -> Option<DHCPInfo> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(_) => return None
+    };
+
+    let mut dhcp_info = parse_key_value_lease(&contents);
+
+    // Unlike dhclient's, these leases have no times in them at all
+    dhcp_info.last_renewed = get_modified_time(path);
+
+    Some(dhcp_info)
+}
+
+/// Parses the KEY=VALUE lease files written by systemd-networkd and NetworkManager's internal client
+fn parse_key_value_lease(contents: &str) -> DHCPInfo {
+    let mut dhcp_info = DHCPInfo::default();
+
+    for line in contents.lines() {
+        let (key, value) = match line.split_once('=') {
+            Some(pair) => pair,
+            None => continue
+        };
+
+        match key.trim() {
+            "SERVER_ADDRESS" => dhcp_info.dhcp_server = Some(value.trim().to_string()),
+            "LIFETIME" => dhcp_info.lease_time = value.trim().parse().ok(),
+            "ROUTER" => dhcp_info.dhcp_router = value.split_whitespace().next().map(|router| router.to_string()),
+            "DNS" => {
+                dhcp_info.dhcp_declared_dns = Some(value.split_whitespace().map(|server| server.to_string()).collect());
+            }
+            _ => {}
+        }
+    }
+
+    dhcp_info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Two renewals of the same lease on wlan0, with an older one from eth0 in between
+    const DHCLIENT_LEASES: &str = "\
+lease {
+  interface \"wlan0\";
+  fixed-address 192.168.1.23;
+  option subnet-mask 255.255.255.0;
+  option dhcp-lease-time 86400;
+  option dhcp-message-type 5;
+  option domain-name-servers 192.168.1.1,8.8.8.8;
+  option dhcp-server-identifier 192.168.1.1;
+  renew 4 2026/10/15 20:00:00;
+  rebind 5 2026/10/16 05:00:00;
+  expire 5 2026/10/16 08:00:00;
+}
+lease {
+  interface \"eth0\";
+  fixed-address 10.0.0.5;
+  option dhcp-lease-time 3600;
+  option dhcp-server-identifier 10.0.0.1;
+  expire 3 2026/10/14 09:00:00;
+}
+lease {
+  interface \"wlan0\";
+  fixed-address 192.168.1.23;
+  option dhcp-lease-time 86400;
+  option dhcp-renewal-time 3600;
+  option routers 192.168.1.254,192.168.1.1;
+  option domain-name-servers 192.168.1.1;
+  option dhcp-server-identifier 192.168.1.254;
+  renew 5 2026/10/16 01:00:00;
+  rebind 5 2026/10/16 18:00:00;
+  expire never;
+}
+";
+
+    #[test]
+    fn dhclient_takes_the_last_lease_for_the_interface() {
+        let lease = parse_dhclient_lease(DHCLIENT_LEASES, "wlan0").unwrap();
+
+        assert_eq!(lease.dhcp_server.as_deref(), Some("192.168.1.254"));
+        assert_eq!(lease.dhcp_router.as_deref(), Some("192.168.1.254"));
+        assert_eq!(lease.lease_time, Some(86400));
+        assert_eq!(lease.dhcp_declared_dns, Some(vec!["192.168.1.1".to_string()]));
+        // Renewal due an hour after 2026-10-16 00:00:00 UTC
+        assert_eq!(lease.last_renewed, Some(1_792_108_800));
+    }
+
+    #[test]
+    fn dhclient_renewal_is_counted_back_from_expiry() {
+        let lease = parse_dhclient_lease(DHCLIENT_LEASES, "eth0").unwrap();
+
+        assert_eq!(lease.dhcp_server.as_deref(), Some("10.0.0.1"));
+        // Expiring at 2026-10-14 09:00:00 UTC after an hour
+        assert_eq!(lease.last_renewed, Some(1_791_964_800));
+    }
+
+    #[test]
+    fn dhclient_prefers_starts() {
+        let lease = parse_dhclient_lease("lease {\n  interface \"eth0\";\n  starts epoch 1760000000;\n  expire epoch 1760003600;\n}\n", "eth0").unwrap();
+        assert_eq!(lease.last_renewed, Some(1_760_000_000));
+    }
+
+    #[test]
+    fn dhclient_without_a_lease_for_the_interface() {
+        assert!(parse_dhclient_lease(DHCLIENT_LEASES, "eth1").is_none());
+    }
+
+    #[test]
+    fn dhclient_times() {
+        assert_eq!(parse_dhclient_time("4 1970/01/01 00:00:00"), Some(0));
+        assert_eq!(parse_dhclient_time("4 2024/02/29 12:30:15"), Some(1_709_209_815));
+        assert_eq!(parse_dhclient_time("epoch 1760531696"), Some(1_760_531_696));
+        assert_eq!(parse_dhclient_time("never"), None);
+        assert_eq!(parse_dhclient_time("4 2026/13/01 00:00:00"), None);
+    }
+
+    #[test]
+    fn networkd_lease() {
+        // Trimmed from /run/systemd/netif/leases/2
+        let lease = parse_key_value_lease("\
+# This is private data. Do not parse.
+ADDRESS=192.168.1.23
+NETMASK=255.255.255.0
+ROUTER=192.168.1.1
+SERVER_ADDRESS=192.168.1.1
+T1=43200
+T2=75600
+LIFETIME=86400
+DNS=192.168.1.1 8.8.8.8
+CLIENTID=ff0a1b2c3d00010001
+");
+
+        assert_eq!(lease.dhcp_server.as_deref(), Some("192.168.1.1"));
+        assert_eq!(lease.lease_time, Some(86400));
+        assert_eq!(lease.dhcp_declared_dns, Some(vec!["192.168.1.1".to_string(), "8.8.8.8".to_string()]));
+        assert_eq!(lease.dhcp_router.as_deref(), Some("192.168.1.1"));
+    }
+
+    fn dhcp_ack(xid: u32, options: &[u8]) -> Vec<u8> {
+        let mut packet = vec![0u8; 236];
+        packet[0] = 2;
+        packet[4..8].copy_from_slice(&xid.to_be_bytes());
+        packet.extend_from_slice(&DHCP_MAGIC_COOKIE);
+        packet.extend_from_slice(options);
+        packet
+    }
+
+    #[test]
+    fn dhcp_inform_request() {
+        let packet = build_dhcp_inform(0x12345678, Ipv4Addr::new(192, 168, 1, 23), [0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+
+        assert_eq!(packet[0], 1);
+        assert_eq!(packet[4..8], [0x12, 0x34, 0x56, 0x78]);
+        // ciaddr is our address, yiaddr is left for the server
+        assert_eq!(packet[12..16], [192, 168, 1, 23]);
+        assert_eq!(packet[16..20], [0, 0, 0, 0]);
+        assert_eq!(packet[28..34], [0x52, 0x54, 0, 0x12, 0x34, 0x56]);
+        assert_eq!(packet[236..240], DHCP_MAGIC_COOKIE);
+        assert_eq!(packet[240..243], [DHCP_OPTION_MESSAGE_TYPE, 1, DHCPINFORM]);
+        assert_eq!(packet.last(), Some(&DHCP_OPTION_END));
+    }
+
+    #[test]
+    fn dhcp_ack_server_identifier() {
+        let options = [0, DHCP_OPTION_MESSAGE_TYPE, 1, DHCPACK, 1, 4, 255, 255, 255, 0, DHCP_OPTION_SERVER_IDENTIFIER, 4, 192, 168, 1, 1, DHCP_OPTION_END];
+
+        assert_eq!(parse_dhcp_ack(&dhcp_ack(7, &options), 7), Some(Ipv4Addr::new(192, 168, 1, 1)));
+        // Someone else's transaction
+        assert_eq!(parse_dhcp_ack(&dhcp_ack(8, &options), 7), None);
+    }
+
+    #[test]
+    fn dhcp_ack_needs_to_be_an_ack() {
+        // A DHCPNAK
+        let options = [DHCP_OPTION_MESSAGE_TYPE, 1, 6, DHCP_OPTION_SERVER_IDENTIFIER, 4, 192, 168, 1, 1, DHCP_OPTION_END];
+        assert_eq!(parse_dhcp_ack(&dhcp_ack(7, &options), 7), None);
+
+        // Option length running past the end of the packet
+        let options = [DHCP_OPTION_MESSAGE_TYPE, 1, DHCPACK, DHCP_OPTION_SERVER_IDENTIFIER, 4, 192, 168];
+        assert_eq!(parse_dhcp_ack(&dhcp_ack(7, &options), 7), None);
+    }
+
+    #[test]
+    fn rogue_server_signs() {
+        let healthy = DHCPInfo {
+            dhcp_server: Some("192.168.1.1".to_string()),
+            server_on_local_subnet: Some(true),
+            router_resolved: Some(true),
+            inform_servers: Some(vec!["192.168.1.1".to_string()]),
+            ..Default::default()
+        };
+        assert!(!possible_rogue_server(&healthy));
+
+        // Nothing answering the DHCPINFORM isn't suspicious, plenty of servers ignore them
+        assert!(!possible_rogue_server(&DHCPInfo { inform_servers: Some(Vec::new()), ..healthy.clone() }));
+
+        assert!(possible_rogue_server(&DHCPInfo { server_on_local_subnet: Some(false), ..healthy.clone() }));
+        assert!(possible_rogue_server(&DHCPInfo { router_resolved: Some(false), ..healthy.clone() }));
+        assert!(possible_rogue_server(&DHCPInfo { inform_servers: Some(vec!["10.0.0.66".to_string()]), ..healthy.clone() }));
+    }
+
+    #[test]
+    fn server_subnet() {
+        let network: IpNetwork = "192.168.1.23/24".parse().unwrap();
+
+        assert_eq!(is_on_local_subnet("192.168.1.1", Some(network)), Some(true));
+        assert_eq!(is_on_local_subnet("10.0.0.1", Some(network)), Some(false));
+        assert_eq!(is_on_local_subnet("10.0.0.1", None), None);
+    }
+}
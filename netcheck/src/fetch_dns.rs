@@ -1,163 +1,353 @@
-use crate::internal_comms::{DNSInfo, DNSServer, FetchedDataMessage};
+use crate::internal_comms::{DNSInfo, DNSQueryResult, DNSServer, DnsSource, FetchedDataMessage, send_or_stop};
 
-use std::sync::mpsc;
-use std::sync::mpsc::{Sender, Receiver};
+use std::sync::mpsc::{self, Sender};
 
 use rustdns::Message;
 
 use resolv_conf::Config;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use socket2::{Socket, Domain, Type, Protocol, SockAddr};
-use std::net::{UdpSocket, IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::net::{UdpSocket, IpAddr, SocketAddr};
+use std::io::{Read, Write};
+use std::process::Command;
+use std::sync::Arc;
+use std::thread;
 
+use quinn::Endpoint;
+
+use crate::fetch_http::connect_from;
 use crate::fetch_local::get_interface_ip;
 
-pub fn fetch_and_return_dns_info(tx: Sender<FetchedDataMessage>, interface: String) {
-    let dns_servers = get_dns_servers();
+// Domain every server is asked to resolve
+const TEST_DOMAIN: &str = "example.com";
 
-    let dns_servers = match dns_servers {
-        Ok(dns_servers) => dns_servers,
-        Err(_) => {
-            tx.send(FetchedDataMessage::DNSInfo(DNSInfo {
-                can_fetch: Some(false),
-                can_bind_interface: None,
-                dns_servers: Vec::new(),
-            })).unwrap();
-            return;
-        }
-    };
+#[cfg(not(windows))]
+const HOSTS_FILE: &str = "/etc/hosts";
+#[cfg(windows)]
+const HOSTS_FILE: &str = r"C:\Windows\System32\Drivers\etc\hosts";
+
+// Resolver used for the DNS over QUIC (RFC 9250) check
+const DOQ_SERVER: &str = "dns.adguard-dns.com";
+const DOQ_PORT: u16 = 853;
+const DOQ_TIMEOUT: Duration = Duration::from_secs(3);
+
+// How long each plain DNS query waits for an answer unless --dns-timeout says otherwise
+pub const DEFAULT_DNS_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub fn fetch_and_return_dns_info(tx: Sender<FetchedDataMessage>, interface: String, timeout: Duration) {
+    let dns_servers = gather_all_dns_servers(&interface);
 
     let interface_ip = get_interface_ip(&interface);
 
     let interface_ip = match interface_ip {
         Ok(interface_ip) => interface_ip,
         Err(_) => {
-            tx.send(FetchedDataMessage::DNSInfo(DNSInfo {
+            send_or_stop(&tx, FetchedDataMessage::DNSInfo(DNSInfo {
                 can_fetch: Some(false),
                 can_bind_interface: Some(false),
-                dns_servers: Vec::new(),
-            })).unwrap();
+                ..Default::default()
+            }));
             return;
         }
     };
 
-    // Eliminate duplicates while preserving order
-    let dns_servers_length = dns_servers.len();
-    let dns_servers = dns_servers.into_iter().fold(Vec::with_capacity(dns_servers_length), |mut acc, x| {
-        if !acc.contains(&x) {
-            acc.push(x);
+    // Eliminate duplicates while preserving order, keeping every source each server came from. They're
+    // compared as addresses, so "8.8.8.8" and "8.8.8.8:53" or two spellings of an IPv6 address merge
+    let mut merged_servers: Vec<DNSServer> = Vec::with_capacity(dns_servers.len());
+
+    for (ip, source) in dns_servers {
+        let address = parse_server_ip(&ip);
+
+        match merged_servers.iter_mut().find(|server| parse_server_ip(&server.ip) == address) {
+            Some(server) => {
+                if !server.sources.contains(&source) {
+                    server.sources.push(source);
+                }
+            }
+            None => merged_servers.push(DNSServer {
+                wrong_family: is_wrong_family(&ip, interface_ip),
+                ip,
+                can_resolve: None,
+                latency_ms: None,
+                resolves_a: None,
+                resolves_aaaa: None,
+                resolves_mx: None,
+                used_tcp_fallback: None,
+                sources: vec![source],
+            }),
         }
-        acc
-    });
+    }
+
+    // A socket bound to an IPv4 address can't reach an IPv6 server or vice versa, so those aren't asked
+    let dns_servers: Vec<String> = merged_servers.iter()
+        .filter(|server| !server.wrong_family)
+        .map(|server| server.ip.clone())
+        .collect();
 
     let mut dns_info = DNSInfo {
         can_fetch: Some(true),
         can_bind_interface: None,
-        dns_servers: dns_servers.iter().map(|server| DNSServer {
-            ip: server.to_string(),
-            can_resolve: None,
-        }).collect(),
+        dns_servers: merged_servers,
+        doq_supported: None,
+        doq_server_tested: DOQ_SERVER.to_string(),
+        local_hosts_override: parse_hosts_file(TEST_DOMAIN),
     };
 
-    tx.send(FetchedDataMessage::DNSInfo(dns_info.clone())).unwrap();
+    if !send_or_stop(&tx, FetchedDataMessage::DNSInfo(dns_info.clone())) {
+        return;
+    }
+
+    // DoQ has its own (longer) timeout, so run it alongside the plain DNS checks
+    let doq_check = thread::spawn(move || check_doq_resolution(DOQ_SERVER, interface_ip));
 
-    // Now start checking if we can resolve DNS through them
+    // Now start checking if we can resolve DNS through them, all at once so a dead server
+    // only costs its own timeout rather than holding up the rest
+    let (result_tx, result_rx) = mpsc::channel();
 
     for server in dns_servers {
-        let can_resolve = check_dns_resolution(&server, interface_ip);
+        let result_tx = result_tx.clone();
+
+        thread::spawn(move || {
+            // A server that only answers A queries breaks IPv6 and mail while looking healthy. The three
+            // are asked at once, so a server that doesn't answer costs one timeout rather than three
+            let ((can_resolve, latency_ms, a_used_tcp), (aaaa, _, aaaa_used_tcp), (mx, _, mx_used_tcp)) = thread::scope(|scope| {
+                let aaaa = scope.spawn(|| check_aaaa_record(&server, interface_ip, timeout));
+                let mx = scope.spawn(|| check_mx_record(&server, interface_ip, timeout));
+
+                let failed = (CheckDNSResolutionResponse::Failure, None, false);
+                (check_a_record(&server, interface_ip, timeout), aaaa.join().unwrap_or(failed), mx.join().unwrap_or(failed))
+            });
 
+            let used_tcp = a_used_tcp || aaaa_used_tcp || mx_used_tcp;
+
+            let (resolves_aaaa, resolves_mx) = match can_resolve {
+                CheckDNSResolutionResponse::CannotBind => (None, None),
+                _ => (Some(aaaa == CheckDNSResolutionResponse::Success), Some(mx == CheckDNSResolutionResponse::Success)),
+            };
+
+            // The fetcher may have stopped early, leaving nobody to take the result
+            let _ = result_tx.send((server, can_resolve, latency_ms, resolves_aaaa, resolves_mx, used_tcp));
+        });
+    }
+
+    drop(result_tx);
+
+    for (server, can_resolve, latency_ms, resolves_aaaa, resolves_mx, used_tcp) in result_rx {
         if can_resolve == CheckDNSResolutionResponse::CannotBind {
-            tx.send(FetchedDataMessage::DNSInfo(DNSInfo {
+            send_or_stop(&tx, FetchedDataMessage::DNSInfo(DNSInfo {
                 can_fetch: Some(false),
                 can_bind_interface: Some(false),
-                dns_servers: Vec::new(),
-            })).unwrap();
+                ..Default::default()
+            }));
             return;
         }
 
         for dns_server in dns_info.dns_servers.iter_mut() {
             if dns_server.ip == server {
                 dns_server.can_resolve = Some(can_resolve == CheckDNSResolutionResponse::Success);
+                dns_server.latency_ms = latency_ms;
+                dns_server.resolves_a = dns_server.can_resolve;
+                dns_server.resolves_aaaa = resolves_aaaa;
+                dns_server.resolves_mx = resolves_mx;
+                dns_server.used_tcp_fallback = Some(used_tcp);
                 break;
             }
         }
 
-        tx.send(FetchedDataMessage::DNSInfo(dns_info.clone())).unwrap();
+        if !send_or_stop(&tx, FetchedDataMessage::DNSInfo(dns_info.clone())) {
+            return;
+        }
     }
+
+    dns_info.doq_supported = Some(doq_check.join().unwrap_or(false));
+
+    send_or_stop(&tx, FetchedDataMessage::DNSInfo(dns_info));
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum CheckDNSResolutionResponse {
+pub enum CheckDNSResolutionResponse {
     Success,
     Failure,
     CannotBind
 }
 
-fn check_dns_resolution(server: &str, ip_addr: IpAddr) -> CheckDNSResolutionResponse {
+fn check_a_record(server: &str, ip_addr: IpAddr, timeout: Duration) -> (CheckDNSResolutionResponse, Option<f64>, bool) {
+    check_dns_resolution(server, ip_addr, rustdns::Type::A, timeout)
+}
+
+fn check_aaaa_record(server: &str, ip_addr: IpAddr, timeout: Duration) -> (CheckDNSResolutionResponse, Option<f64>, bool) {
+    check_dns_resolution(server, ip_addr, rustdns::Type::AAAA, timeout)
+}
+
+fn check_mx_record(server: &str, ip_addr: IpAddr, timeout: Duration) -> (CheckDNSResolutionResponse, Option<f64>, bool) {
+    check_dns_resolution(server, ip_addr, rustdns::Type::MX, timeout)
+}
+
+/// Returns the result along with how long a successful answer took, in milliseconds, and
+/// whether the answer had to be fetched over TCP. example.com has all of A, AAAA and MX
+/// records, so an empty answer means something filtered it.
+fn check_dns_resolution(server: &str, ip_addr: IpAddr, record_type: rustdns::Type, timeout: Duration) -> (CheckDNSResolutionResponse, Option<f64>, bool) {
+    let started = Instant::now();
+
     // go to example.com and resolve it
+    match query_dns_server(server, ip_addr, TEST_DOMAIN, record_type, timeout) {
+        // RFC 1035 says a truncated answer has to be asked for again over TCP
+        Ok(resp) if resp.tc => {
+            let message = match build_query(TEST_DOMAIN, record_type) {
+                Ok(message) => message,
+                Err(error) => return (error, None, true)
+            };
+
+            match check_dns_resolution_tcp(server, ip_addr, &message, timeout) {
+                CheckDNSResolutionResponse::Success => {
+                    (CheckDNSResolutionResponse::Success, Some(started.elapsed().as_secs_f64() * 1000.0), true)
+                }
+                error => (error, None, true)
+            }
+        }
+        Ok(resp) if is_answered(&resp) => {
+            (CheckDNSResolutionResponse::Success, Some(started.elapsed().as_secs_f64() * 1000.0), false)
+        }
+        Ok(_) => (CheckDNSResolutionResponse::Failure, None, false),
+        Err(error) => (error, None, false)
+    }
+}
+
+fn is_answered(resp: &Message) -> bool {
+    resp.rcode == rustdns::Rcode::NoError && !resp.answers.is_empty()
+}
+
+/// Sends `message` over TCP from `ip_addr`. RFC 7766 frames each message on the stream
+/// with a two byte big-endian length, in both directions.
+fn check_dns_resolution_tcp(server: &str, ip_addr: IpAddr, message: &[u8], timeout: Duration) -> CheckDNSResolutionResponse {
+    // connect_from applies the timeout to reads and writes as well as connecting
+    let mut stream = match connect_from(ip_addr, server, 53, timeout) {
+        Ok(stream) => stream,
+        Err(_) => return CheckDNSResolutionResponse::Failure
+    };
+
+    let mut request = (message.len() as u16).to_be_bytes().to_vec();
+    request.extend_from_slice(message);
+
+    if stream.write_all(&request).is_err() {
+        return CheckDNSResolutionResponse::Failure;
+    }
+
+    let mut length = [0u8; 2];
+
+    if stream.read_exact(&mut length).is_err() {
+        return CheckDNSResolutionResponse::Failure;
+    }
+
+    let mut response = vec![0u8; u16::from_be_bytes(length) as usize];
+
+    if stream.read_exact(&mut response).is_err() {
+        return CheckDNSResolutionResponse::Failure;
+    }
+
+    match Message::from_slice(&response) {
+        Ok(resp) if is_answered(&resp) => CheckDNSResolutionResponse::Success,
+        _ => CheckDNSResolutionResponse::Failure
+    }
+}
+
+fn build_query(domain: &str, record_type: rustdns::Type) -> Result<Vec<u8>, CheckDNSResolutionResponse> {
     let mut message = Message::default();
-    message.add_question("example.com", rustdns::Type::A, rustdns::Class::Internet);
+    message.add_question(domain, record_type, rustdns::Class::Internet);
+
+    match message.to_vec() {
+        Ok(message) => Ok(message),
+        Err(_) => Err(CheckDNSResolutionResponse::Failure)
+    }
+}
 
-    let message = message.to_vec().unwrap();
+/// Reads a server however its source wrote it: a plain address, with a port ("8.8.8.8:53",
+/// "[2001:4860:4860::8888]:53"), with a DNS-over-TLS name ("1.1.1.1#cloudflare-dns.com") or with
+/// a zone ("fe80::1%eth0"), which the socket is already bound to. Queries always go to port 53.
+fn parse_server_ip(server: &str) -> Option<IpAddr> {
+    let server = server.split('#').next().unwrap_or(server).trim();
+
+    if let Ok(address) = server.parse::<SocketAddr>() {
+        return Some(address.ip());
+    }
+
+    server.split('%').next().unwrap_or(server).parse().ok()
+}
+
+/// Whether `server` is a different address family to the interface address queries are sent from
+fn is_wrong_family(server: &str, interface_ip: IpAddr) -> bool {
+    match parse_server_ip(server) {
+        Some(server_ip) => server_ip.is_ipv4() != interface_ip.is_ipv4(),
+        None => false
+    }
+}
+
+/// Sends a single query to `server` from a socket bound to `ip_addr` and returns the parsed response
+pub fn query_dns_server(server: &str, ip_addr: IpAddr, domain: &str, record_type: rustdns::Type, timeout: Duration) -> Result<Message, CheckDNSResolutionResponse> {
+    let message = build_query(domain, record_type)?;
 
     let socket = match ip_addr.is_ipv4() {
         true => {
-            // Set bind address to the interface IP
-            let bind_addr = SocketAddr::new(ip_addr, 5000);
+            // Set bind address to the interface IP, letting the OS pick a free port
+            let bind_addr = SocketAddr::new(ip_addr, 0);
 
             let socket = Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP));
 
             let socket = match socket {
                 Ok(socket) => socket,
-                Err(_) => return CheckDNSResolutionResponse::CannotBind
+                Err(_) => return Err(CheckDNSResolutionResponse::CannotBind)
             };
 
             if socket.bind(&SockAddr::from(bind_addr)).is_err() {
-                return CheckDNSResolutionResponse::CannotBind;
+                return Err(CheckDNSResolutionResponse::CannotBind);
             };
 
             socket
         },
         false => {
-            // Set bind address to the interface IP
-            let bind_addr = SocketAddr::new(ip_addr, 5000);
+            // Set bind address to the interface IP, letting the OS pick a free port
+            let bind_addr = SocketAddr::new(ip_addr, 0);
 
             let socket = Socket::new(Domain::IPV6, Type::DGRAM, Some(Protocol::UDP));
 
             let socket = match socket {
                 Ok(socket) => socket,
-                Err(_) => return CheckDNSResolutionResponse::CannotBind
+                Err(_) => return Err(CheckDNSResolutionResponse::CannotBind)
             };
 
             if socket.bind(&SockAddr::from(bind_addr)).is_err() {
-                return CheckDNSResolutionResponse::CannotBind;
+                return Err(CheckDNSResolutionResponse::CannotBind);
             };
 
             socket
         }
     };
 
+    let server_ip = match parse_server_ip(server) {
+        Some(server_ip) => server_ip,
+        None => return Err(CheckDNSResolutionResponse::Failure)
+    };
+
     // Now send the message
     let udp_socket = UdpSocket::from(socket);
 
-    // Set a timeout of 1 second
-    if udp_socket.set_read_timeout(Some(Duration::from_secs(1))).is_err() {
-        return CheckDNSResolutionResponse::Failure;
+    if udp_socket.set_read_timeout(Some(timeout)).is_err() || udp_socket.set_write_timeout(Some(timeout)).is_err() {
+        return Err(CheckDNSResolutionResponse::Failure);
     };
 
-    let resp =  udp_socket.connect(format!("{}:53", server));
+    let resp = udp_socket.connect(SocketAddr::new(server_ip, 53));
 
     match resp {
         Ok(_) => {},
-        Err(error) => {
-            return CheckDNSResolutionResponse::Failure
+        Err(_) => {
+            return Err(CheckDNSResolutionResponse::Failure)
         }
     };
 
     if udp_socket.send(&message).is_err() {
-        return CheckDNSResolutionResponse::Failure;
+        return Err(CheckDNSResolutionResponse::Failure);
     };
 
     let mut buf = [0u8; 512];
@@ -165,21 +355,238 @@ fn check_dns_resolution(server: &str, ip_addr: IpAddr) -> CheckDNSResolutionResp
     let resp_len = match udp_socket.recv(&mut buf) {
         Ok(resp_len) => resp_len,
         Err(_) => {
-            return CheckDNSResolutionResponse::Failure
+            return Err(CheckDNSResolutionResponse::Failure)
         }
     };
 
-    let resp = match Message::from_slice(&buf[..resp_len]) {
-        Ok(resp) => resp,
-        Err(_) => {
-            return CheckDNSResolutionResponse::Failure
+    match Message::from_slice(&buf[..resp_len]) {
+        Ok(resp) => Ok(resp),
+        Err(_) => Err(CheckDNSResolutionResponse::Failure)
+    }
+}
+
+/// Runs a query from the interactive DNS tool against each server in turn,
+/// sending back every answer record so the user can inspect them.
+pub fn run_dns_query(tx: Sender<DNSQueryResult>, servers: Vec<String>, interface: String, domain: String, record_type: rustdns::Type, timeout: Duration) {
+    // PTR lookups are far easier to type as a plain IP address
+    let domain = match (record_type, domain.parse::<IpAddr>()) {
+        (rustdns::Type::PTR, Ok(ip)) => reverse_lookup_name(ip),
+        _ => domain
+    };
+
+    let interface_ip = get_interface_ip(&interface);
+
+    for server in servers {
+        let result = match interface_ip {
+            Ok(interface_ip) => query_dns_server(&server, interface_ip, &domain, record_type, timeout),
+            Err(_) => Err(CheckDNSResolutionResponse::CannotBind)
+        };
+
+        let result = match result {
+            Ok(resp) => DNSQueryResult {
+                server,
+                rcode: Some(resp.rcode.to_string()),
+                answers: resp.answers.iter().map(|answer| answer.to_string().trim_end().to_string()).collect(),
+            },
+            Err(_) => DNSQueryResult {
+                server,
+                rcode: None,
+                answers: Vec::new(),
+            }
+        };
+
+        if tx.send(result).is_err() {
+            // The query popup has been closed
+            return;
         }
+    }
+}
+
+/// Builds the in-addr.arpa / ip6.arpa name used for reverse lookups of `ip`
+pub fn reverse_lookup_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let octets = ip.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", octets[3], octets[2], octets[1], octets[0])
+        }
+        IpAddr::V6(ip) => {
+            let mut name = String::with_capacity(72);
+            for octet in ip.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", octet & 0x0f, octet >> 4));
+            }
+            name.push_str("ip6.arpa");
+            name
+        }
+    }
+}
+
+/// Resolves example.com over DNS over QUIC, returning whether a valid answer came back
+fn check_doq_resolution(server: &str, ip_addr: IpAddr) -> bool {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build();
+
+    let runtime = match runtime {
+        Ok(runtime) => runtime,
+        Err(_) => return false
+    };
+
+    let supported = runtime.block_on(async {
+        matches!(tokio::time::timeout(DOQ_TIMEOUT, query_doq(server, ip_addr)).await, Ok(Ok(())))
+    });
+
+    // A lookup that outlived the timeout is still stuck on a blocking thread, so don't wait for it
+    runtime.shutdown_background();
+
+    supported
+}
+
+async fn query_doq(server: &str, ip_addr: IpAddr) -> Result<(), ()> {
+    // We can only talk to the server over the same address family as the interface. The lookup
+    // has to be async, a blocking one would hold up the runtime so the timeout could never fire
+    let server_addr = match tokio::net::lookup_host((server, DOQ_PORT)).await {
+        Ok(mut addrs) => addrs.find(|addr| addr.is_ipv4() == ip_addr.is_ipv4()),
+        Err(_) => None
+    };
+
+    let server_addr = match server_addr {
+        Some(server_addr) => server_addr,
+        None => return Err(())
     };
 
-    match resp.rcode == rustdns::Rcode::NoError {
-        true => CheckDNSResolutionResponse::Success,
-        false => CheckDNSResolutionResponse::Failure
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    let tls_config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_protocol_versions(&[&rustls::version::TLS13]);
+
+    let mut tls_config = match tls_config {
+        Ok(tls_config) => tls_config.with_root_certificates(roots).with_no_client_auth(),
+        Err(_) => return Err(())
+    };
+
+    tls_config.alpn_protocols = vec![b"doq".to_vec()];
+
+    let quic_config = match quinn::crypto::rustls::QuicClientConfig::try_from(tls_config) {
+        Ok(quic_config) => quic_config,
+        Err(_) => return Err(())
+    };
+
+    let mut endpoint = match Endpoint::client(SocketAddr::new(ip_addr, 0)) {
+        Ok(endpoint) => endpoint,
+        Err(_) => return Err(())
+    };
+
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(quic_config)));
+
+    let connection = match endpoint.connect(server_addr, server) {
+        Ok(connecting) => connecting.await,
+        Err(_) => return Err(())
+    };
+
+    let connection = match connection {
+        Ok(connection) => connection,
+        Err(_) => return Err(())
+    };
+
+    let (mut send, mut recv) = match connection.open_bi().await {
+        Ok(streams) => streams,
+        Err(_) => return Err(())
+    };
+
+    // RFC 9250 requires the message ID to be zero and a 2-byte length prefix
+    let mut message = Message {
+        id: 0,
+        ..Default::default()
+    };
+    message.add_question(TEST_DOMAIN, rustdns::Type::A, rustdns::Class::Internet);
+
+    let message = match message.to_vec() {
+        Ok(message) => message,
+        Err(_) => return Err(())
+    };
+
+    let mut query = (message.len() as u16).to_be_bytes().to_vec();
+    query.extend_from_slice(&message);
+
+    if send.write_all(&query).await.is_err() || send.finish().is_err() {
+        return Err(());
+    }
+
+    let response = match recv.read_to_end(u16::MAX as usize + 2).await {
+        Ok(response) => response,
+        Err(_) => return Err(())
+    };
+
+    connection.close(0u32.into(), b"done");
+
+    if response.len() < 2 {
+        return Err(());
+    }
+
+    let response_len = u16::from_be_bytes([response[0], response[1]]) as usize;
+
+    let resp = match response.get(2..2 + response_len) {
+        Some(resp) => Message::from_slice(resp),
+        None => return Err(())
+    };
+
+    match resp {
+        Ok(resp) if resp.rcode == rustdns::Rcode::NoError => Ok(()),
+        _ => Err(())
+    }
+}
+
+/// Looks `domain` up in the hosts file, returning the address it's pinned to if any.
+/// An entry here takes priority over DNS, which can make it look like DNS is broken.
+pub fn parse_hosts_file(domain: &str) -> Option<String> {
+    let file = match std::fs::read_to_string(HOSTS_FILE) {
+        Ok(file) => file,
+        Err(_) => return None
+    };
+
+    for line in file.lines() {
+        // Strip comments
+        let line = line.split('#').next().unwrap_or("");
+
+        let mut fields = line.split_whitespace();
+
+        let ip = match fields.next() {
+            Some(ip) => ip,
+            None => continue
+        };
+
+        if fields.any(|name| name.trim_end_matches('.').eq_ignore_ascii_case(domain)) {
+            return Some(ip.to_string());
+        }
+    }
+
+    None
+}
+
+/// Collects DNS servers from every configuration mechanism we know about, written as plain addresses.
+/// The same IP may appear more than once if several sources declare it.
+pub fn gather_all_dns_servers(interface: &str) -> Vec<(String, DnsSource)> {
+    let mut dns_servers = Vec::new();
+
+    let sources: [(DnsSource, Result<Vec<String>, ()>); 4] = [
+        (DnsSource::ResolvConf, get_dns_servers()),
+        (DnsSource::NetworkManager, get_networkmanager_dns_servers(interface)),
+        (DnsSource::SystemdResolved, get_systemd_resolved_dns_servers(interface)),
+        (DnsSource::EnvVar, get_env_dns_servers()),
+    ];
+
+    for (source, servers) in sources {
+        // A source being unavailable isn't an error, most systems only use one or two
+        if let Ok(servers) = servers {
+            // Written the same way whatever the source, so they can be compared and shown consistently
+            for server in servers.iter().filter_map(|server| parse_server_ip(server)) {
+                dns_servers.push((server.to_string(), source));
+            }
+        }
     }
+
+    dns_servers
 }
 
 fn get_dns_servers() -> Result<Vec<String>, ()> {
@@ -204,4 +611,241 @@ fn get_dns_servers() -> Result<Vec<String>, ()> {
     }
 
     Ok(dns_servers)
-}
\ No newline at end of file
+}
+
+fn get_networkmanager_dns_servers(interface: &str) -> Result<Vec<String>, ()> {
+    let output = Command::new("nmcli")
+        .arg("device")
+        .arg("show")
+        .arg(interface)
+        .output();
+
+    let output = match output {
+        Ok(output) => output,
+        Err(_) => return Err(())
+    };
+
+    let output_str = match std::str::from_utf8(&output.stdout) {
+        Ok(output_str) => output_str,
+        Err(_) => return Err(())
+    };
+
+    Ok(parse_nmcli_device_show(output_str))
+}
+
+/// Pulls the IPv4 and IPv6 DNS servers out of `nmcli device show <interface>`
+fn parse_nmcli_device_show(output: &str) -> Vec<String> {
+    let mut dns_servers = Vec::new();
+
+    // Lines look like "IP4.DNS[1]:                             192.168.1.1"
+    for line in output.lines() {
+        if !line.starts_with("IP4.DNS") && !line.starts_with("IP6.DNS") {
+            continue;
+        }
+
+        let server = match line.split_once(':') {
+            Some((_, server)) => server.trim(),
+            None => continue
+        };
+
+        if parse_server_ip(server).is_some() {
+            dns_servers.push(server.to_string());
+        }
+    }
+
+    dns_servers
+}
+
+fn get_systemd_resolved_dns_servers(interface: &str) -> Result<Vec<String>, ()> {
+    let output = Command::new("systemd-resolve")
+        .arg("--status")
+        .arg("--no-pager")
+        .output();
+
+    // systemd-resolve has been replaced by resolvectl on newer systems
+    let output = match output {
+        Ok(output) if output.status.success() => output,
+        _ => match Command::new("resolvectl").arg("status").arg("--no-pager").output() {
+            Ok(output) => output,
+            Err(_) => return Err(())
+        }
+    };
+
+    let output_str = match std::str::from_utf8(&output.stdout) {
+        Ok(output_str) => output_str,
+        Err(_) => return Err(())
+    };
+
+    Ok(parse_systemd_resolved_status(output_str, interface))
+}
+
+/// Pulls the servers out of the "Global" section and the section for our link.
+/// Additional servers are listed one per line underneath "DNS Servers:".
+fn parse_systemd_resolved_status(status: &str, interface: &str) -> Vec<String> {
+    let link_header = format!("({})", interface);
+
+    let mut dns_servers = Vec::new();
+    let mut in_relevant_section = false;
+    let mut in_server_list = false;
+
+    for line in status.lines() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("Global") {
+            in_relevant_section = true;
+            in_server_list = false;
+            continue;
+        }
+
+        if trimmed.starts_with("Link ") {
+            in_relevant_section = trimmed.ends_with(&link_header);
+            in_server_list = false;
+            continue;
+        }
+
+        if !in_relevant_section {
+            continue;
+        }
+
+        let candidate = match trimmed.strip_prefix("DNS Servers:") {
+            Some(rest) => {
+                in_server_list = true;
+                rest.trim()
+            }
+            None if in_server_list => trimmed,
+            None => continue
+        };
+
+        // DNS-over-TLS servers are written as "1.1.1.1#cloudflare-dns.com"
+        match parse_server_ip(candidate) {
+            Some(ip) => dns_servers.push(ip.to_string()),
+            None => in_server_list = false
+        }
+    }
+
+    dns_servers
+}
+
+fn get_env_dns_servers() -> Result<Vec<String>, ()> {
+    let value = match std::env::var("DNSSERVERS") {
+        Ok(value) => value,
+        Err(_) => return Err(())
+    };
+
+    Ok(parse_env_dns_servers(&value))
+}
+
+/// DNSSERVERS is a list separated by commas and/or whitespace. Anything that isn't an address is skipped
+fn parse_env_dns_servers(value: &str) -> Vec<String> {
+    value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|server| parse_server_ip(server).is_some())
+        .map(|server| server.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::Ipv4Addr;
+
+    #[test]
+    fn concurrent_checks_can_all_bind() {
+        // Hold the port the check used to bind to, so a regression can't pass by luck
+        let _port_5000 = UdpSocket::bind((Ipv4Addr::LOCALHOST, 5000));
+
+        // Nothing needs to answer: a refused or unanswered query is a Failure, only binding gives CannotBind
+        let checks: Vec<_> = ["127.0.0.1", "127.0.0.2"].into_iter()
+            .map(|server| thread::spawn(move || {
+                check_dns_resolution(server, IpAddr::V4(Ipv4Addr::LOCALHOST), rustdns::Type::A, Duration::from_millis(200))
+            }))
+            .collect();
+
+        for check in checks {
+            let (result, _, _) = check.join().unwrap();
+            assert_ne!(result, CheckDNSResolutionResponse::CannotBind);
+        }
+    }
+
+    #[test]
+    fn server_spellings_parse_to_the_same_address() {
+        let google = Some("8.8.8.8".parse::<IpAddr>().unwrap());
+        assert_eq!(parse_server_ip("8.8.8.8"), google);
+        assert_eq!(parse_server_ip("8.8.8.8:53"), google);
+        assert_eq!(parse_server_ip("8.8.8.8#dns.google"), google);
+
+        let google_v6 = Some("2001:4860:4860::8888".parse::<IpAddr>().unwrap());
+        assert_eq!(parse_server_ip("2001:4860:4860:0:0:0:0:8888"), google_v6);
+        assert_eq!(parse_server_ip("[2001:4860:4860::8888]:53"), google_v6);
+
+        assert_eq!(parse_server_ip("fe80::1%eth0"), Some("fe80::1".parse().unwrap()));
+        assert_eq!(parse_server_ip("dns.google"), None);
+    }
+
+    // Trimmed from `resolvectl status` on a laptop with Wi-Fi and a VPN
+    const RESOLVECTL_STATUS: &str = "\
+Global
+           Protocols: +LLMNR +mDNS -DNSOverTLS DNSSEC=no/unsupported
+    resolv.conf mode: stub
+  Current DNS Server: 9.9.9.9
+         DNS Servers: 9.9.9.9
+                      2620:fe::fe
+Fallback DNS Servers: 1.1.1.1#cloudflare-dns.com
+
+Link 2 (wlan0)
+    Current Scopes: DNS LLMNR/IPv4 LLMNR/IPv6
+         Protocols: +DefaultRoute +LLMNR -mDNS -DNSOverTLS DNSSEC=no/unsupported
+Current DNS Server: 192.168.1.1
+       DNS Servers: 192.168.1.1
+                    fe80::1%wlan0
+        DNS Domain: lan
+
+Link 3 (tun0)
+    Current Scopes: DNS
+         Protocols: -DefaultRoute +LLMNR -mDNS -DNSOverTLS DNSSEC=no/unsupported
+       DNS Servers: 10.8.0.1
+        DNS Domain: ~corp.example
+";
+
+    #[test]
+    fn systemd_resolved_status_global_and_link_servers() {
+        assert_eq!(
+            parse_systemd_resolved_status(RESOLVECTL_STATUS, "wlan0"),
+            vec!["9.9.9.9", "2620:fe::fe", "192.168.1.1", "fe80::1"],
+        );
+    }
+
+    #[test]
+    fn systemd_resolved_status_skips_other_links() {
+        assert_eq!(parse_systemd_resolved_status(RESOLVECTL_STATUS, "tun0"), vec!["9.9.9.9", "2620:fe::fe", "10.8.0.1"]);
+        assert_eq!(parse_systemd_resolved_status(RESOLVECTL_STATUS, "eth0"), vec!["9.9.9.9", "2620:fe::fe"]);
+    }
+
+    #[test]
+    fn nmcli_device_show_servers() {
+        // Trimmed from `nmcli device show wlan0`
+        let output = "\
+GENERAL.DEVICE:                         wlan0
+GENERAL.TYPE:                           wifi
+IP4.ADDRESS[1]:                         192.168.1.23/24
+IP4.GATEWAY:                            192.168.1.1
+IP4.DNS[1]:                             192.168.1.1
+IP4.DNS[2]:                             8.8.8.8
+IP4.DOMAIN[1]:                          lan
+IP6.ADDRESS[1]:                         fe80::1c2b:3dff:fe4e:5f60/64
+IP6.GATEWAY:                            fe80::1
+IP6.DNS[1]:                             2001:4860:4860::8888
+";
+
+        assert_eq!(parse_nmcli_device_show(output), vec!["192.168.1.1", "8.8.8.8", "2001:4860:4860::8888"]);
+    }
+
+    #[test]
+    fn env_servers_are_split_on_commas_and_whitespace() {
+        assert_eq!(
+            parse_env_dns_servers("8.8.8.8, 1.1.1.1:53\t2606:4700:4700::1111,,not-an-ip"),
+            vec!["8.8.8.8", "1.1.1.1:53", "2606:4700:4700::1111"],
+        );
+    }
+}
@@ -0,0 +1,446 @@
+use crate::internal_comms::{FetchedDataMessage, HTTPInfo, send_or_stop};
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+use crate::fetch_internet::{CLOUDFLARE_V4, CLOUDFLARE_V6};
+use crate::fetch_local::get_interface_ip;
+
+const GOOGLE_HOST: &str = "www.google.com";
+
+// Firefox's captive portal check, which answers with exactly "success\n" when nothing is in the way
+const PORTAL_CHECK_HOST: &str = "detectportal.firefox.com";
+const PORTAL_CHECK_PATH: &str = "/success.txt";
+const PORTAL_CHECK_BODY: &str = "success\n";
+
+// Android's check, which answers with an empty 204. Portals tend to serve their login page or redirect instead
+const NO_CONTENT_CHECK_HOST: &str = "connectivitycheck.gstatic.com";
+const NO_CONTENT_CHECK_PATH: &str = "/generate_204";
+
+const HTTP_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Portals tend to bounce through a couple of hops before the login page, but a loop shouldn't hang the check
+const MAX_PORTAL_REDIRECTS: usize = 5;
+
+pub struct HttpResponse {
+    pub status: u16,
+    // Header names are lowercased
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl HttpResponse {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+pub fn fetch_and_return_http_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(interface_ip) => interface_ip,
+        Err(_) => {
+            send_or_stop(&tx, FetchedDataMessage::HTTPInfo(HTTPInfo {
+                error: Some("No IP address.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let cloudflare_host = match interface_ip {
+        IpAddr::V4(_) => CLOUDFLARE_V4.to_string(),
+        IpAddr::V6(_) => CLOUDFLARE_V6.to_string(),
+    };
+
+    let cloudflare_check = thread::spawn(move || http_get(interface_ip, &cloudflare_host, "/"));
+    let google_check = thread::spawn(move || http_get(interface_ip, GOOGLE_HOST, "/"));
+    let portal_check = thread::spawn(move || http_get(interface_ip, PORTAL_CHECK_HOST, PORTAL_CHECK_PATH));
+    let no_content_check = thread::spawn(move || http_get(interface_ip, NO_CONTENT_CHECK_HOST, NO_CONTENT_CHECK_PATH));
+
+    let mut http_info = HTTPInfo {
+        can_access_1111: is_reachable(cloudflare_check.join().ok()),
+        can_access_google: is_reachable(google_check.join().ok()),
+        complete: true,
+        ..Default::default()
+    };
+
+    // Anything other than the expected answer means something responded in the real server's place.
+    // Either check going wrong counts, since DNS and TCP can look fine while every page is the login page
+    let portal_responses = [
+        (portal_check.join(), PORTAL_CHECK_HOST, PORTAL_CHECK_PATH, 200, PORTAL_CHECK_BODY),
+        (no_content_check.join(), NO_CONTENT_CHECK_HOST, NO_CONTENT_CHECK_PATH, 204, ""),
+    ];
+
+    for (response, host, path, expected_status, expected_body) in portal_responses {
+        let response = match response {
+            Ok(Ok(response)) => response,
+            _ => continue
+        };
+
+        let intercepted = response.status != expected_status || response.body != expected_body;
+        let already_found = http_info.captive_portal == Some(true);
+        http_info.captive_portal = Some(already_found || intercepted);
+
+        // The first check to be intercepted is enough to find the portal's login page
+        if intercepted && !already_found {
+            let (url, login_page) = follow_portal_redirects(interface_ip, host, path, response);
+            http_info.captive_portal_redirect_url = url;
+            http_info.captive_portal_title = parse_html_title(&login_page.body);
+        }
+    }
+
+    send_or_stop(&tx, FetchedDataMessage::HTTPInfo(http_info));
+}
+
+/// Follows a portal's redirects from the response `host` gave for `path`, returning the last URL
+/// redirected to (if any) and the last response. An HTTPS hop ends the chain, since until the user
+/// logs in the portal's certificate usually won't match anyway.
+fn follow_portal_redirects(local_ip: IpAddr, host: &str, path: &str, mut response: HttpResponse) -> (Option<String>, HttpResponse) {
+    let mut url = None;
+    let mut authority = host.to_string();
+    let mut current_path = path.to_string();
+
+    for _ in 0..MAX_PORTAL_REDIRECTS {
+        if !(300..400).contains(&response.status) {
+            break;
+        }
+
+        let location = match response.header("location") {
+            Some(location) => location.to_string(),
+            None => break
+        };
+
+        let location = resolve_location(&authority, &current_path, &location);
+
+        url = Some(location.clone());
+
+        let rest = match location.strip_prefix("http://") {
+            Some(rest) => rest,
+            None => break
+        };
+
+        let (next_authority, path) = match rest.split_once('/') {
+            Some((next_authority, path)) => (next_authority, format!("/{}", path)),
+            None => (rest, "/".to_string()),
+        };
+
+        // Portals often run their login page on a port of its own, e.g. "10.0.0.1:8002"
+        let (hostname, port) = match next_authority.rsplit_once(':').map(|(hostname, port)| (hostname, port.parse())) {
+            Some((hostname, Ok(port))) => (hostname, port),
+            _ => (next_authority, 80),
+        };
+
+        let mut stream = match connect_from(local_ip, hostname, port, HTTP_TIMEOUT) {
+            Ok(stream) => stream,
+            Err(_) => break
+        };
+
+        response = match send_http_request(&mut stream, next_authority, &path) {
+            Ok(response) => response,
+            Err(_) => break
+        };
+
+        authority = next_authority.to_string();
+        current_path = path;
+    }
+
+    (url, response)
+}
+
+/// Turns a Location header into an absolute URL. Relative redirects stay on the same host, and
+/// one without a leading '/' replaces the last segment of the current path, as a browser would.
+fn resolve_location(authority: &str, current_path: &str, location: &str) -> String {
+    let lowercase = location.to_ascii_lowercase();
+
+    if lowercase.starts_with("http://") || lowercase.starts_with("https://") {
+        return location.to_string();
+    }
+
+    // Scheme relative, "//portal.example/login"
+    if let Some(rest) = location.strip_prefix("//") {
+        return format!("http://{}", rest);
+    }
+
+    if location.starts_with('/') {
+        return format!("http://{}{}", authority, location);
+    }
+
+    let current_path = current_path.split(['?', '#']).next().unwrap_or(current_path);
+
+    // Only the query changes, "?next=/"
+    if location.starts_with('?') {
+        return format!("http://{}{}{}", authority, current_path, location);
+    }
+
+    let directory = match current_path.rfind('/') {
+        Some(index) => &current_path[..=index],
+        None => "/",
+    };
+
+    format!("http://{}{}{}", authority, directory, location)
+}
+
+/// The text of the page's <title>, with whitespace collapsed
+fn parse_html_title(body: &str) -> Option<String> {
+    // ASCII lowercasing keeps byte offsets the same, so positions found here work on the original
+    let lowercase = body.to_ascii_lowercase();
+
+    let tag_start = lowercase.find("<title")?;
+    let content_start = tag_start + lowercase[tag_start..].find('>')? + 1;
+    let content_end = content_start + lowercase[content_start..].find("</title")?;
+
+    let title = body[content_start..content_end].split_whitespace().collect::<Vec<&str>>().join(" ");
+
+    match title.is_empty() {
+        true => None,
+        false => Some(title),
+    }
+}
+
+/// None if no response came back at all, otherwise whether the status was a success.
+/// Plenty of sites just redirect plain HTTP to HTTPS, which still shows HTTP getting through
+fn is_reachable(response: Option<Result<HttpResponse, ()>>) -> Option<bool> {
+    match response {
+        Some(Ok(response)) => Some((200..400).contains(&response.status)),
+        _ => None
+    }
+}
+
+/// Makes a plain HTTP GET request from the given local address
+pub fn http_get(local_ip: IpAddr, host: &str, path: &str) -> Result<HttpResponse, ()> {
+    let mut stream = match connect_from(local_ip, host, 80, HTTP_TIMEOUT) {
+        Ok(stream) => stream,
+        Err(_) => return Err(())
+    };
+
+    send_http_request(&mut stream, host, path)
+}
+
+/// Opens a TCP connection with the source address pinned, so the request leaves through the chosen interface
+pub fn connect_from(local_ip: IpAddr, host: &str, port: u16, timeout: Duration) -> Result<TcpStream, ()> {
+    let addrs = match (host, port).to_socket_addrs() {
+        Ok(addrs) => addrs,
+        Err(_) => return Err(())
+    };
+
+    let remote = match addrs.into_iter().find(|addr| addr.is_ipv4() == local_ip.is_ipv4()) {
+        Some(remote) => remote,
+        None => return Err(())
+    };
+
+    let socket = match Socket::new(Domain::for_address(remote), Type::STREAM, Some(Protocol::TCP)) {
+        Ok(socket) => socket,
+        Err(_) => return Err(())
+    };
+
+    if socket.bind(&SockAddr::from(SocketAddr::new(local_ip, 0))).is_err() {
+        return Err(());
+    }
+
+    if socket.connect_timeout(&SockAddr::from(remote), timeout).is_err() {
+        return Err(());
+    }
+
+    if socket.set_read_timeout(Some(timeout)).is_err() || socket.set_write_timeout(Some(timeout)).is_err() {
+        return Err(());
+    }
+
+    Ok(TcpStream::from(socket))
+}
+
+/// Sends a GET over an already open stream (plain or TLS) and reads the whole response
+pub fn send_http_request<S: Read + Write>(stream: &mut S, host: &str, path: &str) -> Result<HttpResponse, ()> {
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: netcheck\r\nAccept: */*\r\nConnection: close\r\n\r\n",
+        path, host
+    );
+
+    if stream.write_all(request.as_bytes()).is_err() {
+        return Err(());
+    }
+
+    // Plenty of servers close without a TLS close_notify, so keep whatever arrived before the error
+    let mut response = Vec::new();
+    let mut buf = [0u8; 4096];
+
+    loop {
+        match stream.read(&mut buf) {
+            Ok(0) => break,
+            Ok(len) => response.extend_from_slice(&buf[..len]),
+            Err(_) => break,
+        }
+    }
+
+    parse_http_response(&response)
+}
+
+fn parse_http_response(response: &[u8]) -> Result<HttpResponse, ()> {
+    let response = String::from_utf8_lossy(response);
+
+    let (head, body) = match response.split_once("\r\n\r\n") {
+        Some(parts) => parts,
+        None => return Err(())
+    };
+
+    let mut lines = head.lines();
+
+    let status = match lines.next().unwrap_or("").split_whitespace().nth(1).map(|status| status.parse()) {
+        Some(Ok(status)) => status,
+        _ => return Err(())
+    };
+
+    let headers: Vec<(String, String)> = lines
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        .collect();
+
+    let chunked = headers.iter().any(|(name, value)| name == "transfer-encoding" && value.to_ascii_lowercase().contains("chunked"));
+
+    let body = match chunked {
+        true => decode_chunked(body),
+        false => body.to_string(),
+    };
+
+    Ok(HttpResponse {
+        status,
+        headers,
+        body,
+    })
+}
+
+fn decode_chunked(mut body: &str) -> String {
+    let mut decoded = String::new();
+
+    while let Some((size, rest)) = body.split_once("\r\n") {
+        // Chunk extensions after a ';' are allowed but meaningless to us
+        let size = size.split(';').next().unwrap_or("").trim();
+
+        let size = match usize::from_str_radix(size, 16) {
+            Ok(size) => size,
+            Err(_) => break
+        };
+
+        let (chunk, remaining) = match (rest.get(..size), rest.get(size..)) {
+            (Some(chunk), Some(remaining)) if size > 0 => (chunk, remaining),
+            _ => break
+        };
+
+        decoded.push_str(chunk);
+        body = remaining.trim_start_matches("\r\n");
+    }
+
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::{Ipv4Addr, TcpListener};
+
+    fn response(status: u16, headers: &[(&str, &str)], body: &str) -> HttpResponse {
+        HttpResponse {
+            status,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            body: body.to_string(),
+        }
+    }
+
+    #[test]
+    fn parses_status_headers_and_body() {
+        let parsed = parse_http_response(b"HTTP/1.1 302 Found\r\nLocation: /login\r\nContent-Length: 5\r\n\r\nhello").unwrap();
+
+        assert_eq!(parsed.status, 302);
+        assert_eq!(parsed.header("LOCATION"), Some("/login"));
+        assert_eq!(parsed.header("content-length"), Some("5"));
+        assert_eq!(parsed.body, "hello");
+    }
+
+    #[test]
+    fn parses_chunked_bodies() {
+        let parsed = parse_http_response(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n7\r\nsuccess\r\n1\r\n\n\r\n0\r\n\r\n").unwrap();
+        assert_eq!(parsed.body, "success\n");
+    }
+
+    #[test]
+    fn rejects_responses_without_a_status() {
+        assert!(parse_http_response(b"HTTP/1.1\r\n\r\n").is_err());
+        assert!(parse_http_response(b"HTTP/1.1 200 OK\r\nServer: cut off").is_err());
+    }
+
+    #[test]
+    fn decodes_chunks_with_extensions() {
+        assert_eq!(decode_chunked("4;name=value\r\nWiki\r\n5\r\npedia\r\n0\r\n\r\n"), "Wikipedia");
+    }
+
+    #[test]
+    fn stops_decoding_at_a_truncated_chunk() {
+        assert_eq!(decode_chunked("4\r\nWiki\r\nA\r\nped"), "Wiki");
+        assert_eq!(decode_chunked("zz\r\nWiki\r\n"), "");
+    }
+
+    #[test]
+    fn finds_the_title() {
+        assert_eq!(parse_html_title("<html><head><TITLE lang=\"en\">\n  Hotel   Wi-Fi\n</Title></head></html>"), Some("Hotel Wi-Fi".to_string()));
+        assert_eq!(parse_html_title("<title>  </title>"), None);
+        assert_eq!(parse_html_title("<html><body>No head</body></html>"), None);
+    }
+
+    #[test]
+    fn resolves_relative_locations() {
+        assert_eq!(resolve_location("10.0.0.1", "/success.txt", "https://portal.example/login"), "https://portal.example/login");
+        assert_eq!(resolve_location("10.0.0.1", "/success.txt", "//portal.example/login"), "http://portal.example/login");
+        assert_eq!(resolve_location("10.0.0.1", "/success.txt", "/login"), "http://10.0.0.1/login");
+        assert_eq!(resolve_location("10.0.0.1:8002", "/portal/start?x=1", "login.html"), "http://10.0.0.1:8002/portal/login.html");
+        assert_eq!(resolve_location("10.0.0.1", "/portal/start?x=1", "?step=2"), "http://10.0.0.1/portal/start?step=2");
+        assert_eq!(resolve_location("10.0.0.1", "", "login"), "http://10.0.0.1/login");
+    }
+
+    #[test]
+    fn no_redirect_is_left_alone() {
+        let (url, page) = follow_portal_redirects(IpAddr::V4(Ipv4Addr::LOCALHOST), "example.com", "/", response(200, &[], "<title>Login</title>"));
+
+        assert_eq!(url, None);
+        assert_eq!(page.body, "<title>Login</title>");
+    }
+
+    #[test]
+    fn https_redirect_ends_the_chain() {
+        let redirect = response(302, &[("location", "https://portal.example/login")], "");
+        let (url, page) = follow_portal_redirects(IpAddr::V4(Ipv4Addr::LOCALHOST), "example.com", "/", redirect);
+
+        assert_eq!(url.as_deref(), Some("https://portal.example/login"));
+        assert_eq!(page.status, 302);
+    }
+
+    #[test]
+    fn follows_a_relative_redirect_to_the_login_page() {
+        // Stands in for a portal serving its login page on a port of its own
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+        let authority = format!("127.0.0.1:{}", listener.local_addr().unwrap().port());
+
+        let portal = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+
+            let mut request = [0u8; 1024];
+            let len = stream.read(&mut request).unwrap();
+
+            stream.write_all(b"HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n<title>Guest Login</title>").unwrap();
+            String::from_utf8_lossy(&request[..len]).into_owned()
+        });
+
+        let redirect = response(302, &[("location", "login")], "");
+        let (url, page) = follow_portal_redirects(IpAddr::V4(Ipv4Addr::LOCALHOST), &authority, "/portal/start", redirect);
+
+        assert_eq!(url, Some(format!("http://{}/portal/login", authority)));
+        assert_eq!(parse_html_title(&page.body).as_deref(), Some("Guest Login"));
+        assert!(portal.join().unwrap().starts_with("GET /portal/login HTTP/1.1\r\n"));
+    }
+}
@@ -0,0 +1,856 @@
+use crate::internal_comms::{FetchedDataMessage, HTTPSInfo, send_or_stop};
+
+use std::net::{IpAddr, TcpStream};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::client::WebPkiServerVerifier;
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, ServerName, TrustAnchor, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+
+use crate::fetch_http::{connect_from, HttpResponse};
+use crate::fetch_internet::{https_get, https_request, CLOUDFLARE_V4, CLOUDFLARE_V6};
+use crate::fetch_local::get_interface_ip;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+const GOOGLE_HOST: &str = "www.google.com";
+
+// www.google.com leaves HSTS to the preload list and never sends the header, so this asks a host that does
+pub const HSTS_HOST: &str = "www.cloudflare.com";
+// The preload list has the registrable domain, with includeSubDomains covering www
+const HSTS_PRELOAD_DOMAIN: &str = "cloudflare.com";
+const HSTS_PRELOAD_API_HOST: &str = "hstspreload.org";
+// Below this a policy can lapse between visits, and the preload list won't accept it either
+pub const HSTS_MIN_MAX_AGE: u64 = 180 * 24 * 60 * 60;
+
+// The headers securityheaders.com grades on besides HSTS, each worth the same share of the score,
+// and the short names they go by where there isn't room for the whole thing
+const SECURITY_HEADERS: [(&str, &str); 5] = [
+    ("content-security-policy", "CSP"),
+    ("x-frame-options", "XFO"),
+    ("x-content-type-options", "XCTO"),
+    ("referrer-policy", "RP"),
+    ("permissions-policy", "PP"),
+];
+// Every value Referrer-Policy can take (W3C Referrer Policy), of which only unsafe-url leaks the full URL
+const REFERRER_POLICIES: [&str; 8] = [
+    "no-referrer",
+    "no-referrer-when-downgrade",
+    "origin",
+    "origin-when-cross-origin",
+    "same-origin",
+    "strict-origin",
+    "strict-origin-when-cross-origin",
+    "unsafe-url",
+];
+
+/// SHA-256 of the SubjectPublicKeyInfo of each root the 1.1.1.1 certificate is expected to chain up
+/// to, the same value an HPKP pin-sha256 is taken over.
+///
+/// Cloudflare reissues the 1.1.1.1 leaf every few months, always from DigiCert, so the pin is on
+/// DigiCert's TLS roots rather than on a leaf that would go stale with each rotation. This has to be
+/// updated if Cloudflare moves 1.1.1.1 to another CA or DigiCert retires one of these roots,
+/// otherwise every network will look like it's intercepting TLS. Check the chain from a network
+/// known not to intercept traffic with `openssl s_client -connect 1.1.1.1:443 -showcerts`.
+const CLOUDFLARE_1111_ROOT_PINS: [&str; 4] = [
+    // DigiCert Global Root G2
+    "8B:B5:93:A9:3B:E1:D0:E8:A8:22:BB:88:7C:54:78:90:C3:E7:06:AA:D2:DA:B7:62:54:F9:7F:B3:6B:82:FC:26",
+    // DigiCert Global Root G3
+    "B9:4C:19:83:00:CE:C5:C0:57:AD:07:27:B7:0B:BE:91:81:69:92:25:64:39:A7:B3:2F:45:98:11:9D:DA:9C:97",
+    // DigiCert TLS RSA4096 Root G5
+    "6A:97:B5:1C:82:19:E9:3E:5D:EC:64:BA:D5:80:6C:DE:B0:F8:35:5B:E4:7E:75:70:10:B7:02:45:6E:01:AA:FD",
+    // DigiCert TLS ECC P384 Root G5
+    "A0:2F:AF:A1:92:C8:CB:81:CB:13:41:55:4F:9C:05:B7:1C:CA:2A:89:0B:0D:12:98:D6:83:64:7C:96:1E:FB:DF",
+];
+
+// Where the common distributions keep their bundle of trusted CAs
+const SYSTEM_CA_BUNDLES: [&str; 4] = [
+    "/etc/ssl/certs/ca-certificates.crt",
+    "/etc/pki/tls/certs/ca-bundle.crt",
+    "/etc/ssl/ca-bundle.pem",
+    "/etc/ssl/cert.pem",
+];
+
+// X.520 attribute types for the common name and organisation
+const OID_COMMON_NAME: [u8; 3] = [0x55, 0x04, 0x03];
+const OID_ORGANISATION: [u8; 3] = [0x55, 0x04, 0x0A];
+
+/// The directives of a Strict-Transport-Security header (RFC 6797)
+#[derive(Debug, PartialEq)]
+struct HstsPolicy {
+    max_age: u64,
+    include_subdomains: bool,
+    preload: bool,
+}
+
+/// What HSTS_HOST's response and the preload list said
+struct HstsResult {
+    // None if the header was missing or invalid
+    policy: Option<HstsPolicy>,
+    preload_listed: Option<bool>,
+    security_score: u8,
+    missing_headers: Vec<String>,
+}
+
+/// Details of the certificate the server presented, whether or not it could be trusted
+struct PeerCertificate {
+    fingerprint: String,
+    subject: Option<String>,
+    issuer: Option<String>,
+    expires: Option<String>,
+}
+
+struct HandshakeResult {
+    // Whether the chain validates against the system trust store
+    chain_valid: bool,
+    // Whether it also validates against the public roots built into netcheck
+    publicly_trusted: bool,
+    // SHA-256 of the SubjectPublicKeyInfo of the public root it validates up to
+    root_fingerprint: Option<String>,
+    tls_version: Option<String>,
+    certificate: Option<PeerCertificate>,
+}
+
+pub fn fetch_and_return_https_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(interface_ip) => interface_ip,
+        Err(_) => {
+            send_or_stop(&tx, FetchedDataMessage::HTTPSInfo(HTTPSInfo {
+                error: Some("No IP address.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let cloudflare = match interface_ip {
+        IpAddr::V4(_) => IpAddr::V4(CLOUDFLARE_V4),
+        IpAddr::V6(_) => IpAddr::V6(CLOUDFLARE_V6),
+    };
+
+    let cloudflare_check = thread::spawn(move || {
+        tls_handshake(interface_ip, &cloudflare.to_string(), ServerName::from(cloudflare))
+    });
+
+    let google_check = thread::spawn(move || {
+        match ServerName::try_from(GOOGLE_HOST) {
+            Ok(server_name) => tls_handshake(interface_ip, GOOGLE_HOST, server_name),
+            Err(_) => Err(())
+        }
+    });
+
+    let hsts_check = thread::spawn(move || check_hsts(interface_ip));
+
+    let mut https_info = HTTPSInfo {
+        complete: true,
+        ..Default::default()
+    };
+
+    if let Ok(Ok(hsts)) = hsts_check.join() {
+        record_hsts(&mut https_info, hsts);
+    }
+
+    if let Ok(Ok(handshake)) = google_check.join() {
+        https_info.can_access_google = Some(handshake.chain_valid);
+        https_info.mitm_detected = Some(roots_in_local_ca(&handshake));
+    }
+
+    let handshake = match cloudflare_check.join() {
+        Ok(Ok(handshake)) => handshake,
+        _ => {
+            send_or_stop(&tx, FetchedDataMessage::HTTPSInfo(https_info));
+            return;
+        }
+    };
+
+    record_1111_handshake(&mut https_info, handshake, expected_fingerprint());
+
+    send_or_stop(&tx, FetchedDataMessage::HTTPSInfo(https_info));
+}
+
+/// Fills in what the 1.1.1.1 handshake showed, after anything the Google one already found
+fn record_1111_handshake(https_info: &mut HTTPSInfo, handshake: HandshakeResult, expected_leaf: Option<String>) {
+    // A leaf pin names the exact certificate, so it holds whatever the trust stores think of the chain.
+    // The root is only found for a chain the public roots accept, so without a leaf pin an expired
+    // certificate or a wrong clock is left to show up as invalid rather than failing the root pins
+    let pin_mismatch = match expected_leaf {
+        Some(ref expected) => !matches_leaf_pin(&handshake, expected),
+        None => handshake.publicly_trusted && !matches_root_pin(&handshake),
+    };
+
+    let intercepted = roots_in_local_ca(&handshake) || pin_mismatch || https_info.mitm_detected == Some(true);
+
+    https_info.can_access_1111 = Some(handshake.chain_valid);
+    https_info.tls_version = handshake.tls_version;
+
+    if let Some(certificate) = handshake.certificate {
+        https_info.cert_fingerprint_seen = Some(certificate.fingerprint);
+        https_info.cert_subject = certificate.subject;
+        https_info.cert_issuer = certificate.issuer;
+        https_info.cert_expires = certificate.expires;
+    }
+
+    https_info.mitm_detected = Some(intercepted);
+}
+
+/// Fetches HSTS_HOST's front page for its Strict-Transport-Security header, and asks whether the
+/// domain is preloaded. The rest of its security headers are scored from the same response.
+/// Fails if HSTS_HOST couldn't be reached at all.
+fn check_hsts(interface_ip: IpAddr) -> Result<HstsResult, ()> {
+    let response = match https_request(interface_ip, HSTS_HOST, 443, "/") {
+        Ok(response) => response,
+        Err(_) => return Err(())
+    };
+
+    let policy = response.header("strict-transport-security").and_then(parse_hsts_header);
+
+    let preload_listed = match https_get(interface_ip, HSTS_PRELOAD_API_HOST, 443, &format!("/api/v2/status?domain={}", HSTS_PRELOAD_DOMAIN)) {
+        Ok(body) => parse_preload_status(&body),
+        Err(_) => None,
+    };
+
+    let (security_score, missing_headers) = score_security_headers(&response);
+
+    Ok(HstsResult { policy, preload_listed, security_score, missing_headers })
+}
+
+fn record_hsts(https_info: &mut HTTPSInfo, hsts: HstsResult) {
+    https_info.hsts_host = Some(HSTS_HOST.to_string());
+    https_info.hsts_preload_listed = hsts.preload_listed;
+    https_info.security_score = Some(hsts.security_score);
+    https_info.missing_headers = hsts.missing_headers;
+
+    if let Some(policy) = hsts.policy {
+        https_info.hsts_max_age = Some(policy.max_age);
+        https_info.hsts_include_subdomains = Some(policy.include_subdomains);
+        https_info.hsts_preload = Some(policy.preload);
+    }
+}
+
+/// Reads the directives out of a Strict-Transport-Security value. Names are case insensitive and
+/// values may be quoted. Without a usable max-age the whole header is to be ignored.
+fn parse_hsts_header(value: &str) -> Option<HstsPolicy> {
+    let mut max_age = None;
+    let mut include_subdomains = false;
+    let mut preload = false;
+
+    for directive in value.split(';') {
+        let (name, value) = match directive.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+            None => (directive.trim(), None),
+        };
+
+        match name.to_ascii_lowercase().as_str() {
+            "max-age" => max_age = value.and_then(|value| value.parse::<u64>().ok()),
+            "includesubdomains" => include_subdomains = true,
+            "preload" => preload = true,
+            _ => {}
+        }
+    }
+
+    Some(HstsPolicy {
+        max_age: max_age?,
+        include_subdomains,
+        preload,
+    })
+}
+
+/// Scores the response's SECURITY_HEADERS out of 100, returning the ones that are missing or set to
+/// something that doesn't protect anything
+fn score_security_headers(response: &HttpResponse) -> (u8, Vec<String>) {
+    let mut score = 0;
+    let mut missing_headers = Vec::new();
+
+    for (header, _) in SECURITY_HEADERS {
+        let configured = match response.header(header).map(str::trim) {
+            Some(value) => security_header_configured(response, header, value),
+            None => false,
+        };
+
+        match configured {
+            true => score += 100 / SECURITY_HEADERS.len() as u8,
+            false => missing_headers.push(header.to_string()),
+        }
+    }
+
+    (score, missing_headers)
+}
+
+/// The short name of one of SECURITY_HEADERS, for the HTTPS panel
+pub fn security_header_abbreviation(header: &str) -> &str {
+    SECURITY_HEADERS.iter()
+        .find(|(name, _)| *name == header)
+        .map(|(_, abbreviation)| *abbreviation)
+        .unwrap_or(header)
+}
+
+fn security_header_configured(response: &HttpResponse, header: &str, value: &str) -> bool {
+    match header {
+        // CSP's frame-ancestors supersedes it, so a policy with one doesn't need both
+        "x-frame-options" => {
+            value.eq_ignore_ascii_case("deny")
+                || value.eq_ignore_ascii_case("sameorigin")
+                || response.header("content-security-policy").is_some_and(|csp| csp.to_ascii_lowercase().contains("frame-ancestors"))
+        }
+        "x-content-type-options" => value.eq_ignore_ascii_case("nosniff"),
+        // Browsers go by the last policy they recognise in the list
+        "referrer-policy" => {
+            let policy = value.split(',')
+                .map(|policy| policy.trim().to_ascii_lowercase())
+                .rev()
+                .find(|policy| REFERRER_POLICIES.contains(&policy.as_str()));
+
+            matches!(policy, Some(policy) if policy != "unsafe-url")
+        }
+        _ => !value.is_empty(),
+    }
+}
+
+/// Whether hstspreload.org's status says the domain is on Chrome's list. Pending and rejected
+/// submissions aren't, and neither is a domain it's never heard of ("unknown").
+fn parse_preload_status(body: &str) -> Option<bool> {
+    let status: serde_json::Value = serde_json::from_str(body).ok()?;
+
+    status.get("status")?.as_str().map(|status| status == "preloaded")
+}
+
+/// Checks the 1.1.1.1 leaf against the fingerprint given in NETCHECK_1111_FINGERPRINT. No certificate
+/// to compare counts as a mismatch.
+fn matches_leaf_pin(handshake: &HandshakeResult, expected_leaf: &str) -> bool {
+    match handshake.certificate {
+        Some(ref certificate) => certificate.fingerprint.eq_ignore_ascii_case(expected_leaf),
+        None => false,
+    }
+}
+
+/// Checks the root of a publicly trusted 1.1.1.1 chain against CLOUDFLARE_1111_ROOT_PINS. A chain
+/// whose root couldn't be found counts as a mismatch.
+fn matches_root_pin(handshake: &HandshakeResult) -> bool {
+    match handshake.root_fingerprint {
+        Some(ref root) => CLOUDFLARE_1111_ROOT_PINS.iter().any(|pin| pin.eq_ignore_ascii_case(root)),
+        None => false,
+    }
+}
+
+/// Both hosts have certificates from public CAs, so one that only validates thanks to a CA added to
+/// this machine means something is re-signing traffic. A chain that fails both checks is just invalid
+/// (expired, a wrong clock, the wrong name) and shows up as an invalid certificate instead.
+fn roots_in_local_ca(handshake: &HandshakeResult) -> bool {
+    handshake.chain_valid && !handshake.publicly_trusted
+}
+
+/// NETCHECK_1111_FINGERPRINT pins the SHA-256 of the exact 1.1.1.1 leaf in place of the embedded
+/// root pins, e.g. to follow a CA change before a release with new pins is out
+fn expected_fingerprint() -> Option<String> {
+    let fingerprint = std::env::var("NETCHECK_1111_FINGERPRINT").ok()?;
+
+    // Accept both "AB:CD:..." and plain hex
+    let fingerprint: String = fingerprint.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+
+    match fingerprint.len() {
+        64 => Some(format_fingerprint(&fingerprint)),
+        _ => None,
+    }
+}
+
+/// Completes a TLS handshake from the interface's address without sending any request.
+/// An untrusted certificate doesn't abort the handshake, so it can still be inspected.
+fn tls_handshake(local_ip: IpAddr, host: &str, server_name: ServerName<'static>) -> Result<HandshakeResult, ()> {
+    let mut stream = match connect_from(local_ip, host, 443, HANDSHAKE_TIMEOUT) {
+        Ok(stream) => stream,
+        Err(_) => return Err(())
+    };
+
+    let provider = Arc::new(rustls::crypto::ring::default_provider());
+
+    let verifier = match RecordingVerifier::new(provider.clone()) {
+        Ok(verifier) => Arc::new(verifier),
+        Err(_) => return Err(())
+    };
+
+    let tls_config = match rustls::ClientConfig::builder_with_provider(provider).with_safe_default_protocol_versions() {
+        Ok(tls_config) => tls_config
+            .dangerous()
+            .with_custom_certificate_verifier(verifier.clone())
+            .with_no_client_auth(),
+        Err(_) => return Err(())
+    };
+
+    let mut connection = match rustls::ClientConnection::new(Arc::new(tls_config), server_name) {
+        Ok(connection) => connection,
+        Err(_) => return Err(())
+    };
+
+    while connection.is_handshaking() {
+        if connection.complete_io(&mut stream).is_err() {
+            return Err(());
+        }
+    }
+
+    let tls_version = connection.protocol_version().map(|version| match version {
+        rustls::ProtocolVersion::TLSv1_3 => "TLS 1.3".to_string(),
+        rustls::ProtocolVersion::TLSv1_2 => "TLS 1.2".to_string(),
+        other => format!("{:?}", other),
+    });
+
+    let certificate = connection.peer_certificates()
+        .and_then(|certificates| certificates.first())
+        .map(inspect_certificate);
+
+    close(&mut connection, &mut stream);
+
+    Ok(HandshakeResult {
+        chain_valid: verifier.chain_valid(),
+        publicly_trusted: verifier.publicly_trusted(),
+        root_fingerprint: verifier.root_fingerprint(),
+        tls_version,
+        certificate,
+    })
+}
+
+/// Sends close_notify without waiting around for the server's reply
+fn close(connection: &mut rustls::ClientConnection, stream: &mut TcpStream) {
+    connection.send_close_notify();
+    let _ = connection.write_tls(stream);
+}
+
+/// Runs the normal webpki checks against both the system trust store and the public roots built
+/// into netcheck, but only records the outcomes, letting the handshake carry on either way.
+/// A chain only the system store accepts roots in a CA that was added locally.
+#[derive(Debug)]
+struct RecordingVerifier {
+    inner: Arc<WebPkiServerVerifier>,
+    public: Arc<WebPkiServerVerifier>,
+    provider: Arc<CryptoProvider>,
+    public_roots: Arc<rustls::RootCertStore>,
+    chain_valid: Mutex<bool>,
+    publicly_trusted: Mutex<bool>,
+    root_fingerprint: Mutex<Option<String>>,
+}
+
+impl RecordingVerifier {
+    fn new(provider: Arc<CryptoProvider>) -> Result<Self, ()> {
+        let inner = match WebPkiServerVerifier::builder_with_provider(Arc::new(system_roots()), provider.clone()).build() {
+            Ok(inner) => inner,
+            Err(_) => return Err(())
+        };
+
+        let mut public_roots = rustls::RootCertStore::empty();
+        public_roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+        let public_roots = Arc::new(public_roots);
+
+        let public = match WebPkiServerVerifier::builder_with_provider(public_roots.clone(), provider.clone()).build() {
+            Ok(public) => public,
+            Err(_) => return Err(())
+        };
+
+        Ok(RecordingVerifier {
+            inner,
+            public,
+            provider,
+            public_roots,
+            chain_valid: Mutex::new(false),
+            publicly_trusted: Mutex::new(false),
+            root_fingerprint: Mutex::new(None),
+        })
+    }
+
+    fn chain_valid(&self) -> bool {
+        *self.chain_valid.lock().unwrap()
+    }
+
+    fn publicly_trusted(&self) -> bool {
+        *self.publicly_trusted.lock().unwrap()
+    }
+
+    fn root_fingerprint(&self) -> Option<String> {
+        self.root_fingerprint.lock().unwrap().clone()
+    }
+
+    /// Builds the path from the leaf to one of the public roots, the same way the public verifier
+    /// does, to find out which root it ends at
+    fn public_root(&self, end_entity: &CertificateDer<'_>, intermediates: &[CertificateDer<'_>], now: UnixTime) -> Option<String> {
+        let end_entity = webpki::EndEntityCert::try_from(end_entity).ok()?;
+
+        let path = end_entity.verify_for_usage(
+            self.provider.signature_verification_algorithms.all,
+            &self.public_roots.roots,
+            intermediates,
+            now,
+            webpki::KeyUsage::server_auth(),
+            None,
+            None,
+        ).ok()?;
+
+        Some(spki_fingerprint(path.anchor()))
+    }
+}
+
+impl ServerCertVerifier for RecordingVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        intermediates: &[CertificateDer<'_>],
+        server_name: &ServerName<'_>,
+        ocsp_response: &[u8],
+        now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let result = self.inner.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now);
+        *self.chain_valid.lock().unwrap() = result.is_ok();
+
+        let result = self.public.verify_server_cert(end_entity, intermediates, server_name, ocsp_response, now);
+        *self.publicly_trusted.lock().unwrap() = result.is_ok();
+
+        *self.root_fingerprint.lock().unwrap() = match result {
+            Ok(_) => self.public_root(end_entity, intermediates, now),
+            Err(_) => None,
+        };
+
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls12_signature(message, cert, dss)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        self.inner.verify_tls13_signature(message, cert, dss)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.inner.supported_verify_schemes()
+    }
+}
+
+/// Loads the first CA bundle the system has, falling back to the public roots built into netcheck
+fn system_roots() -> rustls::RootCertStore {
+    let mut roots = rustls::RootCertStore::empty();
+
+    for path in SYSTEM_CA_BUNDLES {
+        if let Ok(certificates) = CertificateDer::pem_file_iter(path) {
+            roots.add_parsable_certificates(certificates.flatten());
+        }
+
+        if !roots.is_empty() {
+            return roots;
+        }
+    }
+
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+    roots
+}
+
+fn inspect_certificate(der: &CertificateDer<'_>) -> PeerCertificate {
+    let mut certificate = PeerCertificate {
+        fingerprint: sha256_fingerprint(der.as_ref()),
+        subject: None,
+        issuer: None,
+        expires: None,
+    };
+
+    if let Some((issuer, not_after, subject)) = parse_tbs_certificate(der.as_ref()) {
+        certificate.issuer = name_summary(issuer);
+        certificate.expires = format_time(not_after);
+        certificate.subject = name_summary(subject);
+    }
+
+    certificate
+}
+
+/// Trust anchors keep only the contents of the SubjectPublicKeyInfo, so it has to be wrapped back
+/// up in its SEQUENCE before hashing to give the usual SPKI fingerprint
+fn spki_fingerprint(anchor: &TrustAnchor<'_>) -> String {
+    let contents = anchor.subject_public_key_info.as_ref();
+
+    let mut spki = vec![0x30];
+    match contents.len() {
+        len @ 0..=0x7F => spki.push(len as u8),
+        len => {
+            let len_bytes: Vec<u8> = len.to_be_bytes().into_iter().skip_while(|byte| *byte == 0).collect();
+            spki.push(0x80 | len_bytes.len() as u8);
+            spki.extend(len_bytes);
+        }
+    }
+    spki.extend_from_slice(contents);
+
+    sha256_fingerprint(&spki)
+}
+
+fn sha256_fingerprint(data: &[u8]) -> String {
+    let digest = ring::digest::digest(&ring::digest::SHA256, data);
+    let hex: String = digest.as_ref().iter().map(|byte| format!("{:02X}", byte)).collect();
+
+    format_fingerprint(&hex)
+}
+
+/// Turns plain hex into the usual colon separated, upper case form
+fn format_fingerprint(hex: &str) -> String {
+    hex.to_ascii_uppercase()
+        .as_bytes()
+        .chunks(2)
+        .map(|pair| String::from_utf8_lossy(pair).to_string())
+        .collect::<Vec<String>>()
+        .join(":")
+}
+
+/// Splits one DER element off the front of `data`, returning its tag, contents and whatever follows
+fn read_der(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+    let tag = *data.first()?;
+    let first_len = *data.get(1)? as usize;
+
+    let (len, header_len) = match first_len {
+        0..=0x7F => (first_len, 2),
+        0x81..=0x84 => {
+            let len_bytes = first_len & 0x7F;
+            let len = data.get(2..2 + len_bytes)?.iter().fold(0usize, |len, byte| (len << 8) | *byte as usize);
+            (len, 2 + len_bytes)
+        }
+        _ => return None,
+    };
+
+    let contents = data.get(header_len..header_len + len)?;
+    Some((tag, contents, &data[header_len + len..]))
+}
+
+/// Pulls the issuer, notAfter and subject out of a certificate's TBSCertificate
+fn parse_tbs_certificate(der: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let (_, certificate, _) = read_der(der)?;
+    let (_, tbs, _) = read_der(certificate)?;
+
+    // The explicit [0] version is absent on v1 certificates
+    let mut rest = tbs;
+    if rest.first() == Some(&0xA0) {
+        rest = read_der(rest)?.2;
+    }
+
+    let (_, _serial, rest) = read_der(rest)?;
+    let (_, _signature_algorithm, rest) = read_der(rest)?;
+    let (_, issuer, rest) = read_der(rest)?;
+    let (_, validity, rest) = read_der(rest)?;
+    let (_, subject, _) = read_der(rest)?;
+
+    let (_, _not_before, validity) = read_der(validity)?;
+    let (_, not_after, _) = read_der(validity)?;
+
+    Some((issuer, not_after, subject))
+}
+
+/// Gives the common name of an X.501 Name, or the organisation if there isn't one
+fn name_summary(mut name: &[u8]) -> Option<String> {
+    let mut organisation = None;
+
+    while let Some((_, set, rest)) = read_der(name) {
+        name = rest;
+
+        let (_, attribute, _) = read_der(set)?;
+        let (_, oid, attribute) = read_der(attribute)?;
+        let (_, value, _) = read_der(attribute)?;
+
+        let value = String::from_utf8_lossy(value).to_string();
+
+        if oid == OID_COMMON_NAME {
+            return Some(value);
+        }
+
+        if oid == OID_ORGANISATION {
+            organisation = Some(value);
+        }
+    }
+
+    organisation
+}
+
+/// Formats a UTCTime ("YYMMDDHHMMSSZ") or GeneralizedTime ("YYYYMMDDHHMMSSZ") as a date
+fn format_time(time: &[u8]) -> Option<String> {
+    let time = std::str::from_utf8(time).ok()?;
+
+    let (year, rest) = match time.len() {
+        13 => {
+            // RFC 5280 puts two digit years of 50 and above in the 1900s
+            let year: u32 = time.get(..2)?.parse().ok()?;
+            (if year >= 50 { 1900 + year } else { 2000 + year }, &time[2..])
+        }
+        15 => (time.get(..4)?.parse().ok()?, &time[4..]),
+        _ => return None,
+    };
+
+    Some(format!("{}-{}-{}", year, rest.get(..2)?, rest.get(2..4)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEAF_FINGERPRINT: &str = "AA:BB:CC:DD:EE:FF:00:11:22:33:44:55:66:77:88:99:AA:BB:CC:DD:EE:FF:00:11:22:33:44:55:66:77:88:99";
+
+    fn handshake(chain_valid: bool, publicly_trusted: bool, root_fingerprint: Option<&str>) -> HandshakeResult {
+        HandshakeResult {
+            chain_valid,
+            publicly_trusted,
+            root_fingerprint: root_fingerprint.map(str::to_string),
+            tls_version: Some("TLS 1.3".to_string()),
+            certificate: Some(PeerCertificate {
+                fingerprint: LEAF_FINGERPRINT.to_string(),
+                subject: Some("cloudflare-dns.com".to_string()),
+                issuer: Some("DigiCert Global G2 TLS RSA SHA256 2020 CA1".to_string()),
+                expires: Some("2027-01-01".to_string()),
+            }),
+        }
+    }
+
+    fn mitm_detected(handshake: HandshakeResult, expected_leaf: Option<&str>) -> Option<bool> {
+        let mut https_info = HTTPSInfo::default();
+        record_1111_handshake(&mut https_info, handshake, expected_leaf.map(str::to_string));
+        https_info.mitm_detected
+    }
+
+    #[test]
+    fn wrong_leaf_fingerprint_is_interception() {
+        let wrong = LEAF_FINGERPRINT.replace("AA", "00");
+        assert_eq!(mitm_detected(handshake(true, true, Some(CLOUDFLARE_1111_ROOT_PINS[0])), Some(&wrong)), Some(true));
+    }
+
+    #[test]
+    fn matching_leaf_fingerprint_is_not_interception() {
+        let lower_case = LEAF_FINGERPRINT.to_ascii_lowercase();
+        assert_eq!(mitm_detected(handshake(true, true, Some(CLOUDFLARE_1111_ROOT_PINS[0])), Some(&lower_case)), Some(false));
+    }
+
+    #[test]
+    fn wrong_leaf_fingerprint_on_untrusted_chain_is_interception() {
+        // The leaf pin doesn't depend on the public roots accepting the chain
+        let wrong = LEAF_FINGERPRINT.replace("AA", "00");
+        assert_eq!(mitm_detected(handshake(false, false, None), Some(&wrong)), Some(true));
+    }
+
+    #[test]
+    fn matching_leaf_fingerprint_ignores_root_pins() {
+        let root = "00:".repeat(31) + "00";
+        assert_eq!(mitm_detected(handshake(true, true, Some(&root)), Some(LEAF_FINGERPRINT)), Some(false));
+    }
+
+    #[test]
+    fn unpinned_root_is_interception() {
+        let root = "00:".repeat(31) + "00";
+        assert_eq!(mitm_detected(handshake(true, true, Some(&root)), None), Some(true));
+    }
+
+    #[test]
+    fn invalid_chain_is_not_interception() {
+        // What an expired certificate or a wrong clock looks like, no store accepts the chain
+        assert_eq!(mitm_detected(handshake(false, false, None), None), Some(false));
+    }
+
+    #[test]
+    fn pinned_root_is_not_interception() {
+        for pin in CLOUDFLARE_1111_ROOT_PINS {
+            assert_eq!(mitm_detected(handshake(true, true, Some(pin)), None), Some(false));
+        }
+    }
+
+    #[test]
+    fn parses_hsts_directives() {
+        assert_eq!(
+            parse_hsts_header("max-age=31536000; includeSubDomains; preload"),
+            Some(HstsPolicy { max_age: 31536000, include_subdomains: true, preload: true })
+        );
+        assert_eq!(
+            parse_hsts_header("Max-Age=\"86400\""),
+            Some(HstsPolicy { max_age: 86400, include_subdomains: false, preload: false })
+        );
+    }
+
+    #[test]
+    fn hsts_without_max_age_is_ignored() {
+        assert_eq!(parse_hsts_header("includeSubDomains; preload"), None);
+        assert_eq!(parse_hsts_header("max-age=soon"), None);
+    }
+
+    #[test]
+    fn only_preloaded_status_counts() {
+        assert_eq!(parse_preload_status(r#"{"name":"cloudflare.com","status":"preloaded","bulk":true}"#), Some(true));
+        assert_eq!(parse_preload_status(r#"{"name":"example.com","status":"unknown"}"#), Some(false));
+        assert_eq!(parse_preload_status("<html>"), None);
+    }
+
+    #[test]
+    fn missing_hsts_header_still_records_the_host() {
+        let mut https_info = HTTPSInfo::default();
+        record_hsts(&mut https_info, HstsResult { policy: None, preload_listed: Some(false), security_score: 0, missing_headers: Vec::new() });
+
+        assert_eq!(https_info.hsts_host.as_deref(), Some(HSTS_HOST));
+        assert_eq!(https_info.hsts_max_age, None);
+    }
+
+    fn response(headers: &[(&str, &str)]) -> HttpResponse {
+        HttpResponse {
+            status: 200,
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+            body: String::new(),
+        }
+    }
+
+    #[test]
+    fn scores_each_configured_security_header() {
+        let all = response(&[
+            ("content-security-policy", "default-src 'self'"),
+            ("x-frame-options", "DENY"),
+            ("x-content-type-options", "nosniff"),
+            ("referrer-policy", "strict-origin-when-cross-origin"),
+            ("permissions-policy", "camera=()"),
+        ]);
+        assert_eq!(score_security_headers(&all), (100, Vec::new()));
+
+        let some = response(&[("x-content-type-options", "nosniff"), ("referrer-policy", "no-referrer")]);
+        assert_eq!(
+            score_security_headers(&some),
+            (40, vec!["content-security-policy".to_string(), "x-frame-options".to_string(), "permissions-policy".to_string()])
+        );
+    }
+
+    #[test]
+    fn misconfigured_security_headers_count_as_missing() {
+        let misconfigured = response(&[
+            ("x-frame-options", "ALLOW-FROM https://example.com"),
+            ("x-content-type-options", "sniff"),
+            ("referrer-policy", "no-referrer, unsafe-url"),
+            ("permissions-policy", ""),
+        ]);
+        assert_eq!(score_security_headers(&misconfigured).0, 0);
+
+        // Browsers skip policies they don't know, like a newer one listed after a fallback
+        let fallback = response(&[("referrer-policy", "no-referrer, some-future-policy")]);
+        assert!(!score_security_headers(&fallback).1.contains(&"referrer-policy".to_string()));
+    }
+
+    #[test]
+    fn frame_ancestors_covers_x_frame_options() {
+        let csp_only = response(&[("content-security-policy", "frame-ancestors 'none'")]);
+        assert!(!score_security_headers(&csp_only).1.contains(&"x-frame-options".to_string()));
+    }
+
+    #[test]
+    fn pins_are_public_roots() {
+        // Catches a typo in a pin, or a root that's been dropped from the public store
+        let public_roots: Vec<String> = webpki_roots::TLS_SERVER_ROOTS.iter().map(spki_fingerprint).collect();
+
+        for pin in CLOUDFLARE_1111_ROOT_PINS {
+            assert!(public_roots.iter().any(|root| root == pin), "{} is not a public root", pin);
+        }
+    }
+}
@@ -0,0 +1,201 @@
+use crate::internal_comms::{FetchedDataMessage, IPv6Info, send_or_stop};
+
+use std::net::{IpAddr, Ipv6Addr};
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::time::Instant;
+
+use socket2::Socket;
+
+#[cfg(any(target_os = "linux", target_os = "macos", target_os = "freebsd"))]
+use crate::fetch_local::command_stdout;
+use crate::fetch_local::{is_global_ipv6, is_link_local_ipv6, open_icmpv6_socket, send_icmpv6_echo, ICMPV6_ECHO_REPLY};
+
+// Cloudflare's and Google's public resolvers, which answer pings and are well known IPv6 addresses
+pub const IPV6_PING_TARGETS: [(&str, Ipv6Addr); 2] = [
+    ("Cloudflare", Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111)),
+    ("Google", Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888)),
+];
+const IPV6_PING_ATTEMPTS: u16 = 3;
+// Kept clear of the sequence numbers the path MTU test uses on the same identifier
+const IPV6_PING_FIRST_SEQUENCE: u16 = 3000;
+
+pub fn fetch_and_return_ipv6_info(tx: Sender<FetchedDataMessage>, interface: String, raw_socket: bool) {
+    let iface = match pnet::datalink::interfaces().into_iter().find(|iface| iface.name == interface) {
+        Some(iface) => iface,
+        None => {
+            send_or_stop(&tx, FetchedDataMessage::IPv6Info(IPv6Info {
+                error: Some("Interface not found.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let global_ipv6 = iface.ips.iter().map(|ip| ip.ip()).find(is_global_ipv6);
+
+    let mut ipv6_info = IPv6Info {
+        has_global_ipv6: Some(global_ipv6.is_some()),
+        ipv6_gateway: get_ipv6_gateway(&interface).ok(),
+        ..Default::default()
+    };
+
+    // Without a global address there's no route to the IPv6 internet to test
+    let global_ipv6 = match global_ipv6 {
+        Some(IpAddr::V6(global_ipv6)) => global_ipv6,
+        _ => {
+            ipv6_info.can_reach_ipv6_internet = Some(false);
+            ipv6_info.complete = true;
+            send_or_stop(&tx, FetchedDataMessage::IPv6Info(ipv6_info));
+            return;
+        }
+    };
+
+    // Pinging needs a raw socket, so without one reachability stays unknown
+    if !raw_socket {
+        ipv6_info.complete = true;
+        send_or_stop(&tx, FetchedDataMessage::IPv6Info(ipv6_info));
+        return;
+    }
+
+    if !send_or_stop(&tx, FetchedDataMessage::IPv6Info(ipv6_info.clone())) {
+        return;
+    }
+
+    let socket = match open_icmpv6_socket(global_ipv6) {
+        Ok(socket) => socket,
+        Err(_) => {
+            ipv6_info.complete = true;
+            send_or_stop(&tx, FetchedDataMessage::IPv6Info(ipv6_info));
+            return;
+        }
+    };
+
+    // The gateway resolves our address with a neighbour solicitation to this group before it can
+    // reply. The kernel has normally joined it already, so a failure here isn't worth stopping for
+    let _ = socket.join_multicast_v6(&solicited_node_address(global_ipv6), iface.index);
+
+    let identifier = std::process::id() as u16;
+    let mut sequence = IPV6_PING_FIRST_SEQUENCE;
+
+    // The gateway is usually a link-local address, which only means something on this interface
+    let gateway = ipv6_info.ipv6_gateway.as_ref().and_then(|gateway| gateway.parse::<Ipv6Addr>().ok());
+
+    if let Some(gateway) = gateway {
+        let scope_id = if is_link_local_ipv6(&IpAddr::V6(gateway)) { iface.index } else { 0 };
+        let latency = ping_ipv6(&socket, gateway, scope_id, identifier, sequence);
+
+        ipv6_info.gateway_reachable = Some(latency.is_some());
+        ipv6_info.gateway_ping_ms = latency;
+        sequence += IPV6_PING_ATTEMPTS;
+
+        if !send_or_stop(&tx, FetchedDataMessage::IPv6Info(ipv6_info.clone())) {
+            return;
+        }
+    }
+
+    for (_, target) in IPV6_PING_TARGETS {
+        let latency = ping_ipv6(&socket, target, 0, identifier, sequence);
+
+        ipv6_info.ping_results.push((target.to_string(), latency));
+        sequence += IPV6_PING_ATTEMPTS;
+    }
+
+    let fastest = ipv6_info.ping_results.iter()
+        .filter_map(|(_, latency)| *latency)
+        .min_by(|a, b| a.total_cmp(b));
+
+    ipv6_info.can_reach_ipv6_internet = Some(fastest.is_some());
+    ipv6_info.ping_ms = fastest;
+    ipv6_info.complete = true;
+    send_or_stop(&tx, FetchedDataMessage::IPv6Info(ipv6_info));
+}
+
+/// Pings `target` a few times and returns the fastest reply in milliseconds, or None if nothing came back
+fn ping_ipv6(socket: &Socket, target: Ipv6Addr, scope_id: u32, identifier: u16, first_sequence: u16) -> Option<f64> {
+    (0..IPV6_PING_ATTEMPTS)
+        .filter_map(|attempt| {
+            let started = Instant::now();
+
+            match send_icmpv6_echo(socket, target, scope_id, identifier, first_sequence + attempt, 64) {
+                Ok(Some(ICMPV6_ECHO_REPLY)) => Some(started.elapsed().as_secs_f64() * 1000.0),
+                _ => None
+            }
+        })
+        .min_by(|a, b| a.total_cmp(b))
+}
+
+/// The solicited-node multicast group for an address, ff02::1:ff00:0/104 plus its last 24 bits (RFC 4291)
+fn solicited_node_address(ip: Ipv6Addr) -> Ipv6Addr {
+    let segments = ip.segments();
+
+    Ipv6Addr::new(0xff02, 0, 0, 0, 0, 1, 0xff00 | (segments[6] & 0x00ff), segments[7])
+}
+
+/// IPv6 default routes usually point at the router's link-local address, learned from its adverts
+#[cfg(target_os = "linux")]
+fn get_ipv6_gateway(interface: &String) -> Result<String, ()> {
+    let output = Command::new("ip")
+        .arg("-6")
+        .arg("route")
+        .arg("show")
+        .arg("default")
+        .arg("dev")
+        .arg(interface)
+        .output();
+
+    let output_str = command_stdout(output)?;
+
+    // Lines look like "default via fe80::1 proto ra metric 1024 expires 1795sec pref medium"
+    for line in output_str.lines() {
+        let mut fields = line.split_whitespace().skip_while(|field| *field != "via");
+
+        if let (Some(_), Some(gateway)) = (fields.next(), fields.next()) {
+            return Ok(gateway.to_string());
+        }
+    }
+
+    Err(())
+}
+
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn get_ipv6_gateway(interface: &String) -> Result<String, ()> {
+    let output = Command::new("netstat")
+        .arg("-rn")
+        .arg("-f")
+        .arg("inet6")
+        .output();
+
+    let output_str = command_stdout(output)?;
+
+    // Lines look like "default   fe80::1%en0   UGcg   en0", with the interface last
+    for line in output_str.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        if fields.first() == Some(&"default") && fields.last() == Some(&interface.as_str()) && fields.len() >= 2 {
+            // Drop the scope, the panel already says which interface this is
+            let gateway = fields[1].split('%').next().unwrap_or(fields[1]);
+            return Ok(gateway.to_string());
+        }
+    }
+
+    Err(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd")))]
+fn get_ipv6_gateway(_interface: &String) -> Result<String, ()> {
+    Err(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solicited_node_address_keeps_the_last_24_bits() {
+        let ip: Ipv6Addr = "2001:db8::4f:12ab:34cd".parse().unwrap();
+
+        assert_eq!(solicited_node_address(ip), "ff02::1:ffab:34cd".parse::<Ipv6Addr>().unwrap());
+    }
+}
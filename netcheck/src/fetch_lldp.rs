@@ -0,0 +1,132 @@
+use crate::internal_comms::{FetchedDataMessage, LldpInfo, send_or_stop};
+
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use pnet::datalink::{self, Channel};
+use pnet::packet::ethernet::{EtherType, EthernetPacket};
+use pnet::packet::Packet;
+
+const LLDP_ETHERTYPE: EtherType = EtherType(0x88cc);
+
+// Switches send LLDP frames every 30 seconds by default
+const LISTEN_DURATION: Duration = Duration::from_secs(30);
+
+pub fn fetch_and_return_lldp_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    let iface = datalink::interfaces().into_iter().find(|iface| iface.name == interface);
+
+    let iface = match iface {
+        Some(iface) => iface,
+        None => {
+            send_or_stop(&tx, FetchedDataMessage::LldpInfo(LldpInfo {
+                listen_complete: true,
+                error: Some("Interface not found.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let config = datalink::Config {
+        read_timeout: Some(Duration::from_secs(1)),
+        ..Default::default()
+    };
+
+    // Opening a layer 2 channel needs raw socket access
+    let mut rx = match datalink::channel(&iface, config) {
+        Ok(Channel::Ethernet(_, rx)) => rx,
+        _ => {
+            send_or_stop(&tx, FetchedDataMessage::LldpInfo(LldpInfo {
+                listen_complete: true,
+                error: Some("Can't open raw socket.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    if !send_or_stop(&tx, FetchedDataMessage::LldpInfo(LldpInfo::default())) {
+        return;
+    }
+
+    let started = Instant::now();
+
+    while started.elapsed() < LISTEN_DURATION {
+        // Read timeouts show up as errors, so just go round again
+        let frame = match rx.next() {
+            Ok(frame) => frame,
+            Err(_) => continue
+        };
+
+        let ethernet = match EthernetPacket::new(frame) {
+            Some(ethernet) => ethernet,
+            None => continue
+        };
+
+        if ethernet.get_ethertype() != LLDP_ETHERTYPE {
+            continue;
+        }
+
+        if let Some(mut lldp_info) = parse_lldpdu(ethernet.payload()) {
+            // One advertisement is all we need
+            lldp_info.listen_complete = true;
+            send_or_stop(&tx, FetchedDataMessage::LldpInfo(lldp_info));
+            return;
+        }
+    }
+
+    send_or_stop(&tx, FetchedDataMessage::LldpInfo(LldpInfo {
+        listen_complete: true,
+        ..Default::default()
+    }));
+}
+
+/// Parses the TLVs of an LLDP data unit (IEEE 802.1AB)
+fn parse_lldpdu(payload: &[u8]) -> Option<LldpInfo> {
+    let mut lldp_info = LldpInfo {
+        neighbor_found: true,
+        ..Default::default()
+    };
+
+    let mut offset = 0;
+
+    while offset + 2 <= payload.len() {
+        // 7 bit type followed by 9 bit length
+        let header = u16::from_be_bytes([payload[offset], payload[offset + 1]]);
+        let tlv_type = header >> 9;
+        let tlv_length = (header & 0x01ff) as usize;
+
+        let value = payload.get(offset + 2..offset + 2 + tlv_length)?;
+        offset += 2 + tlv_length;
+
+        match tlv_type {
+            // End of LLDPDU
+            0 => break,
+            1 if !value.is_empty() => lldp_info.chassis_id = Some(format_id(value[0], 4, &value[1..])),
+            2 if !value.is_empty() => lldp_info.port_id = Some(format_id(value[0], 3, &value[1..])),
+            4 => lldp_info.port_description = Some(String::from_utf8_lossy(value).to_string()),
+            5 => lldp_info.neighbor_name = Some(String::from_utf8_lossy(value).to_string()),
+            // IEEE 802.1 organisationally specific TLV, subtype 1 is the port VLAN ID
+            127 if value.len() >= 6 && value[..4] == [0x00, 0x80, 0xc2, 0x01] => {
+                lldp_info.vlan_id = Some(u16::from_be_bytes([value[4], value[5]]));
+            }
+            _ => {}
+        }
+    }
+
+    // The chassis and port IDs are mandatory
+    if lldp_info.chassis_id.is_none() || lldp_info.port_id.is_none() {
+        return None;
+    }
+
+    Some(lldp_info)
+}
+
+/// Chassis and port IDs are either a MAC address or text depending on their subtype
+fn format_id(subtype: u8, mac_subtype: u8, id: &[u8]) -> String {
+    if subtype == mac_subtype && id.len() == 6 {
+        return id.iter().map(|byte| format!("{:02X}", byte)).collect::<Vec<String>>().join(":");
+    }
+
+    String::from_utf8_lossy(id).to_string()
+}
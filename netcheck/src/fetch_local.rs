@@ -1,52 +1,130 @@
-use crate::internal_comms::{LocalInfo, FetchedDataMessage};
+use crate::internal_comms::{FetchedDataMessage, LocalInfo, send_or_stop};
 
-use std::net::IpAddr;
-use std::sync::mpsc;
-use std::sync::mpsc::{Sender, Receiver};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
 
-use pnet;
+use pnet::ipnetwork::IpNetwork;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
 use std::process::Command;
 
-pub fn fetch_and_return_local_info(tx: Sender<FetchedDataMessage>, interface: String) {
+// Cloudflare's public resolver, used as the far end of the IPv6 path MTU test
+const IPV6_PMTUD_TARGET: Ipv6Addr = Ipv6Addr::new(0x2606, 0x4700, 0x4700, 0, 0, 0, 0, 0x1111);
+const IPV6_PMTUD_TIMEOUT: Duration = Duration::from_secs(2);
+const IPV6_PMTUD_ATTEMPTS: u16 = 3;
+
+const GATEWAY_PING_ATTEMPTS: u16 = 3;
+const GATEWAY_PING_TIMEOUT: Duration = Duration::from_secs(1);
+
+const ICMPV6_PACKET_TOO_BIG: u8 = 2;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+pub const ICMPV6_ECHO_REPLY: u8 = 129;
+
+pub fn fetch_and_return_local_info(tx: Sender<FetchedDataMessage>, interface: String, raw_socket: bool) {
     let interfaces = pnet::datalink::interfaces();
     for iface in interfaces {
         if iface.name == *interface {
-            // Get local IP
-            let local_ip = iface.ips[0].ip().to_string();
+            // Get local IPs and their prefix lengths
+            let local_ips = iface.ips.iter().map(|address| (address.ip(), address.prefix())).collect();
 
-            // Get subnet mask
-            let subnet_mask = iface.ips[0].prefix().to_string();
+            // The first of each kind of address, for panels that only care about one
+            let ipv4_addr = iface.ips.iter().map(|ip| ip.ip()).find(|ip| ip.is_ipv4());
+            let ipv6_global = iface.ips.iter().map(|ip| ip.ip()).find(is_global_ipv6);
+            let ipv6_link_local = iface.ips.iter().map(|ip| ip.ip()).find(is_link_local_ipv6);
 
-            // Get gateway
-            let gateway = get_default_gateway(&interface);
-
-            let gateway = match gateway {
-                Ok(gateway) => Some(gateway),
-                Err(_) => None
+            // Get gateway, which can't be in use without an address to route from
+            let gateway = match preferred_address(&iface.ips) {
+                Some(_) => get_default_gateway(&interface).ok(),
+                None => None
             };
 
-            let local_info = LocalInfo {
-                local_ip: Some(local_ip),
-                subnet_mask: Some(subnet_mask),
-                gateway: gateway
+            // Loopback and some tunnel interfaces have no hardware address, pnet gives them all zeroes
+            let mac_address = iface.mac
+                .filter(|mac| !mac.is_zero())
+                .map(|mac| mac.to_string().to_uppercase());
+
+            let mut local_info = LocalInfo {
+                mac_address,
+                is_up: iface.is_up(),
+                is_loopback: iface.is_loopback(),
+                mtu: get_mtu(&interface).ok(),
+                local_ips,
+                ipv4_addr,
+                ipv6_global,
+                ipv6_link_local,
+                gateway,
+                ..Default::default()
             };
 
-            tx.send(FetchedDataMessage::LocalInfo(local_info)).unwrap();
+            if !send_or_stop(&tx, FetchedDataMessage::LocalInfo(local_info.clone())) {
+                return;
+            }
+
+            let gateway_ip = local_info.gateway.as_ref().and_then(|gateway| gateway.parse::<Ipv4Addr>().ok());
+
+            if let (true, Some(gateway_ip), Some(interface_ipv4)) = (raw_socket, gateway_ip, ipv4_addr) {
+                if let Ok(latency) = ping_gateway(interface_ipv4, gateway_ip) {
+                    local_info.gateway_reachable = Some(latency.is_some());
+                    local_info.gateway_latency_ms = latency;
+
+                    if !send_or_stop(&tx, FetchedDataMessage::LocalInfo(local_info.clone())) {
+                        return;
+                    }
+                }
+            }
+
+            // Sending ICMPv6 ourselves needs a raw socket, and there's nothing to test without a global address
+            if let (true, Some(IpAddr::V6(global_ipv6))) = (raw_socket, ipv6_global) {
+                local_info.ipv6_pmtud_working = check_ipv6_pmtud(global_ipv6);
+                if !send_or_stop(&tx, FetchedDataMessage::LocalInfo(local_info)) {
+                    return;
+                }
+            }
+
+            return;
         }
     }
+
+    // The interface went away after it was picked, so all there is to report is that nothing's known
+    send_or_stop(&tx, FetchedDataMessage::LocalInfo(LocalInfo::default()));
 }
 
 pub fn get_interface_ip(interface: &String) -> Result<IpAddr, ()> {
     let interfaces = pnet::datalink::interfaces();
     for iface in interfaces {
         if iface.name == *interface {
-            return Ok(iface.ips[0].ip());
+            return match preferred_address(&iface.ips) {
+                Some(address) => Ok(address.ip()),
+                None => Err(())
+            };
         }
     }
 
     Err(())
 }
 
+/// The address checks should run from: IPv4 if the interface has one, otherwise global IPv6
+/// before unique local. Link-local addresses can't reach past the link, so they're never picked.
+pub fn preferred_address(ips: &[IpNetwork]) -> Option<IpNetwork> {
+    let rank = |address: &IpNetwork| match address.ip() {
+        IpAddr::V4(ip) if ip.is_link_local() || ip.is_unspecified() => None,
+        IpAddr::V4(ip) if ip.is_loopback() => Some(3),
+        IpAddr::V4(_) => Some(0),
+        ip if is_global_ipv6(&ip) => Some(1),
+        // fe80::/10
+        IpAddr::V6(ip) if (ip.segments()[0] & 0xffc0) == 0xfe80 || ip.is_unspecified() => None,
+        IpAddr::V6(_) => Some(2),
+    };
+
+    // min_by_key keeps the first of equals, so the OS's own ordering breaks ties
+    ips.iter()
+        .filter_map(|address| rank(address).map(|rank| (rank, *address)))
+        .min_by_key(|(rank, _)| *rank)
+        .map(|(_, address)| address)
+}
+
+#[cfg(target_os = "linux")]
 fn get_default_gateway(interface: &String) -> Result<String, ()> {
     let output = Command::new("ip")
         .arg("route")
@@ -55,17 +133,7 @@ fn get_default_gateway(interface: &String) -> Result<String, ()> {
         .arg(interface)
         .output();
 
-    let output = match output {
-        Ok(output) => output,
-        Err(_) => return Err(())
-    };
-
-    let output_str = std::str::from_utf8(&output.stdout);
-
-    let output_str = match output_str {
-        Ok(output_str) => output_str,
-        Err(_) => return Err(())
-    };
+    let output_str = command_stdout(output)?;
 
     for line in output_str.lines() {
         if line.contains("default") {
@@ -81,4 +149,340 @@ fn get_default_gateway(interface: &String) -> Result<String, ()> {
     }
 
     Err(())
-}
\ No newline at end of file
+}
+
+/// Reads the routing table from `netstat`, whose default route lines look like
+/// "default  192.168.1.1  UGScg  en0" on both macOS and FreeBSD. Netif is the fourth column.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+fn get_default_gateway(interface: &String) -> Result<String, ()> {
+    let output = Command::new("netstat")
+        .arg("-rn")
+        .arg("-f")
+        .arg("inet")
+        .output();
+
+    let output_str = command_stdout(output)?;
+
+    for line in output_str.lines() {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+
+        if columns.len() >= 4 && columns[0] == "default" && columns[3] == interface {
+            return Ok(columns[1].to_string());
+        }
+    }
+
+    Err(())
+}
+
+/// `route print` lists routes by the interface's address rather than its name, so match on that.
+/// Default routes are the rows with a destination and netmask of 0.0.0.0.
+#[cfg(windows)]
+fn get_default_gateway(interface: &String) -> Result<String, ()> {
+    // The table below is IPv4 only, and Windows often lists the link-local IPv6 address first
+    let interface_ip = pnet::datalink::interfaces().into_iter()
+        .find(|iface| iface.name == *interface)
+        .and_then(|iface| iface.ips.iter().map(|ip| ip.ip()).find(|ip| ip.is_ipv4()));
+
+    let interface_ip = match interface_ip {
+        Some(interface_ip) => interface_ip.to_string(),
+        None => return Err(())
+    };
+
+    let output = Command::new("route")
+        .arg("print")
+        .arg("-4")
+        .arg("0.0.0.0")
+        .output();
+
+    let output_str = command_stdout(output)?;
+
+    for line in output_str.lines() {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+
+        // Network Destination, Netmask, Gateway, Interface, Metric
+        if columns.len() >= 4 && columns[0] == "0.0.0.0" && columns[1] == "0.0.0.0" && columns[3] == interface_ip {
+            // On-link means there's no gateway to speak of
+            if columns[2] == "On-link" {
+                return Err(());
+            }
+
+            return Ok(columns[2].to_string());
+        }
+    }
+
+    Err(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd", windows)))]
+fn get_default_gateway(_interface: &String) -> Result<String, ()> {
+    Err(())
+}
+
+#[cfg(target_os = "linux")]
+pub fn get_mtu(interface: &String) -> Result<u32, ()> {
+    let mtu = match std::fs::read_to_string(format!("/sys/class/net/{}/mtu", interface)) {
+        Ok(mtu) => mtu,
+        Err(_) => return Err(())
+    };
+
+    match mtu.trim().parse() {
+        Ok(mtu) => Ok(mtu),
+        Err(_) => Err(())
+    }
+}
+
+/// ifconfig's first line for the interface ends in "mtu 1500"
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub fn get_mtu(interface: &String) -> Result<u32, ()> {
+    let output = Command::new("ifconfig")
+        .arg(interface)
+        .output();
+
+    let output_str = command_stdout(output)?;
+
+    let first_line = match output_str.lines().next() {
+        Some(first_line) => first_line,
+        None => return Err(())
+    };
+
+    let mut words = first_line.split_whitespace().skip_while(|word| *word != "mtu").skip(1);
+
+    match words.next().map(|mtu| mtu.parse()) {
+        Some(Ok(mtu)) => Ok(mtu),
+        _ => Err(())
+    }
+}
+
+/// netsh lists subinterfaces as "MTU  MediaSenseState  Bytes In  Bytes Out  Interface",
+/// where the interface is the connection name ("Wi-Fi") and may contain spaces
+#[cfg(windows)]
+pub fn get_mtu(interface: &String) -> Result<u32, ()> {
+    let connection_name = crate::netlib::display_name(interface);
+
+    let output = Command::new("netsh")
+        .args(["interface", "ipv4", "show", "subinterfaces"])
+        .output();
+
+    let output_str = command_stdout(output)?;
+
+    for line in output_str.lines() {
+        let columns: Vec<&str> = line.split_whitespace().collect();
+
+        if columns.len() >= 5 && columns[4..].join(" ") == connection_name {
+            return match columns[0].parse() {
+                Ok(mtu) => Ok(mtu),
+                Err(_) => Err(())
+            };
+        }
+    }
+
+    Err(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", target_os = "freebsd", windows)))]
+pub fn get_mtu(_interface: &String) -> Result<u32, ()> {
+    Err(())
+}
+
+pub fn command_stdout(output: std::io::Result<std::process::Output>) -> Result<String, ()> {
+    let output = match output {
+        Ok(output) => output,
+        Err(_) => return Err(())
+    };
+
+    match String::from_utf8(output.stdout) {
+        Ok(output_str) => Ok(output_str),
+        Err(_) => Err(())
+    }
+}
+
+/// Pings the gateway a few times and returns the fastest reply in milliseconds, or None if
+/// it never answered. Some routers drop pings to themselves, so None isn't proof it's down.
+/// Err means the ping couldn't be sent at all.
+fn ping_gateway(interface_ip: IpAddr, gateway_ip: Ipv4Addr) -> Result<Option<f64>, ()> {
+    let socket = crate::fetch_traceroute::open_icmp_socket(interface_ip)?;
+
+    let identifier = std::process::id() as u16;
+
+    // Kept clear of the sequence numbers the traceroute and 1.1.1.1 ping use on the same identifier
+    let fastest = (0..GATEWAY_PING_ATTEMPTS)
+        .filter_map(|attempt| {
+            crate::fetch_traceroute::send_probe(&socket, gateway_ip, identifier, 2000 + attempt, GATEWAY_PING_TIMEOUT)
+                .filter(|response| response.is_reply)
+                .map(|response| response.latency)
+        })
+        .min_by(|a, b| a.total_cmp(b));
+
+    Ok(fastest)
+}
+
+pub fn is_global_ipv6(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(_) => false,
+        IpAddr::V6(ip) => {
+            let first_segment = ip.segments()[0];
+
+            // Skip loopback, link-local (fe80::/10) and unique local (fc00::/7) addresses
+            !ip.is_loopback() && (first_segment & 0xffc0) != 0xfe80 && (first_segment & 0xfe00) != 0xfc00
+        }
+    }
+}
+
+pub fn is_link_local_ipv6(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(_) => false,
+        // fe80::/10
+        IpAddr::V6(ip) => (ip.segments()[0] & 0xffc0) == 0xfe80,
+    }
+}
+
+/// IPv6 routers never fragment, so a too-large packet only gets through if the
+/// ICMPv6 Packet Too Big message makes it back to us. Firewalls that drop those
+/// make large transfers stall while small ones work fine.
+///
+/// Returns None if even a small echo request goes unanswered, since then we can't tell.
+fn check_ipv6_pmtud(local_ip: Ipv6Addr) -> Option<bool> {
+    let socket = match open_icmpv6_socket(local_ip) {
+        Ok(socket) => socket,
+        Err(_) => return None
+    };
+
+    let identifier = std::process::id() as u16;
+
+    // First make sure the target answers at all
+    match send_icmpv6_echo(&socket, IPV6_PMTUD_TARGET, 0, identifier, 1, 64) {
+        Ok(Some(ICMPV6_ECHO_REPLY)) => {}
+        _ => return None
+    }
+
+    // Then fill a whole 1500 byte Ethernet frame. The attempts are only sent as the decision needs them
+    let attempts = (0..IPV6_PMTUD_ATTEMPTS)
+        .map(|attempt| send_icmpv6_echo(&socket, IPV6_PMTUD_TARGET, 0, identifier, 2 + attempt, 1500));
+
+    decide_pmtud(attempts)
+}
+
+/// Works out whether path MTU discovery works from the replies to each full-size probe, stopping at
+/// the first conclusive one. A single lost packet isn't enough to call it blocked, so only a run of
+/// unanswered attempts counts. If a probe couldn't even be sent (EMSGSIZE when the link itself is
+/// under 1500 bytes) the path was never tested, so there's no answer either way.
+fn decide_pmtud(attempts: impl IntoIterator<Item = Result<Option<u8>, ()>>) -> Option<bool> {
+    for attempt in attempts {
+        match attempt {
+            Err(_) => return None,
+            Ok(Some(ICMPV6_ECHO_REPLY)) | Ok(Some(ICMPV6_PACKET_TOO_BIG)) => return Some(true),
+            Ok(_) => {}
+        }
+    }
+
+    Some(false)
+}
+
+/// A raw ICMPv6 socket sending from `local_ip`, which keeps the probes on the chosen interface
+pub fn open_icmpv6_socket(local_ip: Ipv6Addr) -> Result<Socket, ()> {
+    let socket = match Socket::new(Domain::IPV6, Type::RAW, Some(Protocol::ICMPV6)) {
+        Ok(socket) => socket,
+        Err(_) => return Err(())
+    };
+
+    if socket.bind(&SockAddr::from(SocketAddr::new(IpAddr::V6(local_ip), 0))).is_err() {
+        return Err(());
+    }
+
+    if socket.set_read_timeout(Some(Duration::from_millis(200))).is_err() {
+        return Err(());
+    }
+
+    Ok(socket)
+}
+
+/// Sends an echo request to `target` making up an IPv6 packet of `packet_size` bytes,
+/// and returns the ICMPv6 type of whatever comes back about it. Err means it couldn't be sent.
+/// `scope_id` is the interface index for link-local targets, and 0 for everything else.
+pub fn send_icmpv6_echo(socket: &Socket, target: Ipv6Addr, scope_id: u32, identifier: u16, sequence: u16, packet_size: usize) -> Result<Option<u8>, ()> {
+    // 40 byte IPv6 header, the kernel fills in the ICMPv6 checksum for us
+    let mut request = vec![0u8; packet_size - 40];
+    request[0] = ICMPV6_ECHO_REQUEST;
+    request[4..6].copy_from_slice(&identifier.to_be_bytes());
+    request[6..8].copy_from_slice(&sequence.to_be_bytes());
+
+    if socket.send_to(&request, &SockAddr::from(SocketAddrV6::new(target, 0, 0, scope_id))).is_err() {
+        return Err(());
+    }
+
+    let started = Instant::now();
+    let mut buf = [0u8; 2048];
+
+    while started.elapsed() < IPV6_PMTUD_TIMEOUT {
+        // Raw ICMPv6 sockets see every ICMPv6 packet, so keep going until one is about our request
+        let len = match (&*socket).read(&mut buf) {
+            Ok(len) => len,
+            Err(_) => continue
+        };
+
+        let packet = &buf[..len];
+
+        if packet.len() < 8 {
+            continue;
+        }
+
+        let ours = match packet[0] {
+            ICMPV6_ECHO_REPLY => packet[4..8] == request[4..8],
+            // Errors quote our packet after their own 8 byte header: IPv6 header, then our ICMPv6 header
+            ICMPV6_PACKET_TOO_BIG => packet.len() >= 56 && packet[48] == ICMPV6_ECHO_REQUEST && packet[52..56] == request[4..8],
+            _ => false
+        };
+
+        if ours {
+            return Ok(Some(packet[0]));
+        }
+    }
+
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn echo_reply_means_pmtud_works() {
+        assert_eq!(decide_pmtud([Ok(Some(ICMPV6_ECHO_REPLY))]), Some(true));
+    }
+
+    #[test]
+    fn packet_too_big_means_pmtud_works() {
+        // The router told us about the smaller MTU, which is exactly what has to get through
+        assert_eq!(decide_pmtud([Ok(Some(ICMPV6_PACKET_TOO_BIG))]), Some(true));
+    }
+
+    #[test]
+    fn one_lost_probe_is_retried() {
+        assert_eq!(decide_pmtud([Ok(None), Ok(Some(ICMPV6_ECHO_REPLY))]), Some(true));
+    }
+
+    #[test]
+    fn every_probe_unanswered_is_blocked() {
+        assert_eq!(decide_pmtud([Ok(None), Ok(None), Ok(None)]), Some(false));
+    }
+
+    #[test]
+    fn unrelated_replies_are_not_an_answer() {
+        // Destination unreachable, say, doesn't show the large packet made it
+        assert_eq!(decide_pmtud([Ok(Some(1)), Ok(None), Ok(None)]), Some(false));
+    }
+
+    #[test]
+    fn probe_that_cannot_be_sent_is_unknown() {
+        assert_eq!(decide_pmtud([Ok(None), Err(())]), None);
+    }
+
+    #[test]
+    fn stops_at_the_first_conclusive_attempt() {
+        let mut sent = 0;
+        let attempts = [Ok(Some(ICMPV6_ECHO_REPLY)), Ok(None), Ok(None)].into_iter().inspect(|_| sent += 1);
+
+        assert_eq!(decide_pmtud(attempts), Some(true));
+        assert_eq!(sent, 1);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::internal_comms::{FetchedDataMessage, MdnsInfo, send_or_stop};
+
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::process::Command;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use rustdns::Message;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+use crate::fetch_local::get_interface_ip;
+
+pub const MDNS_GROUP: Ipv4Addr = Ipv4Addr::new(224, 0, 0, 251);
+pub const MDNS_PORT: u16 = 5353;
+
+const SELF_RESOLUTION_TIMEOUT: Duration = Duration::from_millis(1500);
+const LISTEN_DURATION: Duration = Duration::from_secs(3);
+
+pub fn fetch_and_return_mdns_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    // mDNS here is IPv4 only
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(IpAddr::V4(interface_ip)) => interface_ip,
+        _ => {
+            send_or_stop(&tx, FetchedDataMessage::MdnsInfo(MdnsInfo {
+                error: Some("No IPv4 address.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let hostname = match get_hostname() {
+        Some(hostname) => format!("{}.local", hostname),
+        None => {
+            send_or_stop(&tx, FetchedDataMessage::MdnsInfo(MdnsInfo {
+                error: Some("Can't get hostname.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let mut mdns_info = MdnsInfo {
+        hostname: Some(hostname.clone()),
+        ..Default::default()
+    };
+
+    if !send_or_stop(&tx, FetchedDataMessage::MdnsInfo(mdns_info.clone())) {
+        return;
+    }
+
+    mdns_info.self_resolution_ok = Some(check_self_resolution(&hostname, interface_ip));
+
+    if !send_or_stop(&tx, FetchedDataMessage::MdnsInfo(mdns_info.clone())) {
+        return;
+    }
+
+    mdns_info.devices_discovered = count_mdns_devices(interface_ip);
+    mdns_info.discovery_complete = true;
+
+    send_or_stop(&tx, FetchedDataMessage::MdnsInfo(mdns_info));
+}
+
+fn get_hostname() -> Option<String> {
+    let output = match Command::new("hostname").output() {
+        Ok(output) => output,
+        Err(_) => return None
+    };
+
+    let hostname = String::from_utf8_lossy(&output.stdout).trim().to_string();
+
+    // We only want the bare host name, not any domain it's configured with
+    let hostname = hostname.split('.').next().unwrap_or("").to_string();
+
+    match hostname.is_empty() {
+        true => None,
+        false => Some(hostname)
+    }
+}
+
+/// Builds an mDNS query (RFC 6762). With `unicast_response` set the QU bit asks responders
+/// to reply straight to us rather than to the whole multicast group.
+pub fn build_mdns_query(name: &str, record_type: rustdns::Type, unicast_response: bool) -> Option<Vec<u8>> {
+    // mDNS queries always use an ID of zero
+    let mut message = Message {
+        id: 0,
+        ..Default::default()
+    };
+    message.add_question(name, record_type, rustdns::Class::Internet);
+
+    let mut query = match message.to_vec() {
+        Ok(query) => query,
+        Err(_) => return None
+    };
+
+    // rustdns can't express the QU bit, it's the top bit of the last question's class
+    if unicast_response {
+        let class_offset = query.len() - 2;
+        query[class_offset] |= 0x80;
+    }
+
+    Some(query)
+}
+
+/// Asks the network what address our own .local name has, which is answered
+/// by our own responder (Avahi, systemd-resolved, mDNSResponder) if it's working.
+fn check_self_resolution(hostname: &str, interface_ip: Ipv4Addr) -> bool {
+    let query = match build_mdns_query(hostname, rustdns::Type::A, true) {
+        Some(query) => query,
+        None => return false
+    };
+
+    let socket = match UdpSocket::bind(SocketAddr::new(IpAddr::V4(interface_ip), 0)) {
+        Ok(socket) => socket,
+        Err(_) => return false
+    };
+
+    // std doesn't expose IP_MULTICAST_IF, so go through socket2 for it
+    if socket2::SockRef::from(&socket).set_multicast_if_v4(&interface_ip).is_err() {
+        return false;
+    }
+
+    if socket.set_read_timeout(Some(Duration::from_millis(250))).is_err() {
+        return false;
+    }
+
+    if socket.send_to(&query, SocketAddr::new(IpAddr::V4(MDNS_GROUP), MDNS_PORT)).is_err() {
+        return false;
+    }
+
+    let started = Instant::now();
+    let mut buf = [0u8; 1500];
+
+    while started.elapsed() < SELF_RESOLUTION_TIMEOUT {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(_) => continue
+        };
+
+        let resp = match Message::from_slice(&buf[..len]) {
+            Ok(resp) => resp,
+            Err(_) => continue
+        };
+
+        // Success means the name came back pointing at this interface
+        for answer in resp.answers {
+            if !answer.name.trim_end_matches('.').eq_ignore_ascii_case(hostname) {
+                continue;
+            }
+
+            if let rustdns::Resource::A(ip) = answer.resource {
+                return ip == interface_ip;
+            }
+        }
+    }
+
+    false
+}
+
+/// Joins the mDNS multicast group on the interface, sharing port 5353 with any
+/// responder already running on this machine.
+pub fn open_mdns_listener(interface_ip: Ipv4Addr) -> Result<UdpSocket, ()> {
+    let socket = match Socket::new(Domain::IPV4, Type::DGRAM, Some(Protocol::UDP)) {
+        Ok(socket) => socket,
+        Err(_) => return Err(())
+    };
+
+    if socket.set_reuse_address(true).is_err() {
+        return Err(());
+    }
+
+    #[cfg(unix)]
+    if socket.set_reuse_port(true).is_err() {
+        return Err(());
+    }
+
+    let bind_addr = SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), MDNS_PORT);
+
+    if socket.bind(&SockAddr::from(bind_addr)).is_err() {
+        return Err(());
+    }
+
+    if socket.join_multicast_v4(&MDNS_GROUP, &interface_ip).is_err() {
+        return Err(());
+    }
+
+    if socket.set_multicast_if_v4(&interface_ip).is_err() {
+        return Err(());
+    }
+
+    if socket.set_read_timeout(Some(Duration::from_millis(250))).is_err() {
+        return Err(());
+    }
+
+    Ok(UdpSocket::from(socket))
+}
+
+/// Counts the other hosts sending mDNS traffic, prompting them with a service enumeration query
+fn count_mdns_devices(interface_ip: Ipv4Addr) -> u8 {
+    let socket = match open_mdns_listener(interface_ip) {
+        Ok(socket) => socket,
+        Err(_) => return 0
+    };
+
+    if let Some(query) = build_mdns_query("_services._dns-sd._udp.local", rustdns::Type::PTR, false) {
+        let _ = socket.send_to(&query, SocketAddr::new(IpAddr::V4(MDNS_GROUP), MDNS_PORT));
+    }
+
+    let mut devices = HashSet::new();
+    let started = Instant::now();
+    let mut buf = [0u8; 9000];
+
+    while started.elapsed() < LISTEN_DURATION {
+        if let Ok((_, source)) = socket.recv_from(&mut buf) {
+            if source.ip() != IpAddr::V4(interface_ip) {
+                devices.insert(source.ip());
+            }
+        }
+    }
+
+    devices.len().min(u8::MAX as usize) as u8
+}
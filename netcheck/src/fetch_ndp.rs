@@ -0,0 +1,64 @@
+use crate::internal_comms::{FetchedDataMessage, NdpEntry, send_or_stop};
+
+use std::sync::mpsc::Sender;
+
+use std::process::Command;
+
+/// Sends the IPv6 (NDP) neighbour cache, then the IPv4 (ARP) one
+pub fn fetch_and_return_ndp_entries(tx: Sender<FetchedDataMessage>, interface: String) {
+    let ndp_entries = get_neighbour_entries(&interface, "-6").unwrap_or_default();
+
+    if !send_or_stop(&tx, FetchedDataMessage::NDPEntries(ndp_entries)) {
+        return;
+    }
+
+    let arp_entries = get_neighbour_entries(&interface, "-4").unwrap_or_default();
+
+    send_or_stop(&tx, FetchedDataMessage::ArpEntries(arp_entries));
+}
+
+/// Reads `ip neigh` for one address family, given as "-4" or "-6"
+pub fn get_neighbour_entries(interface: &str, family: &str) -> Result<Vec<NdpEntry>, ()> {
+    let output = Command::new("ip")
+        .arg(family)
+        .arg("neigh")
+        .arg("show")
+        .arg("dev")
+        .arg(interface)
+        .output();
+
+    let output = match output {
+        Ok(output) => output,
+        Err(_) => return Err(())
+    };
+
+    let output_str = match std::str::from_utf8(&output.stdout) {
+        Ok(output_str) => output_str,
+        Err(_) => return Err(())
+    };
+
+    let mut ndp_entries = Vec::new();
+
+    // Lines look like "fe80::1 lladdr 52:54:00:12:34:56 router REACHABLE",
+    // entries that never resolved have no lladdr, e.g. "fe80::2 FAILED"
+    for line in output_str.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+
+        if fields.len() < 2 {
+            continue;
+        }
+
+        let mac = match fields.iter().position(|field| *field == "lladdr") {
+            Some(index) => fields.get(index + 1).unwrap_or(&"").to_string(),
+            None => String::new()
+        };
+
+        ndp_entries.push(NdpEntry {
+            ip: fields[0].to_string(),
+            mac,
+            state: fields[fields.len() - 1].to_string(),
+        });
+    }
+
+    Ok(ndp_entries)
+}
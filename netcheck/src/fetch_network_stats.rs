@@ -0,0 +1,89 @@
+use crate::internal_comms::{FetchedDataMessage, NetworkStatsInfo, send_or_stop};
+
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Samples the interface's counters every second for as long as the UI is listening,
+/// working out throughput from the difference between consecutive samples.
+pub fn fetch_and_return_network_stats(tx: Sender<FetchedDataMessage>, interface: String) {
+    let mut previous: Option<(NetworkStatsInfo, Instant)> = None;
+
+    loop {
+        let mut stats = match read_counters(&interface) {
+            Ok(stats) => stats,
+            Err(_) => {
+                send_or_stop(&tx, FetchedDataMessage::NetworkStats(NetworkStatsInfo {
+                    error: Some("Couldn't read interface counters.".to_string()),
+                    ..Default::default()
+                }));
+                return;
+            }
+        };
+
+        let sampled_at = Instant::now();
+
+        if let Some((ref previous_stats, previous_sampled_at)) = previous {
+            let elapsed = sampled_at.duration_since(previous_sampled_at).as_secs_f64();
+
+            // The counters start again from zero if the interface is recreated, so skip that sample
+            stats.rx_bps = stats.rx_bytes.checked_sub(previous_stats.rx_bytes).map(|bytes| bytes as f64 / elapsed);
+            stats.tx_bps = stats.tx_bytes.checked_sub(previous_stats.tx_bytes).map(|bytes| bytes as f64 / elapsed);
+        }
+
+        if !send_or_stop(&tx, FetchedDataMessage::NetworkStats(stats.clone())) {
+            return;
+        }
+
+        previous = Some((stats, sampled_at));
+
+        thread::sleep(SAMPLE_INTERVAL);
+    }
+}
+
+/// Reads the interface's line of /proc/net/dev, which looks like
+/// "  eth0: <8 receive counters> <8 transmit counters>" starting with bytes, packets and errors.
+#[cfg(target_os = "linux")]
+fn read_counters(interface: &str) -> Result<NetworkStatsInfo, ()> {
+    let contents = match std::fs::read_to_string("/proc/net/dev") {
+        Ok(contents) => contents,
+        Err(_) => return Err(())
+    };
+
+    for line in contents.lines() {
+        // Older kernels leave no space between the colon and a large first counter
+        let (name, counters) = match line.split_once(':') {
+            Some((name, counters)) => (name.trim(), counters),
+            None => continue
+        };
+
+        if name != interface {
+            continue;
+        }
+
+        let counters: Vec<u64> = counters.split_whitespace().filter_map(|counter| counter.parse().ok()).collect();
+
+        if counters.len() < 16 {
+            return Err(());
+        }
+
+        return Ok(NetworkStatsInfo {
+            rx_bytes: counters[0],
+            rx_packets: counters[1],
+            rx_errors: counters[2],
+            tx_bytes: counters[8],
+            tx_packets: counters[9],
+            tx_errors: counters[10],
+            ..Default::default()
+        });
+    }
+
+    Err(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_counters(_interface: &str) -> Result<NetworkStatsInfo, ()> {
+    Err(())
+}
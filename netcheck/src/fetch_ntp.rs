@@ -0,0 +1,163 @@
+use crate::internal_comms::{FetchedDataMessage, NTPInfo, send_or_stop};
+
+use std::fs;
+use std::net::ToSocketAddrs;
+use std::sync::mpsc::Sender;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::fetch_local::get_interface_ip;
+use crate::fetch_udp::exchange;
+
+const TIMESYNCD_CONF: &str = "/etc/systemd/timesyncd.conf";
+const NTPD_CONF: &str = "/etc/ntp.conf";
+const FALLBACK_NTP_SERVER: &str = "pool.ntp.org";
+
+// Seconds between the NTP era (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET: f64 = 2_208_988_800.0;
+
+pub fn fetch_and_return_ntp_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(interface_ip) => interface_ip,
+        Err(_) => {
+            send_or_stop(&tx, FetchedDataMessage::NTPInfo(NTPInfo {
+                error: Some("No IP address.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let configured_server = get_configured_server();
+
+    // NETCHECK_NTP_SERVER picks the server to ask, e.g. "129.6.15.28" for NIST
+    let server = std::env::var("NETCHECK_NTP_SERVER").ok()
+        .map(|server| server.trim().to_string())
+        .filter(|server| !server.is_empty())
+        .or_else(|| configured_server.clone())
+        .unwrap_or_else(|| FALLBACK_NTP_SERVER.to_string());
+
+    let mut ntp_info = NTPInfo {
+        do_use_ntp: Some(configured_server.is_some()),
+        ntp_server: Some(server.clone()),
+        ..Default::default()
+    };
+
+    // Show which server we're about to ask while the request is in flight
+    if !send_or_stop(&tx, FetchedDataMessage::NTPInfo(ntp_info.clone())) {
+        return;
+    }
+
+    let remote = match (server.as_str(), 123).to_socket_addrs() {
+        Ok(addrs) => addrs.into_iter().find(|addr| addr.is_ipv4() == interface_ip.is_ipv4()),
+        Err(_) => None
+    };
+
+    let sample = match remote {
+        Some(remote) => query_ntp(|request| exchange(interface_ip, remote, request).map_err(|_| ())),
+        None => None
+    };
+
+    ntp_info.can_access_ntp = Some(sample.is_some());
+    ntp_info.complete = true;
+
+    if let Some(sample) = sample {
+        ntp_info.clock_offset_ms = Some(sample.offset * 1000.0);
+        ntp_info.round_trip_delay_ms = Some(sample.delay * 1000.0);
+        ntp_info.local_time = Some(sample.local_time as u64);
+        ntp_info.server_time = Some(sample.server_time as u64);
+    }
+
+    send_or_stop(&tx, FetchedDataMessage::NTPInfo(ntp_info));
+}
+
+/// Times in seconds since the Unix epoch, offset and delay in seconds
+struct NtpSample {
+    offset: f64,
+    delay: f64,
+    local_time: f64,
+    server_time: f64,
+}
+
+/// Does one RFC 5905 client/server exchange through `send`, which should return the server's reply
+fn query_ntp(send: impl FnOnce(&[u8]) -> Result<Vec<u8>, ()>) -> Option<NtpSample> {
+    // LI 0, version 4, mode 3 (client)
+    let mut request = [0u8; 48];
+    request[0] = 0b00_100_011;
+
+    let t1 = unix_now();
+    request[40..48].copy_from_slice(&to_ntp_timestamp(t1));
+
+    let response = send(&request).ok()?;
+    let t4 = unix_now();
+
+    // Mode 4 is a server reply, and stratum 0 is a kiss-o'-death rather than a time
+    if response.len() < 48 || response[0] & 0b111 != 4 || response[1] == 0 {
+        return None;
+    }
+
+    // The originate timestamp echoes our transmit timestamp, anything else isn't an answer to us
+    if response[24..32] != request[40..48] {
+        return None;
+    }
+
+    let t2 = from_ntp_timestamp(&response[32..40]);
+    let t3 = from_ntp_timestamp(&response[40..48]);
+
+    Some(NtpSample {
+        offset: ((t2 - t1) + (t3 - t4)) / 2.0,
+        delay: (t4 - t1) - (t3 - t2),
+        local_time: t4,
+        server_time: t3,
+    })
+}
+
+fn unix_now() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs_f64()).unwrap_or(0.0)
+}
+
+/// 32 bits of seconds since 1900 followed by 32 bits of fraction
+fn to_ntp_timestamp(unix_time: f64) -> [u8; 8] {
+    let ntp_time = unix_time + NTP_UNIX_OFFSET;
+
+    let seconds = ntp_time.trunc() as u32;
+    let fraction = (ntp_time.fract() * 4_294_967_296.0) as u32;
+
+    let mut timestamp = [0u8; 8];
+    timestamp[..4].copy_from_slice(&seconds.to_be_bytes());
+    timestamp[4..].copy_from_slice(&fraction.to_be_bytes());
+    timestamp
+}
+
+fn from_ntp_timestamp(timestamp: &[u8]) -> f64 {
+    let seconds = u32::from_be_bytes([timestamp[0], timestamp[1], timestamp[2], timestamp[3]]) as f64;
+    let fraction = u32::from_be_bytes([timestamp[4], timestamp[5], timestamp[6], timestamp[7]]) as f64;
+
+    seconds + fraction / 4_294_967_296.0 - NTP_UNIX_OFFSET
+}
+
+/// The first server from timesyncd's NTP= or ntpd's server/pool lines, if either is set up
+fn get_configured_server() -> Option<String> {
+    if let Ok(contents) = fs::read_to_string(TIMESYNCD_CONF) {
+        // NTP= holds a space separated list, and the last assignment in the file wins
+        let server = contents.lines()
+            .filter_map(|line| line.trim().strip_prefix("NTP="))
+            .next_back()
+            .and_then(|servers| servers.split_whitespace().next());
+
+        if let Some(server) = server {
+            return Some(server.to_string());
+        }
+    }
+
+    if let Ok(contents) = fs::read_to_string(NTPD_CONF) {
+        for line in contents.lines() {
+            let mut parts = line.split_whitespace();
+
+            if let (Some("server" | "pool"), Some(server)) = (parts.next(), parts.next()) {
+                return Some(server.to_string());
+            }
+        }
+    }
+
+    None
+}
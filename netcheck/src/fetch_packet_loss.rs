@@ -0,0 +1,316 @@
+use crate::internal_comms::{FetchedDataMessage, LossPattern, PacketLossInfo, send_or_stop};
+
+use std::io::Read;
+use std::net::Ipv4Addr;
+use std::sync::mpsc::Sender;
+use std::time::{Duration, Instant};
+
+use pnet::packet::icmp::echo_reply::EchoReplyPacket;
+use pnet::packet::icmp::{IcmpPacket, IcmpTypes};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::Packet;
+use socket2::Socket;
+
+use crate::fetch_internet::CLOUDFLARE_V4;
+use crate::fetch_local::get_interface_ip;
+use crate::fetch_traceroute::{open_icmp_socket, send_echo_request, SMALL_PROBE_SIZE};
+
+pub const DEFAULT_PROBE_COUNT: u16 = 100;
+// Keeps the test, at one probe per interval, comfortably inside --no-tui's overall timeout
+pub const MAX_PROBE_COUNT: u16 = 1000;
+// The packet rate of a typical VoIP call
+const PROBE_INTERVAL: Duration = Duration::from_millis(20);
+// How long after the last probe replies still count
+const LATE_REPLY_WAIT: Duration = Duration::from_secs(1);
+const PROGRESS_EVERY: usize = 10;
+// Kept clear of the sequence numbers the pings, traceroute and MTU probes use on the same identifier
+const FIRST_SEQUENCE: u16 = 5000;
+
+// A few losses can line up by chance, so evenly spaced ones only suggest a rate limit past this many
+const MIN_RATE_LIMITED_LOSSES: usize = 3;
+// How likely a loss has to be to follow another before losses count as coming in bursts
+const MIN_BURST_PERSISTENCE: f64 = 0.25;
+
+/// What --loss-count and --loss-target ask the burst test for
+#[derive(Debug, Clone, Copy)]
+pub struct LossTestSettings {
+    pub target: Ipv4Addr,
+    pub count: u16,
+}
+
+impl Default for LossTestSettings {
+    fn default() -> Self {
+        LossTestSettings {
+            target: CLOUDFLARE_V4,
+            count: DEFAULT_PROBE_COUNT,
+        }
+    }
+}
+
+pub fn fetch_and_return_packet_loss_info(tx: Sender<FetchedDataMessage>, interface: String, settings: LossTestSettings) {
+    let mut packet_loss_info = PacketLossInfo {
+        target: Some(settings.target.to_string()),
+        ..Default::default()
+    };
+
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(interface_ip) => interface_ip,
+        Err(_) => {
+            packet_loss_info.error = Some("No IP address.".to_string());
+            send_or_stop(&tx, FetchedDataMessage::PacketLossInfo(packet_loss_info));
+            return;
+        }
+    };
+
+    let socket = match open_icmp_socket(interface_ip) {
+        Ok(socket) => socket,
+        Err(_) => {
+            packet_loss_info.error = Some("Can't open ICMP socket.".to_string());
+            send_or_stop(&tx, FetchedDataMessage::PacketLossInfo(packet_loss_info));
+            return;
+        }
+    };
+
+    if !send_or_stop(&tx, FetchedDataMessage::PacketLossInfo(packet_loss_info.clone())) {
+        return;
+    }
+
+    let identifier = std::process::id() as u16;
+    let count = settings.count as usize;
+
+    let mut replied = vec![false; count];
+    let mut buf = [0u8; 1500];
+
+    // Probes go out on a fixed schedule whatever comes back, with replies collected in between
+    let started = Instant::now();
+
+    for index in 0..count {
+        let sequence = FIRST_SEQUENCE.wrapping_add(index as u16);
+
+        if send_echo_request(&socket, settings.target, identifier, sequence, SMALL_PROBE_SIZE).is_err() {
+            packet_loss_info.error = Some("Can't send echo requests.".to_string());
+            send_or_stop(&tx, FetchedDataMessage::PacketLossInfo(packet_loss_info));
+            return;
+        }
+
+        packet_loss_info.sent += 1;
+
+        let next_probe = started + PROBE_INTERVAL * (index as u32 + 1);
+        collect_replies(&socket, &mut buf, &mut replied, settings.target, identifier, next_probe);
+
+        if index % PROGRESS_EVERY == PROGRESS_EVERY - 1 {
+            packet_loss_info.received = replied.iter().filter(|replied| **replied).count() as u16;
+
+            if !send_or_stop(&tx, FetchedDataMessage::PacketLossInfo(packet_loss_info.clone())) {
+                return;
+            }
+        }
+    }
+
+    collect_replies(&socket, &mut buf, &mut replied, settings.target, identifier, Instant::now() + LATE_REPLY_WAIT);
+
+    let lost: Vec<bool> = replied.iter().map(|replied| !replied).collect();
+
+    packet_loss_info.received = replied.iter().filter(|replied| **replied).count() as u16;
+    packet_loss_info.longest_burst = loss_runs(&lost).into_iter().max().unwrap_or(0) as u16;
+
+    // Nothing coming back at all is the target ignoring pings rather than a pattern of loss
+    match packet_loss_info.received {
+        0 => packet_loss_info.error = Some("No replies, target blocks ping?".to_string()),
+        _ => packet_loss_info.loss_pattern = Some(classify_loss(&lost)),
+    }
+
+    packet_loss_info.complete = true;
+    send_or_stop(&tx, FetchedDataMessage::PacketLossInfo(packet_loss_info));
+}
+
+/// Marks off the probes whose replies arrive before `until`
+fn collect_replies(socket: &Socket, buf: &mut [u8], replied: &mut [bool], target: Ipv4Addr, identifier: u16, until: Instant) {
+    loop {
+        let remaining = match until.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => remaining,
+            _ => return
+        };
+
+        if socket.set_read_timeout(Some(remaining)).is_err() {
+            return;
+        }
+
+        // Raw ICMP sockets see every ICMP packet for the host, so filter down to replies to this test
+        let len = match (&*socket).read(buf) {
+            Ok(len) => len,
+            Err(_) => continue
+        };
+
+        let sequence = match parse_echo_reply(&buf[..len], target, identifier) {
+            Some(sequence) => sequence,
+            None => continue
+        };
+
+        if let Some(slot) = replied.get_mut(sequence.wrapping_sub(FIRST_SEQUENCE) as usize) {
+            *slot = true;
+        }
+    }
+}
+
+/// The sequence number of an echo reply from `target` to one of our requests
+fn parse_echo_reply(packet: &[u8], target: Ipv4Addr, identifier: u16) -> Option<u16> {
+    // IPv4 raw sockets hand us the IP header too
+    let packet = Ipv4Packet::new(packet)?;
+
+    if packet.get_source() != target {
+        return None;
+    }
+
+    if IcmpPacket::new(packet.payload())?.get_icmp_type() != IcmpTypes::EchoReply {
+        return None;
+    }
+
+    let echo = EchoReplyPacket::new(packet.payload())?;
+
+    match echo.get_identifier() == identifier {
+        true => Some(echo.get_sequence_number()),
+        false => None,
+    }
+}
+
+/// The lengths of each run of consecutive losses, in order
+fn loss_runs(lost: &[bool]) -> Vec<usize> {
+    let mut runs = Vec::new();
+    let mut current = 0;
+
+    for &lost in lost {
+        match lost {
+            true => current += 1,
+            false if current > 0 => {
+                runs.push(current);
+                current = 0;
+            }
+            false => {}
+        }
+    }
+
+    if current > 0 {
+        runs.push(current);
+    }
+
+    runs
+}
+
+/// Works out what kind of loss this was with a two state (Gilbert-Elliott) model: how likely a
+/// loss is after a reply, against how likely one is after another loss. Independent losses make
+/// the two about equal, while losses that stick around mean something like a full buffer.
+fn classify_loss(lost: &[bool]) -> LossPattern {
+    let runs = loss_runs(lost);
+    let losses: usize = runs.iter().sum();
+
+    if losses == 0 {
+        return LossPattern::None;
+    }
+
+    // A limiter letting so many packets a second through drops single packets at a steady rate
+    if losses >= MIN_RATE_LIMITED_LOSSES && runs.iter().all(|run| *run == 1) {
+        let positions: Vec<usize> = lost.iter().enumerate().filter(|(_, lost)| **lost).map(|(index, _)| index).collect();
+        let gaps: Vec<usize> = positions.windows(2).map(|pair| pair[1] - pair[0]).collect();
+
+        let shortest = gaps.iter().min().copied().unwrap_or(0);
+        let longest = gaps.iter().max().copied().unwrap_or(0);
+
+        if longest - shortest <= 1 {
+            return LossPattern::RateLimited;
+        }
+    }
+
+    let (mut after_reply, mut lost_after_reply, mut after_loss, mut lost_after_loss) = (0, 0, 0, 0);
+
+    for pair in lost.windows(2) {
+        match pair[0] {
+            false => {
+                after_reply += 1;
+                lost_after_reply += pair[1] as usize;
+            }
+            true => {
+                after_loss += 1;
+                lost_after_loss += pair[1] as usize;
+            }
+        }
+    }
+
+    let ratio = |part: usize, whole: usize| match whole {
+        0 => 0.0,
+        _ => part as f64 / whole as f64,
+    };
+
+    let entering_burst = ratio(lost_after_reply, after_reply);
+    let staying_in_burst = ratio(lost_after_loss, after_loss);
+
+    if staying_in_burst >= MIN_BURST_PERSISTENCE && staying_in_burst > entering_burst * 2.0 {
+        let average_burst = (losses as f64 / runs.len() as f64).round().min(u8::MAX as f64) as u8;
+        return LossPattern::Bursty(average_burst);
+    }
+
+    LossPattern::Random
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 100 probes with the given ones lost
+    fn lost_at(indices: &[usize]) -> Vec<bool> {
+        let mut lost = vec![false; 100];
+
+        for &index in indices {
+            lost[index] = true;
+        }
+
+        lost
+    }
+
+    #[test]
+    fn no_loss_has_no_pattern() {
+        assert_eq!(classify_loss(&lost_at(&[])), LossPattern::None);
+    }
+
+    #[test]
+    fn scattered_losses_are_random() {
+        assert_eq!(classify_loss(&lost_at(&[3, 17, 18, 52, 90])), LossPattern::Random);
+        assert_eq!(classify_loss(&lost_at(&[40])), LossPattern::Random);
+    }
+
+    #[test]
+    fn runs_of_losses_are_bursty() {
+        assert_eq!(classify_loss(&lost_at(&[10, 11, 12, 13, 60, 61, 62, 63])), LossPattern::Bursty(4));
+        assert_eq!(classify_loss(&lost_at(&[30, 31, 32])), LossPattern::Bursty(3));
+    }
+
+    #[test]
+    fn evenly_spaced_single_losses_are_rate_limiting() {
+        let every_tenth: Vec<usize> = (0..100).step_by(10).collect();
+        assert_eq!(classify_loss(&lost_at(&every_tenth)), LossPattern::RateLimited);
+
+        // Every other packet past a token bucket's allowance
+        let every_other: Vec<usize> = (50..100).step_by(2).collect();
+        assert_eq!(classify_loss(&lost_at(&every_other)), LossPattern::RateLimited);
+    }
+
+    #[test]
+    fn loss_runs_include_one_at_the_end() {
+        assert_eq!(loss_runs(&[true, false, true, true, false, false, true, true, true]), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn parses_only_our_replies_from_the_target() {
+        // 20 byte IPv4 header from 1.1.1.1, then an echo reply with identifier 0x1234 and sequence 5007
+        let mut packet = vec![0x45, 0, 0, 36, 0, 0, 0, 0, 64, 1, 0, 0, 1, 1, 1, 1, 192, 168, 1, 23];
+        packet.extend([0, 0, 0, 0, 0x12, 0x34, 0x13, 0x8f, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        assert_eq!(parse_echo_reply(&packet, CLOUDFLARE_V4, 0x1234), Some(5007));
+        assert_eq!(parse_echo_reply(&packet, CLOUDFLARE_V4, 0x4321), None);
+        assert_eq!(parse_echo_reply(&packet, Ipv4Addr::new(8, 8, 8, 8), 0x1234), None);
+
+        // An echo request isn't a reply
+        packet[20] = 8;
+        assert_eq!(parse_echo_reply(&packet, CLOUDFLARE_V4, 0x1234), None);
+    }
+}
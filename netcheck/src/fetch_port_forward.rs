@@ -0,0 +1,92 @@
+use crate::internal_comms::PortForwardInfo;
+
+use std::net::IpAddr;
+
+use crate::fetch_internet::https_get;
+
+const PORT_CHECKER_HOST: &str = "portchecker.co";
+
+/// Asks portchecker.co to connect to the port on the public IP, which is the only way to see
+/// whether a forward works: connecting to the public IP from inside relies on the router
+/// supporting hairpin NAT, and says nothing about what the rest of the internet gets
+pub fn check_port_forward(interface_ip: IpAddr, public_ip: IpAddr, port: u16) -> PortForwardInfo {
+    let path = format!("/api/v1/query?host={}&port={}", public_ip, port);
+
+    let body = match https_get(interface_ip, PORT_CHECKER_HOST, 443, &path) {
+        Ok(body) => body,
+        Err(_) => {
+            return PortForwardInfo {
+                port,
+                error: Some("Couldn't reach portchecker.co.".to_string()),
+                ..Default::default()
+            };
+        }
+    };
+
+    parse_port_check(&body, port)
+}
+
+/// Reads the result for `port` out of a response like
+/// {"error": false, "host": "203.0.113.7", "check": [{"port": 25565, "status": true}]}
+fn parse_port_check(body: &str, port: u16) -> PortForwardInfo {
+    let mut port_forward_info = PortForwardInfo {
+        port,
+        complete: true,
+        ..Default::default()
+    };
+
+    let response = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(response) => response,
+        Err(_) => {
+            port_forward_info.error = Some("Unexpected response from portchecker.co.".to_string());
+            return port_forward_info;
+        }
+    };
+
+    if response["error"].as_bool() == Some(true) {
+        port_forward_info.error = Some("portchecker.co couldn't check the port.".to_string());
+        return port_forward_info;
+    }
+
+    let result = response["check"].as_array()
+        .and_then(|checks| checks.iter().find(|check| check["port"].as_u64() == Some(port as u64)));
+
+    match result.and_then(|result| result["status"].as_bool()) {
+        Some(reachable) => port_forward_info.externally_reachable = Some(reachable),
+        None => port_forward_info.error = Some("Unexpected response from portchecker.co.".to_string()),
+    }
+
+    port_forward_info
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_status_of_the_checked_port() {
+        let body = r#"{"error": false, "host": "203.0.113.7", "check": [{"port": 25565, "status": true}]}"#;
+        let port_forward_info = parse_port_check(body, 25565);
+
+        assert_eq!(port_forward_info.error, None);
+        assert_eq!(port_forward_info.externally_reachable, Some(true));
+
+        let body = r#"{"error": false, "host": "203.0.113.7", "check": [{"port": 25565, "status": false}]}"#;
+        assert_eq!(parse_port_check(body, 25565).externally_reachable, Some(false));
+    }
+
+    #[test]
+    fn other_ports_and_service_errors_are_not_results() {
+        let body = r#"{"error": false, "host": "203.0.113.7", "check": [{"port": 80, "status": true}]}"#;
+        let port_forward_info = parse_port_check(body, 25565);
+
+        assert_eq!(port_forward_info.externally_reachable, None);
+        assert!(port_forward_info.error.is_some());
+
+        let body = r#"{"error": true, "message": "invalid host"}"#;
+        let port_forward_info = parse_port_check(body, 25565);
+
+        assert_eq!(port_forward_info.externally_reachable, None);
+        assert!(port_forward_info.error.is_some());
+    }
+}
@@ -0,0 +1,568 @@
+use crate::internal_comms::{FetchedDataMessage, QUICInfo, TCPInfo, send_or_stop};
+
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::Sender;
+use std::sync::Arc;
+use std::time::Duration;
+
+use quinn::{Connection, Endpoint};
+
+use crate::fetch_internet::{CLOUDFLARE_V4, CLOUDFLARE_V6};
+use crate::fetch_local::get_interface_ip;
+
+const GOOGLE_HOST: &str = "www.google.com";
+const QUIC_PORT: u16 = 443;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+
+// Cloudflare's diagnostic page, a short plain text 200 on any of its addresses
+const HTTP3_PATH: &str = "/cdn-cgi/trace";
+const HTTP3_TIMEOUT: Duration = Duration::from_secs(3);
+// Far more than the trace page needs, it only caps what a misbehaving server can make us buffer
+const HTTP3_MAX_RESPONSE: usize = 64 * 1024;
+
+// HTTP/3 stream and frame types (RFC 9114)
+const H3_STREAM_TYPE_CONTROL: u64 = 0x00;
+const H3_FRAME_HEADERS: u64 = 0x01;
+const H3_FRAME_SETTINGS: u64 = 0x04;
+
+// QPACK static table indices (RFC 9204 Appendix A)
+const QPACK_AUTHORITY: u8 = 0;
+const QPACK_PATH: u8 = 1;
+const QPACK_METHOD_GET: u8 = 17;
+const QPACK_SCHEME_HTTPS: u8 = 23;
+
+pub fn fetch_and_return_quic_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(interface_ip) => interface_ip,
+        Err(_) => {
+            send_or_stop(&tx, FetchedDataMessage::QUICInfo(QUICInfo {
+                error: Some("No IP address.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build();
+
+    let runtime = match runtime {
+        Ok(runtime) => runtime,
+        Err(_) => {
+            send_or_stop(&tx, FetchedDataMessage::QUICInfo(QUICInfo {
+                error: Some("Couldn't start runtime.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let quic_info = runtime.block_on(check_quic(interface_ip));
+
+    send_or_stop(&tx, FetchedDataMessage::QUICInfo(quic_info));
+}
+
+/// Handshakes with 1.1.1.1 and Google at the same time over one endpoint bound to the interface.
+/// If the endpoint can't be bound both results stay None, since nothing was actually tested.
+async fn check_quic(interface_ip: IpAddr) -> QUICInfo {
+    let mut quic_info = QUICInfo {
+        complete: true,
+        ..Default::default()
+    };
+
+    let endpoint = match make_endpoint(interface_ip) {
+        Ok(endpoint) => endpoint,
+        Err(_) => return quic_info
+    };
+
+    let cloudflare = match interface_ip {
+        IpAddr::V4(_) => IpAddr::V4(CLOUDFLARE_V4),
+        IpAddr::V6(_) => IpAddr::V6(CLOUDFLARE_V6),
+    };
+
+    let google = match (GOOGLE_HOST, QUIC_PORT).to_socket_addrs() {
+        Ok(mut addrs) => addrs.find(|addr| addr.is_ipv4() == interface_ip.is_ipv4()),
+        Err(_) => None
+    };
+
+    let authority = cloudflare.to_string();
+    let cloudflare_endpoint = endpoint.clone();
+
+    // Only 1.1.1.1 gets the HTTP/3 request, Google's handshake is there to compare against
+    let cloudflare_check = tokio::spawn(async move {
+        let connection = handshake(cloudflare_endpoint, SocketAddr::new(cloudflare, QUIC_PORT), authority.clone()).await?;
+        let rtt = connection.rtt();
+        let http3_supported = check_http3(&connection, &authority).await;
+        connection.close(0u32.into(), b"done");
+
+        Ok::<_, ()>((rtt, http3_supported))
+    });
+
+    let google_check = tokio::spawn(async move {
+        match google {
+            Some(google) => Some(handshake(endpoint, google, GOOGLE_HOST.to_string()).await.map(|connection| {
+                connection.close(0u32.into(), b"done");
+                connection.rtt()
+            })),
+            None => None
+        }
+    });
+
+    let cloudflare_result = cloudflare_check.await.unwrap_or(Err(()));
+    let google_rtt = google_check.await.unwrap_or(None);
+
+    quic_info.can_access_1111 = Some(cloudflare_result.is_ok());
+    quic_info.can_access_google = google_rtt.map(|rtt| rtt.is_ok());
+    quic_info.http3_supported = cloudflare_result.map(|(_, http3_supported)| http3_supported).ok();
+
+    // 1.1.1.1's RTT is preferred since it's the one we ping elsewhere too
+    quic_info.quic_rtt_ms = cloudflare_result.ok().map(|(rtt, _)| rtt)
+        .or(google_rtt.and_then(|rtt| rtt.ok()))
+        .map(|rtt| rtt.as_secs_f64() * 1000.0);
+
+    quic_info
+}
+
+/// Both checks go to 1.1.1.1, so QUIC failing where a TCP connection to port 443 worked means
+/// something between here and there drops UDP 443 in particular. None until both have an answer.
+pub fn is_quic_port_blocked(quic_info: &QUICInfo, tcp_info: &TCPInfo) -> Option<bool> {
+    if !quic_info.complete || quic_info.error.is_some() {
+        return None;
+    }
+
+    let tcp_443_works = tcp_info.attempted_to_talk_on_list.iter()
+        .find(|(port, _)| *port == QUIC_PORT)
+        .map(|(_, success)| *success);
+
+    match (quic_info.can_access_1111, tcp_443_works) {
+        (Some(quic_works), Some(tcp_443_works)) => Some(tcp_443_works && !quic_works),
+        _ => None
+    }
+}
+
+fn make_endpoint(interface_ip: IpAddr) -> Result<Endpoint, ()> {
+    let mut roots = rustls::RootCertStore::empty();
+    roots.extend(webpki_roots::TLS_SERVER_ROOTS.iter().cloned());
+
+    let tls_config = rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_protocol_versions(&[&rustls::version::TLS13]);
+
+    let mut tls_config = match tls_config {
+        Ok(tls_config) => tls_config.with_root_certificates(roots).with_no_client_auth(),
+        Err(_) => return Err(())
+    };
+
+    tls_config.alpn_protocols = vec![b"h3".to_vec()];
+
+    let quic_config = match quinn::crypto::rustls::QuicClientConfig::try_from(tls_config) {
+        Ok(quic_config) => quic_config,
+        Err(_) => return Err(())
+    };
+
+    let mut endpoint = match Endpoint::client(SocketAddr::new(interface_ip, 0)) {
+        Ok(endpoint) => endpoint,
+        Err(_) => return Err(())
+    };
+
+    endpoint.set_default_client_config(quinn::ClientConfig::new(Arc::new(quic_config)));
+
+    Ok(endpoint)
+}
+
+/// Completes a QUIC handshake and returns the connection. A timeout here almost always
+/// means UDP 443 is being dropped, as networks that block QUIC tend to do.
+async fn handshake(endpoint: Endpoint, remote: SocketAddr, server_name: String) -> Result<Connection, ()> {
+    let connecting = match endpoint.connect(remote, &server_name) {
+        Ok(connecting) => connecting,
+        Err(_) => return Err(())
+    };
+
+    match tokio::time::timeout(HANDSHAKE_TIMEOUT, connecting).await {
+        Ok(Ok(connection)) => Ok(connection),
+        _ => Err(())
+    }
+}
+
+/// A handshake only proves the transport works. Middleboxes that let QUIC through can still
+/// break what runs over it, so this makes a real request and wants a 200 back.
+async fn check_http3(connection: &Connection, authority: &str) -> bool {
+    match tokio::time::timeout(HTTP3_TIMEOUT, http3_get(connection, authority, HTTP3_PATH)).await {
+        Ok(Ok(status)) => status == 200,
+        _ => false
+    }
+}
+
+/// Makes a GET request over HTTP/3 and returns the response's status code
+async fn http3_get(connection: &Connection, authority: &str, path: &str) -> Result<u16, ()> {
+    // Each side has to open a control stream and start it with its SETTINGS. Leaving them all at
+    // their defaults keeps QPACK's dynamic table off, so the response can only use the static one
+    let mut control = match connection.open_uni().await {
+        Ok(control) => control,
+        Err(_) => return Err(())
+    };
+
+    let mut preface = Vec::new();
+    encode_varint(&mut preface, H3_STREAM_TYPE_CONTROL);
+    encode_varint(&mut preface, H3_FRAME_SETTINGS);
+    encode_varint(&mut preface, 0);
+
+    if control.write_all(&preface).await.is_err() {
+        return Err(());
+    }
+
+    let (mut send, mut recv) = match connection.open_bi().await {
+        Ok(streams) => streams,
+        Err(_) => return Err(())
+    };
+
+    if send.write_all(&encode_request_headers(authority, path)).await.is_err() || send.finish().is_err() {
+        return Err(());
+    }
+
+    let response = match recv.read_to_end(HTTP3_MAX_RESPONSE).await {
+        Ok(response) => response,
+        Err(_) => return Err(())
+    };
+
+    // Closing the control stream early is an error, so it's only dropped once the response is in
+    drop(control);
+
+    parse_response_status(&response).ok_or(())
+}
+
+/// A HEADERS frame for a GET, using the static table where it has the whole field
+fn encode_request_headers(authority: &str, path: &str) -> Vec<u8> {
+    // Required Insert Count and Delta Base, both 0 without a dynamic table
+    let mut field_section = vec![0x00, 0x00];
+
+    // Indexed field lines: 1, T set for the static table, then a 6 bit index
+    field_section.push(0xc0 | QPACK_METHOD_GET);
+    field_section.push(0xc0 | QPACK_SCHEME_HTTPS);
+
+    for (name_index, value) in [(QPACK_AUTHORITY, authority), (QPACK_PATH, path)] {
+        // Literal with a name reference: 01, N clear, T set for the static table, then a 4 bit index.
+        // The value follows as a length with the Huffman bit clear, then the bytes themselves
+        field_section.push(0x50 | name_index);
+        encode_prefixed_int(&mut field_section, 0x00, 7, value.len() as u64);
+        field_section.extend_from_slice(value.as_bytes());
+    }
+
+    let mut frame = Vec::with_capacity(field_section.len() + 3);
+    encode_varint(&mut frame, H3_FRAME_HEADERS);
+    encode_varint(&mut frame, field_section.len() as u64);
+    frame.extend_from_slice(&field_section);
+
+    frame
+}
+
+/// The status code from the first HEADERS frame in a response stream, skipping anything before it
+fn parse_response_status(response: &[u8]) -> Option<u16> {
+    let mut rest = response;
+
+    while !rest.is_empty() {
+        let (frame_type, type_len) = decode_varint(rest)?;
+        let (len, len_len) = decode_varint(rest.get(type_len..)?)?;
+        let start = type_len + len_len;
+        let payload = rest.get(start..)?.get(..usize::try_from(len).ok()?)?;
+
+        if frame_type == H3_FRAME_HEADERS {
+            return parse_status_field(payload);
+        }
+
+        rest = &rest[start + payload.len()..];
+    }
+
+    None
+}
+
+/// Reads :status from a QPACK field section. Pseudo-headers come first, so it's the first line,
+/// either a whole entry from the static table or a literal value with its name from there.
+fn parse_status_field(field_section: &[u8]) -> Option<u16> {
+    let (required_insert_count, count_len) = decode_prefixed_int(field_section, 8)?;
+
+    // We never allowed a dynamic table, so a server referring to one is broken
+    if required_insert_count != 0 {
+        return None;
+    }
+
+    let (_, base_len) = decode_prefixed_int(field_section.get(count_len..)?, 7)?;
+    let lines = field_section.get(count_len + base_len..)?;
+    let first = *lines.first()?;
+
+    // Indexed field line from the static table
+    if first & 0xc0 == 0xc0 {
+        let (index, _) = decode_prefixed_int(lines, 6)?;
+        return static_status(index);
+    }
+
+    // Literal with a static name reference, whatever the N bit says
+    if first & 0xd0 == 0x50 {
+        let (name_index, name_len) = decode_prefixed_int(lines, 4)?;
+
+        // Every :status entry has a value, so this catches names other than :status
+        static_status(name_index)?;
+
+        let value = lines.get(name_len..)?;
+        let huffman = *value.first()? & 0x80 != 0;
+        let (value_len, value_len_len) = decode_prefixed_int(value, 7)?;
+        let value = value.get(value_len_len..)?.get(..usize::try_from(value_len).ok()?)?;
+
+        let digits = match huffman {
+            true => decode_huffman_digits(value)?,
+            false => String::from_utf8(value.to_vec()).ok()?,
+        };
+
+        return digits.parse().ok();
+    }
+
+    None
+}
+
+/// The status codes in QPACK's static table, by index
+fn static_status(index: u64) -> Option<u16> {
+    match index {
+        24 => Some(103),
+        25 => Some(200),
+        26 => Some(304),
+        27 => Some(404),
+        28 => Some(503),
+        63 => Some(100),
+        64 => Some(204),
+        65 => Some(206),
+        66 => Some(302),
+        67 => Some(400),
+        68 => Some(403),
+        69 => Some(421),
+        70 => Some(425),
+        71 => Some(500),
+        _ => None
+    }
+}
+
+/// Decodes a Huffman coded string (RFC 7541 Appendix B) made only of digits, which is all a
+/// status code can be. '0' to '2' are 5 bit codes and '3' to '9' the 6 bit codes 011001 to 011111.
+fn decode_huffman_digits(bytes: &[u8]) -> Option<String> {
+    let total_bits = bytes.len() * 8;
+    let read = |start: usize, count: usize| {
+        (start..start + count).fold(0u8, |value, bit| (value << 1) | ((bytes[bit / 8] >> (7 - bit % 8)) & 1))
+    };
+
+    let mut position = 0;
+    let mut digits = String::new();
+
+    while total_bits - position >= 5 {
+        let code = read(position, 5);
+
+        if code <= 2 {
+            digits.push((b'0' + code) as char);
+            position += 5;
+            continue;
+        }
+
+        if total_bits - position >= 6 {
+            let code = read(position, 6);
+
+            if (0x19..=0x1f).contains(&code) {
+                digits.push((b'3' + code - 0x19) as char);
+                position += 6;
+                continue;
+            }
+        }
+
+        break;
+    }
+
+    // What's left has to be padding, which is under a byte of the EOS code's leading ones
+    let padding = total_bits - position;
+
+    match padding < 8 && read(position, padding) == (1u8 << padding) - 1 {
+        true => Some(digits),
+        false => None
+    }
+}
+
+/// QUIC's variable-length integer (RFC 9000 section 16): the top two bits give the length
+fn encode_varint(out: &mut Vec<u8>, value: u64) {
+    match value {
+        0..=0x3f => out.push(value as u8),
+        0x40..=0x3fff => out.extend_from_slice(&(value as u16 | 0x4000).to_be_bytes()),
+        0x4000..=0x3fff_ffff => out.extend_from_slice(&(value as u32 | 0x8000_0000).to_be_bytes()),
+        _ => out.extend_from_slice(&(value | 0xc000_0000_0000_0000).to_be_bytes()),
+    }
+}
+
+/// Returns the value and how many bytes it took up
+fn decode_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let first = *bytes.first()?;
+    let len = 1 << (first >> 6);
+
+    let value = bytes.get(1..len)?
+        .iter()
+        .fold((first & 0x3f) as u64, |value, byte| value << 8 | *byte as u64);
+
+    Some((value, len))
+}
+
+/// HPACK's prefixed integer (RFC 7541 section 5.1), which QPACK reuses. `flags` fills the bits above the prefix
+fn encode_prefixed_int(out: &mut Vec<u8>, flags: u8, prefix_bits: u8, value: u64) {
+    let max = (1u64 << prefix_bits) - 1;
+
+    if value < max {
+        out.push(flags | value as u8);
+        return;
+    }
+
+    out.push(flags | max as u8);
+
+    let mut rest = value - max;
+
+    while rest >= 0x80 {
+        out.push((rest & 0x7f) as u8 | 0x80);
+        rest >>= 7;
+    }
+
+    out.push(rest as u8);
+}
+
+/// Returns the value and how many bytes it took up, ignoring whatever's above the prefix
+fn decode_prefixed_int(bytes: &[u8], prefix_bits: u8) -> Option<(u64, usize)> {
+    let max = (1u64 << prefix_bits) - 1;
+    let value = *bytes.first()? as u64 & max;
+
+    if value < max {
+        return Some((value, 1));
+    }
+
+    let mut value = max;
+
+    for (index, byte) in bytes.iter().enumerate().skip(1) {
+        let shift = 7 * (index - 1);
+
+        // More continuation bytes than a u64 can hold
+        if shift > 56 {
+            return None;
+        }
+
+        value += ((byte & 0x7f) as u64) << shift;
+
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quic(can_access_1111: Option<bool>) -> QUICInfo {
+        QUICInfo {
+            complete: true,
+            can_access_1111,
+            ..Default::default()
+        }
+    }
+
+    fn tcp(port_443: Option<bool>) -> TCPInfo {
+        TCPInfo {
+            attempted_to_talk_on_list: [(80, true)].into_iter().chain(port_443.map(|success| (443, success))).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn quic_failing_with_tcp_working_is_blocked() {
+        assert_eq!(is_quic_port_blocked(&quic(Some(false)), &tcp(Some(true))), Some(true));
+    }
+
+    #[test]
+    fn quic_working_is_not_blocked() {
+        assert_eq!(is_quic_port_blocked(&quic(Some(true)), &tcp(Some(true))), Some(false));
+    }
+
+    #[test]
+    fn both_failing_is_not_a_quic_problem() {
+        // Nothing gets to 443 at all, which the TCP panel already shows
+        assert_eq!(is_quic_port_blocked(&quic(Some(false)), &tcp(Some(false))), Some(false));
+    }
+
+    #[test]
+    fn unknown_until_both_have_finished() {
+        assert_eq!(is_quic_port_blocked(&quic(Some(false)), &tcp(None)), None);
+        assert_eq!(is_quic_port_blocked(&QUICInfo::default(), &tcp(Some(true))), None);
+        assert_eq!(is_quic_port_blocked(&quic(None), &tcp(Some(true))), None);
+    }
+
+    #[test]
+    fn varints() {
+        for (value, encoded) in [(37, vec![0x25]), (15293, vec![0x7b, 0xbd]), (494878333, vec![0x9d, 0x7f, 0x3e, 0x7d])] {
+            let mut out = Vec::new();
+            encode_varint(&mut out, value);
+
+            assert_eq!(out, encoded);
+            assert_eq!(decode_varint(&encoded), Some((value, encoded.len())));
+        }
+
+        assert_eq!(decode_varint(&[0x7b]), None);
+    }
+
+    #[test]
+    fn prefixed_ints() {
+        // RFC 7541 C.1: 1337 with a 5 bit prefix
+        let mut out = Vec::new();
+        encode_prefixed_int(&mut out, 0x00, 5, 1337);
+
+        assert_eq!(out, vec![0x1f, 0x9a, 0x0a]);
+        assert_eq!(decode_prefixed_int(&out, 5), Some((1337, 3)));
+        assert_eq!(decode_prefixed_int(&[0xea], 5), Some((10, 1)));
+    }
+
+    #[test]
+    fn request_headers() {
+        let frame = encode_request_headers("1.1.1.1", "/cdn-cgi/trace");
+
+        let mut expected = vec![0x01, 0x1d, 0x00, 0x00, 0xd1, 0xd7, 0x50, 0x07];
+        expected.extend_from_slice(b"1.1.1.1");
+        expected.extend_from_slice(&[0x51, 0x0e]);
+        expected.extend_from_slice(b"/cdn-cgi/trace");
+
+        assert_eq!(frame, expected);
+    }
+
+    #[test]
+    fn status_from_the_static_table() {
+        // Indexed :status 200, then a DATA frame
+        let response = [0x01, 0x03, 0x00, 0x00, 0xd9, 0x00, 0x02, b'o', b'k'];
+
+        assert_eq!(parse_response_status(&response), Some(200));
+    }
+
+    #[test]
+    fn status_as_a_literal() {
+        // :status with the name from index 24 and "429" as the plain value, after a reserved frame type to skip
+        let response = [0x21, 0x01, 0xff, 0x01, 0x08, 0x00, 0x00, 0x5f, 0x09, 0x03, b'4', b'2', b'9'];
+
+        assert_eq!(parse_response_status(&response), Some(429));
+    }
+
+    #[test]
+    fn huffman_coded_status() {
+        // "200" is 00010 00000 00000 and a bit of padding
+        assert_eq!(decode_huffman_digits(&[0x10, 0x01]), Some("200".to_string()));
+        // "429" is 011010 00010 011111 and 7 bits of padding
+        assert_eq!(decode_huffman_digits(&[0x68, 0x4f, 0xff]), Some("429".to_string()));
+        // Padding of zeros isn't allowed
+        assert_eq!(decode_huffman_digits(&[0x10, 0x00]), None);
+
+        let response = [0x01, 0x07, 0x00, 0x00, 0x5f, 0x0a, 0x82, 0x10, 0x01];
+        assert_eq!(parse_response_status(&response), Some(200));
+    }
+
+    #[test]
+    fn dynamic_table_references_are_rejected() {
+        assert_eq!(parse_response_status(&[0x01, 0x03, 0x01, 0x00, 0x80]), None);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::internal_comms::{FetchedDataMessage, TCPInfo, send_or_stop};
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+use crate::fetch_internet::{CLOUDFLARE_V4, CLOUDFLARE_V6};
+use crate::fetch_local::get_interface_ip;
+
+// SSH, HTTP, HTTPS, HTTP alternate and DNS over TLS
+const TCP_PORTS: [u16; 5] = [22, 80, 443, 8080, 853];
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub fn fetch_and_return_tcp_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(interface_ip) => interface_ip,
+        Err(_) => {
+            send_or_stop(&tx, FetchedDataMessage::TCPInfo(TCPInfo {
+                error: Some("No IP address.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let target = match interface_ip {
+        IpAddr::V4(_) => IpAddr::V4(CLOUDFLARE_V4),
+        IpAddr::V6(_) => IpAddr::V6(CLOUDFLARE_V6),
+    };
+
+    // Every port gets its own thread so filtered ports don't hold up the rest
+    let (result_tx, result_rx) = mpsc::channel();
+
+    for port in TCP_PORTS {
+        let result_tx = result_tx.clone();
+
+        thread::spawn(move || {
+            let success = check_tcp_port(interface_ip, SocketAddr::new(target, port));
+            // The fetcher may have stopped early, leaving nobody to take the result
+            let _ = result_tx.send((port, success));
+        });
+    }
+
+    drop(result_tx);
+
+    let mut tcp_info = TCPInfo::default();
+
+    for result in result_rx {
+        tcp_info.attempted_to_talk_on_list.push(result);
+
+        // Keep the panel in a stable order however the results arrive
+        tcp_info.attempted_to_talk_on_list.sort_by_key(|(port, _)| TCP_PORTS.iter().position(|p| p == port));
+
+        if !send_or_stop(&tx, FetchedDataMessage::TCPInfo(tcp_info.clone())) {
+            return;
+        }
+    }
+
+    tcp_info.complete = true;
+    send_or_stop(&tx, FetchedDataMessage::TCPInfo(tcp_info));
+}
+
+/// Tries a TCP handshake with `remote`, leaving from the interface's address
+fn check_tcp_port(interface_ip: IpAddr, remote: SocketAddr) -> bool {
+    let socket = match Socket::new(Domain::for_address(remote), Type::STREAM, Some(Protocol::TCP)) {
+        Ok(socket) => socket,
+        Err(_) => return false
+    };
+
+    if socket.bind(&SockAddr::from(SocketAddr::new(interface_ip, 0))).is_err() {
+        return false;
+    }
+
+    socket.connect_timeout(&SockAddr::from(remote), CONNECT_TIMEOUT).is_ok()
+}
@@ -0,0 +1,562 @@
+use crate::internal_comms::{FetchedDataMessage, MplsLabel, Traceroute, TracerouteHop, send_or_stop};
+
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::Sender;
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use pnet::ipnetwork::IpNetwork;
+use pnet::packet::icmp::echo_request::{EchoRequestPacket, MutableEchoRequestPacket};
+use pnet::packet::icmp::{IcmpPacket, IcmpTypes};
+use pnet::packet::ipv4::Ipv4Packet;
+use pnet::packet::Packet;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+use crate::fetch_internet::CLOUDFLARE_V4;
+use crate::fetch_local::{get_interface_ip, get_mtu};
+
+const MAX_HOPS: u8 = 30;
+const PROBES_PER_HOP: u16 = 3;
+const PROBE_TIMEOUT: Duration = Duration::from_secs(1);
+
+// ICMP extension objects (RFC 4884) carrying an MPLS label stack (RFC 4950)
+const ICMP_EXTENSION_VERSION: u8 = 2;
+const MPLS_LABEL_STACK_CLASS: u8 = 1;
+const MPLS_INCOMING_STACK_TYPE: u8 = 1;
+
+// Link MTUs worth trying below the interface's own: PPPoE, PPPoE over VLANs, common tunnels,
+// IPv6's minimum and IPv4's minimum reassembly size
+const COMMON_MTUS: [u16; 7] = [1500, 1492, 1480, 1450, 1400, 1280, 576];
+// Kept clear of the sequence numbers the pings use on the same identifier
+const MTU_PROBE_FIRST_SEQUENCE: u16 = 4000;
+// 20 byte IPv4 header and 8 byte ICMP header, with a little payload
+pub const SMALL_PROBE_SIZE: usize = 36;
+
+// Peering LANs of the major exchanges, see the file for where they come from
+const IXP_SUBNETS: &str = include_str!("ixp_subnets.txt");
+
+/// What came back about a probe
+pub struct ProbeResponse {
+    pub from: Ipv4Addr,
+    pub latency: f64,
+    // Whether it was the target answering rather than a router on the way
+    pub is_reply: bool,
+    pub mpls_labels: Vec<MplsLabel>,
+    // What was left of the answer's TTL when it got here
+    pub ttl: u8,
+}
+
+pub fn fetch_and_return_traceroute(tx: Sender<FetchedDataMessage>, interface: String) {
+    let mut traceroute = Traceroute::default();
+
+    let target = match get_traceroute_target() {
+        Some(target) => target,
+        None => {
+            traceroute.error = Some("Can't resolve target.".to_string());
+            send_or_stop(&tx, FetchedDataMessage::Traceroute(traceroute));
+            return;
+        }
+    };
+
+    traceroute.target = Some(target.to_string());
+
+    // pnet's transport channel can't be bound to a source address, so this uses
+    // a raw socket directly and pnet only for building and parsing the packets
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(interface_ip) => interface_ip,
+        Err(_) => {
+            traceroute.error = Some("No IP address.".to_string());
+            send_or_stop(&tx, FetchedDataMessage::Traceroute(traceroute));
+            return;
+        }
+    };
+
+    let socket = match open_icmp_socket(interface_ip) {
+        Ok(socket) => socket,
+        Err(_) => {
+            traceroute.error = Some("Can't open ICMP socket.".to_string());
+            send_or_stop(&tx, FetchedDataMessage::Traceroute(traceroute));
+            return;
+        }
+    };
+
+    if !send_or_stop(&tx, FetchedDataMessage::Traceroute(traceroute.clone())) {
+        return;
+    }
+
+    let identifier = std::process::id() as u16;
+
+    for ttl in 1..=MAX_HOPS {
+        if socket.set_ttl(ttl as u32).is_err() {
+            traceroute.error = Some("Can't set TTL.".to_string());
+            break;
+        }
+
+        let mut responder = None;
+        let mut latencies = Vec::with_capacity(PROBES_PER_HOP as usize);
+        let mut reached_target = false;
+        let mut mpls_labels = Vec::new();
+
+        for probe in 0..PROBES_PER_HOP {
+            let sequence = ttl as u16 * PROBES_PER_HOP + probe;
+
+            if let Some(response) = send_probe(&socket, target, identifier, sequence, PROBE_TIMEOUT) {
+                responder = Some(response.from);
+                latencies.push(response.latency);
+                reached_target |= response.is_reply;
+
+                if !response.mpls_labels.is_empty() {
+                    mpls_labels = response.mpls_labels;
+                }
+            }
+        }
+
+        let hop = match responder {
+            Some(ip) => TracerouteHop {
+                hop_number: ttl,
+                ip: ip.to_string(),
+                latency: latencies.iter().sum::<f64>() / latencies.len() as f64,
+                jitter: calculate_jitter(&latencies),
+                location: None,
+                mpls_labels,
+                link_mtu: None,
+                ixp: find_ixp(ip),
+            },
+            None => TracerouteHop {
+                hop_number: ttl,
+                ip: "*".to_string(),
+                latency: -1.0,
+                jitter: -1.0,
+                location: None,
+                mpls_labels: Vec::new(),
+                link_mtu: None,
+                ixp: None,
+            },
+        };
+
+        traceroute.hops.push(hop);
+        if !send_or_stop(&tx, FetchedDataMessage::Traceroute(traceroute.clone())) {
+            return;
+        }
+
+        if reached_target {
+            break;
+        }
+    }
+
+    // Without the interface's MTU there's nothing to start the search from or compare against
+    if let Ok(interface_mtu) = get_mtu(&interface) {
+        let interface_mtu = u16::try_from(interface_mtu).unwrap_or(u16::MAX);
+
+        if !measure_hop_mtus(&tx, &mut traceroute, interface_ip, interface_mtu, identifier) {
+            return;
+        }
+    }
+
+    traceroute.complete = true;
+    send_or_stop(&tx, FetchedDataMessage::Traceroute(traceroute));
+}
+
+/// The second pass: the largest packet that reaches each hop with the DF bit set. Nothing bigger
+/// gets to a hop than got to the one before it, so each search starts where the last one stopped.
+/// Returns false if the receiver's gone.
+fn measure_hop_mtus(tx: &Sender<FetchedDataMessage>, traceroute: &mut Traceroute, interface_ip: IpAddr, interface_mtu: u16, identifier: u16) -> bool {
+    // Its own socket, so the DF bit doesn't stick to the other probes
+    let socket = match open_icmp_socket(interface_ip) {
+        Ok(socket) => socket,
+        Err(_) => return true
+    };
+
+    if set_dont_fragment(&socket).is_err() {
+        return true;
+    }
+
+    let mut ceiling = interface_mtu;
+    let mut sequence = MTU_PROBE_FIRST_SEQUENCE;
+
+    // Hops that didn't answer are "*"
+    let hop_ips: Vec<(usize, Ipv4Addr)> = traceroute.hops.iter()
+        .enumerate()
+        .filter_map(|(index, hop)| hop.ip.parse().ok().map(|ip| (index, ip)))
+        .collect();
+
+    for (index, hop_ip) in hop_ips {
+
+        let mut probe = |size: u16| {
+            sequence = sequence.wrapping_add(1);
+
+            matches!(send_sized_probe(&socket, hop_ip, identifier, sequence, PROBE_TIMEOUT, size as usize), Some(response) if response.is_reply)
+        };
+
+        // Routers that don't answer pings at all would look like they had a tiny MTU
+        if !probe(SMALL_PROBE_SIZE as u16) {
+            continue;
+        }
+
+        let link_mtu = candidate_mtus(ceiling).into_iter().find(|size| probe(*size));
+
+        if let Some(link_mtu) = link_mtu {
+            ceiling = link_mtu;
+        }
+
+        traceroute.hops[index].link_mtu = link_mtu;
+
+        if !send_or_stop(tx, FetchedDataMessage::Traceroute(traceroute.clone())) {
+            return false;
+        }
+    }
+
+    true
+}
+
+/// The exchange whose peering LAN the address is on, if it's one of the bundled ones
+fn find_ixp(ip: Ipv4Addr) -> Option<String> {
+    static IXP_PREFIXES: OnceLock<Vec<(IpNetwork, String)>> = OnceLock::new();
+
+    IXP_PREFIXES.get_or_init(|| parse_ixp_subnets(IXP_SUBNETS))
+        .iter()
+        .find(|(prefix, _)| prefix.contains(IpAddr::V4(ip)))
+        .map(|(_, name)| name.clone())
+}
+
+/// Reads "prefix name" lines, skipping blank lines and # comments
+fn parse_ixp_subnets(data: &str) -> Vec<(IpNetwork, String)> {
+    data.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let (prefix, name) = line.split_once(' ')?;
+            Some((prefix.parse().ok()?, name.trim().to_string()))
+        })
+        .collect()
+}
+
+/// Packet sizes to try at a hop, largest first, starting from the most that could get there
+fn candidate_mtus(ceiling: u16) -> Vec<u16> {
+    let mut candidates = vec![ceiling];
+    candidates.extend(COMMON_MTUS.iter().copied().filter(|mtu| *mtu < ceiling));
+
+    candidates
+}
+
+/// Has the kernel set DF on everything the socket sends, rather than fragmenting it or refusing
+/// sizes over a path MTU it's already learned
+#[cfg(target_os = "linux")]
+fn set_dont_fragment(socket: &Socket) -> Result<(), ()> {
+    use std::os::fd::AsRawFd;
+
+    let value: libc::c_int = libc::IP_PMTUDISC_PROBE;
+
+    // SAFETY: the descriptor belongs to a live socket, and the value outlives the call
+    let result = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_IP,
+            libc::IP_MTU_DISCOVER,
+            &value as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of::<libc::c_int>() as libc::socklen_t,
+        )
+    };
+
+    match result {
+        0 => Ok(()),
+        _ => Err(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_dont_fragment(_socket: &Socket) -> Result<(), ()> {
+    Err(())
+}
+
+/// The trace goes to 1.1.1.1 unless NETCHECK_TRACEROUTE_TARGET names another IPv4 host
+fn get_traceroute_target() -> Option<Ipv4Addr> {
+    let target = match std::env::var("NETCHECK_TRACEROUTE_TARGET") {
+        Ok(target) => target,
+        Err(_) => return Some(CLOUDFLARE_V4)
+    };
+
+    if let Ok(ip) = target.parse::<Ipv4Addr>() {
+        return Some(ip);
+    }
+
+    let addrs = match (target.as_str(), 0).to_socket_addrs() {
+        Ok(addrs) => addrs,
+        Err(_) => return None
+    };
+
+    for addr in addrs {
+        if let IpAddr::V4(ip) = addr.ip() {
+            return Some(ip);
+        }
+    }
+
+    None
+}
+
+pub fn open_icmp_socket(interface_ip: IpAddr) -> Result<Socket, ()> {
+    let interface_ip = match interface_ip {
+        IpAddr::V4(interface_ip) => interface_ip,
+        IpAddr::V6(_) => return Err(())
+    };
+
+    let socket = match Socket::new(Domain::IPV4, Type::RAW, Some(Protocol::ICMPV4)) {
+        Ok(socket) => socket,
+        Err(_) => return Err(())
+    };
+
+    if socket.bind(&SockAddr::from(SocketAddr::new(IpAddr::V4(interface_ip), 0))).is_err() {
+        return Err(());
+    }
+
+    if socket.set_read_timeout(Some(Duration::from_millis(200))).is_err() {
+        return Err(());
+    }
+
+    Ok(socket)
+}
+
+/// Sends one echo request and waits for whatever comes back about it
+pub fn send_probe(socket: &Socket, target: Ipv4Addr, identifier: u16, sequence: u16, timeout: Duration) -> Option<ProbeResponse> {
+    send_sized_probe(socket, target, identifier, sequence, timeout, SMALL_PROBE_SIZE)
+}
+
+/// Like send_probe, padded out to make an IPv4 packet of `packet_size` bytes
+fn send_sized_probe(socket: &Socket, target: Ipv4Addr, identifier: u16, sequence: u16, timeout: Duration, packet_size: usize) -> Option<ProbeResponse> {
+    if send_echo_request(socket, target, identifier, sequence, packet_size).is_err() {
+        return None;
+    }
+
+    let started = Instant::now();
+    let mut buf = [0u8; 1500];
+
+    while started.elapsed() < timeout {
+        // Raw ICMP sockets see every ICMP packet for the host, so filter down to ones about this probe
+        let len = match (&*socket).read(&mut buf) {
+            Ok(len) => len,
+            Err(_) => continue
+        };
+
+        let latency = started.elapsed().as_secs_f64() * 1000.0;
+
+        // IPv4 raw sockets hand us the IP header too
+        let packet = match Ipv4Packet::new(&buf[..len]) {
+            Some(packet) => packet,
+            None => continue
+        };
+
+        let icmp = match IcmpPacket::new(packet.payload()) {
+            Some(icmp) => icmp,
+            None => continue
+        };
+
+        let (ours, is_reply) = match icmp.get_icmp_type() {
+            IcmpTypes::EchoReply => (is_our_echo(packet.payload(), identifier, sequence), true),
+            // Errors quote our IP header and the start of our ICMP message after their own 8 byte header
+            IcmpTypes::TimeExceeded | IcmpTypes::DestinationUnreachable => {
+                let quoted = match icmp.payload().get(4..).and_then(Ipv4Packet::new) {
+                    Some(quoted) => quoted,
+                    None => continue
+                };
+
+                (is_our_echo(quoted.payload(), identifier, sequence), false)
+            }
+            _ => continue
+        };
+
+        if ours {
+            // Only Time Exceeded quotes the label stack, but nothing else will have an extension to find
+            let mpls_labels = match is_reply {
+                true => Vec::new(),
+                false => parse_mpls_labels(packet.payload()),
+            };
+
+            return Some(ProbeResponse {
+                from: packet.get_source(),
+                latency,
+                is_reply,
+                mpls_labels,
+                ttl: packet.get_ttl(),
+            });
+        }
+    }
+
+    None
+}
+
+/// Sends an echo request making an IPv4 packet of `packet_size` bytes, without waiting for anything back
+pub fn send_echo_request(socket: &Socket, target: Ipv4Addr, identifier: u16, sequence: u16, packet_size: usize) -> Result<(), ()> {
+    // The kernel adds a 20 byte IPv4 header
+    let mut request = match packet_size.checked_sub(20) {
+        Some(len) => vec![0u8; len],
+        None => return Err(())
+    };
+
+    let mut echo_request = match MutableEchoRequestPacket::new(&mut request) {
+        Some(echo_request) => echo_request,
+        None => return Err(())
+    };
+
+    echo_request.set_icmp_type(IcmpTypes::EchoRequest);
+    echo_request.set_identifier(identifier);
+    echo_request.set_sequence_number(sequence);
+
+    // Unlike ICMPv6, the kernel leaves the ICMPv4 checksum to us
+    let checksum = match IcmpPacket::new(echo_request.packet()) {
+        Some(icmp) => pnet::packet::icmp::checksum(&icmp),
+        None => return Err(())
+    };
+    echo_request.set_checksum(checksum);
+
+    let target = SockAddr::from(SocketAddr::new(IpAddr::V4(target), 0));
+
+    match socket.send_to(&request, &target) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(())
+    }
+}
+
+fn is_our_echo(icmp: &[u8], identifier: u16, sequence: u16) -> bool {
+    // Echo requests and replies share a layout, so the request parser reads either
+    match EchoRequestPacket::new(icmp) {
+        Some(echo) => echo.get_identifier() == identifier && echo.get_sequence_number() == sequence,
+        None => false
+    }
+}
+
+/// Reads the MPLS label stack from the extension structure an ICMP error can carry after the
+/// datagram it quotes. RFC 4884 puts the quoted length, in 32-bit words, in the sixth byte. Routers
+/// from before it leave that zero and always pad the quote to 128 bytes, so that's assumed instead.
+fn parse_mpls_labels(icmp: &[u8]) -> Vec<MplsLabel> {
+    let quoted_len = match icmp.get(5) {
+        Some(0) | None => 128,
+        Some(&words) => words as usize * 4,
+    };
+
+    // 8 bytes of ICMP header before the quote, then a 4 byte extension header with the version on top
+    let extension = match icmp.get(8 + quoted_len..) {
+        Some(extension) if extension.len() >= 4 && extension[0] >> 4 == ICMP_EXTENSION_VERSION => extension,
+        _ => return Vec::new()
+    };
+
+    let mut objects = &extension[4..];
+    let mut labels = Vec::new();
+
+    // Each object is a 2 byte length covering its own 4 byte header, a class and a type
+    while objects.len() >= 4 {
+        let len = u16::from_be_bytes([objects[0], objects[1]]) as usize;
+
+        if len < 4 || len > objects.len() {
+            break;
+        }
+
+        if objects[2] == MPLS_LABEL_STACK_CLASS && objects[3] == MPLS_INCOMING_STACK_TYPE {
+            // 20 bits of label, 3 of traffic class, the bottom of stack bit, then the TTL
+            for entry in objects[4..len].chunks_exact(4) {
+                let entry = u32::from_be_bytes([entry[0], entry[1], entry[2], entry[3]]);
+
+                labels.push(MplsLabel {
+                    label: entry >> 12,
+                    tc: ((entry >> 9) & 0x7) as u8,
+                    bos: (entry >> 8) & 1 == 1,
+                    ttl: entry as u8,
+                });
+            }
+        }
+
+        objects = &objects[len..];
+    }
+
+    labels
+}
+
+/// Mean difference between consecutive round trips, as RFC 3550 describes it
+fn calculate_jitter(latencies: &[f64]) -> f64 {
+    if latencies.len() < 2 {
+        return 0.0;
+    }
+
+    let total: f64 = latencies.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum();
+
+    total / (latencies.len() - 1) as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A Time Exceeded message quoting 128 bytes, followed by an extension with one MPLS object
+    fn time_exceeded_with_labels(length_words: u8, entries: &[[u8; 4]]) -> Vec<u8> {
+        let mut icmp = vec![11, 0, 0, 0, 0, length_words, 0, 0];
+        icmp.extend_from_slice(&[0u8; 128]);
+        icmp.extend_from_slice(&[ICMP_EXTENSION_VERSION << 4, 0, 0, 0]);
+
+        let object_len = 4 + entries.len() as u16 * 4;
+        icmp.extend_from_slice(&object_len.to_be_bytes());
+        icmp.extend_from_slice(&[MPLS_LABEL_STACK_CLASS, MPLS_INCOMING_STACK_TYPE]);
+
+        for entry in entries {
+            icmp.extend_from_slice(entry);
+        }
+
+        icmp
+    }
+
+    #[test]
+    fn mpls_label_stack() {
+        // Label 1234 with traffic class 5 over label 16, which is the bottom of the stack
+        let entries = [[0x00, 0x4d, 0x2a, 0xfe], [0x00, 0x01, 0x01, 0x01]];
+
+        let labels = parse_mpls_labels(&time_exceeded_with_labels(32, &entries));
+
+        assert_eq!(labels, vec![
+            MplsLabel { label: 1234, tc: 5, bos: false, ttl: 254 },
+            MplsLabel { label: 16, tc: 0, bos: true, ttl: 1 },
+        ]);
+    }
+
+    #[test]
+    fn mpls_from_routers_without_a_length_field() {
+        let labels = parse_mpls_labels(&time_exceeded_with_labels(0, &[[0x00, 0x4d, 0x2b, 0x01]]));
+
+        assert_eq!(labels, vec![MplsLabel { label: 1234, tc: 5, bos: true, ttl: 1 }]);
+    }
+
+    #[test]
+    fn no_extension() {
+        // Just the header and a short quote, as most routers send
+        let mut icmp = vec![11, 0, 0, 0, 0, 0, 0, 0];
+        icmp.extend_from_slice(&[0u8; 28]);
+
+        assert!(parse_mpls_labels(&icmp).is_empty());
+    }
+
+    #[test]
+    fn mtu_candidates_start_at_the_ceiling() {
+        assert_eq!(candidate_mtus(1500), vec![1500, 1492, 1480, 1450, 1400, 1280, 576]);
+        assert_eq!(candidate_mtus(1420), vec![1420, 1400, 1280, 576]);
+        assert_eq!(candidate_mtus(9000)[..2], [9000, 1500]);
+    }
+
+    #[test]
+    fn truncated_extension_object() {
+        let mut icmp = time_exceeded_with_labels(32, &[[0x00, 0x4d, 0x2b, 0x01]]);
+        icmp.truncate(icmp.len() - 2);
+
+        assert!(parse_mpls_labels(&icmp).is_empty());
+    }
+
+    #[test]
+    fn every_bundled_ixp_subnet_parses() {
+        let lines = IXP_SUBNETS.lines().filter(|line| !line.trim().is_empty() && !line.starts_with('#')).count();
+        assert_eq!(parse_ixp_subnets(IXP_SUBNETS).len(), lines);
+    }
+
+    #[test]
+    fn hops_on_a_peering_lan_name_the_exchange() {
+        assert_eq!(find_ixp(Ipv4Addr::new(195, 66, 225, 10)).as_deref(), Some("LINX"));
+        assert_eq!(find_ixp(Ipv4Addr::new(206, 126, 237, 30)).as_deref(), Some("Equinix Ashburn"));
+        assert_eq!(find_ixp(Ipv4Addr::new(192, 168, 1, 1)), None);
+    }
+}
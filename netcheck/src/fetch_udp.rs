@@ -0,0 +1,166 @@
+use crate::internal_comms::{FetchedDataMessage, UDPInfo, UdpPortState, send_or_stop};
+
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use std::time::Duration;
+
+use rustdns::Message;
+use socket2::{Domain, Protocol, SockAddr, Socket, Type};
+
+use crate::fetch_local::get_interface_ip;
+
+// DNS, NTP and QUIC
+const UDP_PORTS: [u16; 3] = [53, 123, 443];
+const GOOGLE_DNS_V4: Ipv4Addr = Ipv4Addr::new(8, 8, 8, 8);
+const GOOGLE_DNS_V6: Ipv6Addr = Ipv6Addr::new(0x2001, 0x4860, 0x4860, 0, 0, 0, 0, 0x8888);
+// time.cloudflare.com, by address so the check doesn't depend on DNS working. Google's resolvers
+// don't run an NTP server, so asking them would make port 123 look blocked everywhere
+const CLOUDFLARE_NTP_V4: Ipv4Addr = Ipv4Addr::new(162, 159, 200, 123);
+const CLOUDFLARE_NTP_V6: Ipv6Addr = Ipv6Addr::new(0x2606, 0x4700, 0xf1, 0, 0, 0, 0, 0x123);
+const PROBE_DOMAIN: &str = "google.com";
+const PROBE_TIMEOUT: Duration = Duration::from_millis(1500);
+
+// Versions matching 0x?a?a?a?a are reserved for forcing version negotiation (RFC 9000 section 15)
+const QUIC_GREASE_VERSION: [u8; 4] = [0x1a, 0x2a, 0x3a, 0x4a];
+// Clients have to pad Initial packets to at least 1200 bytes or servers ignore them
+const QUIC_MIN_DATAGRAM: usize = 1200;
+const QUIC_CONNECTION_ID: &[u8; 8] = b"netcheck";
+
+pub fn fetch_and_return_udp_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    let interface_ip = match get_interface_ip(&interface) {
+        Ok(interface_ip) => interface_ip,
+        Err(_) => {
+            send_or_stop(&tx, FetchedDataMessage::UDPInfo(UDPInfo {
+                error: Some("No IP address.".to_string()),
+                ..Default::default()
+            }));
+            return;
+        }
+    };
+
+    let (result_tx, result_rx) = mpsc::channel();
+
+    for port in UDP_PORTS {
+        let result_tx = result_tx.clone();
+
+        thread::spawn(move || {
+            let state = probe_port(interface_ip, SocketAddr::new(probe_target(interface_ip, port), port));
+            // The fetcher may have stopped early, leaving nobody to take the result
+            let _ = result_tx.send((port, state));
+        });
+    }
+
+    drop(result_tx);
+
+    let mut udp_info = UDPInfo::default();
+
+    for result in result_rx {
+        udp_info.attempted_to_talk_on_list.push(result);
+
+        // Keep the panel in a stable order however the results arrive
+        udp_info.attempted_to_talk_on_list.sort_by_key(|(port, _)| UDP_PORTS.iter().position(|p| p == port));
+
+        if !send_or_stop(&tx, FetchedDataMessage::UDPInfo(udp_info.clone())) {
+            return;
+        }
+    }
+
+    udp_info.complete = true;
+    send_or_stop(&tx, FetchedDataMessage::UDPInfo(udp_info));
+}
+
+/// A server that actually runs the service on `port`, in the same family as the interface's address
+fn probe_target(interface_ip: IpAddr, port: u16) -> IpAddr {
+    match (interface_ip, port) {
+        (IpAddr::V4(_), 123) => IpAddr::V4(CLOUDFLARE_NTP_V4),
+        (IpAddr::V6(_), 123) => IpAddr::V6(CLOUDFLARE_NTP_V6),
+        (IpAddr::V4(_), _) => IpAddr::V4(GOOGLE_DNS_V4),
+        (IpAddr::V6(_), _) => IpAddr::V6(GOOGLE_DNS_V6),
+    }
+}
+
+/// Sends a request the service on that port should answer. UDP is connectionless, so only a reply
+/// that parses as the right protocol counts as getting through; send() succeeding proves nothing.
+fn probe_port(interface_ip: IpAddr, remote: SocketAddr) -> UdpPortState {
+    let (request, is_valid_reply): (Vec<u8>, fn(&[u8]) -> bool) = match remote.port() {
+        53 => match dns_request() {
+            Some(request) => (request, is_dns_reply),
+            None => return UdpPortState::NoReply
+        },
+        123 => (ntp_request().to_vec(), is_ntp_reply),
+        _ => (quic_request(), is_quic_reply),
+    };
+
+    match exchange(interface_ip, remote, &request) {
+        Ok(reply) if is_valid_reply(&reply) => UdpPortState::Responded,
+        Ok(_) => UdpPortState::NoReply,
+        // An ICMP Port Unreachable shows up as a refused connection on a connected UDP socket
+        Err(error) if error.kind() == io::ErrorKind::ConnectionRefused => UdpPortState::Closed,
+        Err(_) => UdpPortState::NoReply,
+    }
+}
+
+fn dns_request() -> Option<Vec<u8>> {
+    let mut message = Message::default();
+    message.add_question(PROBE_DOMAIN, rustdns::Type::A, rustdns::Class::Internet);
+
+    message.to_vec().ok()
+}
+
+fn is_dns_reply(reply: &[u8]) -> bool {
+    Message::from_slice(reply).is_ok()
+}
+
+/// An NTP v4 client request; everything past the first byte can be left zeroed
+fn ntp_request() -> [u8; 48] {
+    // LI 0, version 4, mode 3 (client)
+    let mut request = [0u8; 48];
+    request[0] = 0b00_100_011;
+    request
+}
+
+fn is_ntp_reply(reply: &[u8]) -> bool {
+    // Mode 4 is a server reply
+    reply.len() >= 48 && reply[0] & 0b111 == 4
+}
+
+/// A padded long header Initial with a reserved version. Any QUIC server has to answer it
+/// with a Version Negotiation packet, which saves doing a real handshake just to see a reply.
+fn quic_request() -> Vec<u8> {
+    let mut request = vec![0xC0];
+    request.extend_from_slice(&QUIC_GREASE_VERSION);
+
+    // Destination and source connection IDs, which the server echoes back swapped
+    for _ in 0..2 {
+        request.push(QUIC_CONNECTION_ID.len() as u8);
+        request.extend_from_slice(QUIC_CONNECTION_ID);
+    }
+
+    request.resize(QUIC_MIN_DATAGRAM, 0);
+    request
+}
+
+fn is_quic_reply(reply: &[u8]) -> bool {
+    // Version Negotiation is a long header packet with a version of zero
+    reply.len() >= 5 && reply[0] & 0x80 != 0 && reply[1..5] == [0, 0, 0, 0]
+}
+
+/// Sends one datagram to `remote` from the interface's address and waits for a single reply
+pub fn exchange(interface_ip: IpAddr, remote: SocketAddr, payload: &[u8]) -> io::Result<Vec<u8>> {
+    let socket = Socket::new(Domain::for_address(remote), Type::DGRAM, Some(Protocol::UDP))?;
+    socket.bind(&SockAddr::from(SocketAddr::new(interface_ip, 0)))?;
+
+    let socket = UdpSocket::from(socket);
+    socket.set_read_timeout(Some(PROBE_TIMEOUT))?;
+
+    // Connecting filters out stray datagrams from anyone else, and lets ICMP errors reach us
+    socket.connect(remote)?;
+    socket.send(payload)?;
+
+    let mut buf = [0u8; 1500];
+    let len = socket.recv(&mut buf)?;
+
+    Ok(buf[..len].to_vec())
+}
@@ -0,0 +1,118 @@
+use crate::internal_comms::{FetchedDataMessage, WifiInfo, send_or_stop};
+
+use std::sync::mpsc::Sender;
+
+#[cfg(target_os = "linux")]
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::process::Command;
+
+pub fn fetch_and_return_wifi_info(tx: Sender<FetchedDataMessage>, interface: String) {
+    send_or_stop(&tx, FetchedDataMessage::WifiInfo(get_wifi_info(&interface)));
+}
+
+/// Only wireless interfaces have a wireless/ directory in sysfs. `iw` gives the most detail,
+/// but isn't always installed, so the signal level falls back to /proc/net/wireless.
+#[cfg(target_os = "linux")]
+fn get_wifi_info(interface: &str) -> WifiInfo {
+    if !Path::new("/sys/class/net").join(interface).join("wireless").exists() {
+        return WifiInfo {
+            is_wireless: false,
+            ..Default::default()
+        };
+    }
+
+    let mut wifi_info = get_iw_link(interface).unwrap_or_default();
+
+    wifi_info.is_wireless = true;
+
+    if wifi_info.signal_dbm.is_none() {
+        wifi_info.signal_dbm = get_proc_signal(interface).ok();
+    }
+
+    wifi_info
+}
+
+#[cfg(not(target_os = "linux"))]
+fn get_wifi_info(_interface: &str) -> WifiInfo {
+    WifiInfo {
+        is_wireless: false,
+        ..Default::default()
+    }
+}
+
+/// Parses `iw dev <iface> link`, which prints "Not connected." or lines such as
+/// "SSID: home", "freq: 5180", "signal: -52 dBm" and "tx bitrate: 866.7 MBit/s VHT-MCS 9"
+#[cfg(target_os = "linux")]
+fn get_iw_link(interface: &str) -> Result<WifiInfo, ()> {
+    let output = Command::new("iw")
+        .arg("dev")
+        .arg(interface)
+        .arg("link")
+        .output();
+
+    let output = match output {
+        Ok(output) => output,
+        Err(_) => return Err(())
+    };
+
+    let output_str = match std::str::from_utf8(&output.stdout) {
+        Ok(output_str) => output_str,
+        Err(_) => return Err(())
+    };
+
+    let mut wifi_info = WifiInfo::default();
+
+    for line in output_str.lines() {
+        let (key, value) = match line.trim().split_once(':') {
+            Some((key, value)) => (key, value.trim()),
+            None => continue
+        };
+
+        let first_word = value.split_whitespace().next().unwrap_or("");
+
+        match key {
+            "SSID" => wifi_info.ssid = Some(value.to_string()),
+            // Newer versions of iw print the frequency with a decimal, e.g. "5180.0"
+            "freq" => wifi_info.frequency_mhz = first_word.parse::<f64>().ok().map(|freq| freq as u32),
+            "signal" => wifi_info.signal_dbm = first_word.parse().ok(),
+            "tx bitrate" => wifi_info.bitrate_mbps = first_word.parse().ok(),
+            _ => {}
+        }
+    }
+
+    Ok(wifi_info)
+}
+
+/// Reads the signal level from the interface's line of /proc/net/wireless, which looks like
+/// "wlan0: 0000   54.  -56.  -256 ..." with the status, link quality, level and noise
+#[cfg(target_os = "linux")]
+fn get_proc_signal(interface: &str) -> Result<i32, ()> {
+    let contents = match std::fs::read_to_string("/proc/net/wireless") {
+        Ok(contents) => contents,
+        Err(_) => return Err(())
+    };
+
+    for line in contents.lines() {
+        let (name, fields) = match line.split_once(':') {
+            Some((name, fields)) => (name.trim(), fields),
+            None => continue
+        };
+
+        if name != interface {
+            continue;
+        }
+
+        let level = match fields.split_whitespace().nth(2) {
+            Some(level) => level.trim_end_matches('.'),
+            None => return Err(())
+        };
+
+        return match level.parse() {
+            Ok(level) => Ok(level),
+            Err(_) => Err(())
+        };
+    }
+
+    Err(())
+}
@@ -0,0 +1,648 @@
+use crate::internal_comms::{DNSServer, FetchedDataMessage, LossPattern, NetworkInfo, UdpPortState};
+use crate::App;
+use crate::fetch_dhcp_info;
+use crate::fetch_https;
+
+use std::mem::{discriminant, Discriminant};
+use std::time::{Duration, Instant};
+
+// Longest we'll wait for the checks before printing whatever has come in
+const OVERALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What gets printed to stdout once the checks are done
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// One line per panel
+    Text,
+    /// Everything that was gathered, pretty-printed
+    Json,
+    /// Everything that was gathered, on one line
+    JsonCompact,
+}
+
+/// How a panel's line in the summary counts towards the exit code
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Outcome {
+    Ok,
+    // Worth mentioning, but not enough to call the network broken
+    Warning,
+    Fail,
+}
+
+/// Waits for the checks started by `initialise_interface_fetching` to finish (or time out),
+/// then prints the results in `output` format. Returns whether everything passed.
+pub fn run(app: &mut App, output: OutputFormat) -> bool {
+    let started = Instant::now();
+
+    // A panel's default value can't be told apart from a real empty result, so note what's arrived
+    let mut received: Vec<Discriminant<FetchedDataMessage>> = Vec::new();
+
+    while !checks_complete(&app.network_info, &received, app.capabilities.raw_socket) {
+        let remaining = match OVERALL_TIMEOUT.checked_sub(started.elapsed()) {
+            Some(remaining) => remaining,
+            None => break
+        };
+
+        let receive_new_data_channel = match app.receive_new_data_channel {
+            Some(ref receive_new_data_channel) => receive_new_data_channel,
+            None => break
+        };
+
+        let message = match receive_new_data_channel.recv_timeout(remaining) {
+            Ok(message) => message,
+            // Either time's up or every fetcher has finished and dropped its sender
+            Err(_) => break
+        };
+
+        if !received.contains(&discriminant(&message)) {
+            received.push(discriminant(&message));
+        }
+
+        app.apply_message(message);
+    }
+
+    let summary = summarise(&app.network_info, app.capabilities.raw_socket);
+
+    match output {
+        OutputFormat::Text => {
+            for (line, outcome) in &summary {
+                match outcome {
+                    Outcome::Ok => println!("{}", line),
+                    Outcome::Warning => println!("{} [WARN]", line),
+                    Outcome::Fail => println!("{} [FAIL]", line),
+                }
+            }
+        }
+        OutputFormat::Json | OutputFormat::JsonCompact => {
+            let json = match output {
+                OutputFormat::JsonCompact => serde_json::to_string(&app.network_info),
+                _ => serde_json::to_string_pretty(&app.network_info),
+            };
+
+            match json {
+                Ok(json) => println!("{}", json),
+                Err(error) => {
+                    eprintln!("Couldn't serialise results: {}", error);
+                    return false;
+                }
+            }
+        }
+    }
+
+    !summary.iter().any(|(_, outcome)| *outcome == Outcome::Fail)
+}
+
+/// Whether every panel in the summary has its final result. Discovery panels (LLDP, mDNS,
+/// devices, NDP) aren't summarised, so there's no point waiting on them.
+fn checks_complete(info: &NetworkInfo, received: &[Discriminant<FetchedDataMessage>], raw_socket: bool) -> bool {
+    let has_received = |message: FetchedDataMessage| received.contains(&discriminant(&message));
+
+    has_received(FetchedDataMessage::LocalInfo(Default::default()))
+        && has_received(FetchedDataMessage::DHCPInfo(Default::default()))
+        && (info.dhcp_info.complete || info.dhcp_info.error.is_some())
+        && (info.dns_info.doq_supported.is_some() || info.dns_info.can_fetch == Some(false))
+        && (info.internet_info.lookup_complete || info.internet_info.reachable == Some(false))
+        && (!raw_socket || info.traceroute.complete || info.traceroute.error.is_some())
+        && (!raw_socket || info.packet_loss_info.complete || info.packet_loss_info.error.is_some())
+        && (info.tcp_info.complete || info.tcp_info.error.is_some())
+        && (info.udp_info.complete || info.udp_info.error.is_some())
+        && (info.http_info.complete || info.http_info.error.is_some())
+        && (info.https_info.complete || info.https_info.error.is_some())
+        && (info.quic_info.complete || info.quic_info.error.is_some())
+        && (info.ntp_info.complete || info.ntp_info.error.is_some())
+}
+
+fn summarise(info: &NetworkInfo, raw_socket: bool) -> Vec<(String, Outcome)> {
+    let mut summary = Vec::new();
+
+    let local = &info.local_info;
+    let local_ips: Vec<String> = local.local_ips.iter().map(|(ip, prefix)| format!("{}/{}", ip, prefix)).collect();
+    summary.push(match (local_ips.is_empty(), &local.gateway) {
+        (true, _) => ("Local: no address".to_string(), Outcome::Fail),
+        (false, Some(gateway)) => (format!("Local: {} via gateway {}", local_ips.join(", "), gateway), Outcome::Ok),
+        (false, None) => (format!("Local: {}, gateway unknown", local_ips.join(", ")), Outcome::Warning),
+    });
+
+    let dhcp = &info.dhcp_info;
+    summary.push(match (&dhcp.error, &dhcp.dhcp_server) {
+        (None, Some(server)) if fetch_dhcp_info::possible_rogue_server(dhcp) => {
+            (format!("DHCP: lease from {}, possible rogue DHCP server", server), Outcome::Warning)
+        }
+        (None, Some(server)) => (format!("DHCP: lease from {}", server), Outcome::Ok),
+        _ => ("DHCP: no lease found".to_string(), Outcome::Warning),
+    });
+
+    let dns = &info.dns_info;
+    // Servers of the other address family can't be asked from this interface, so they don't count either way
+    let testable: Vec<&DNSServer> = dns.dns_servers.iter().filter(|server| !server.wrong_family).collect();
+    let working = testable.iter().filter(|server| server.can_resolve == Some(true)).count();
+    summary.push(match dns.can_fetch {
+        Some(true) if dns.dns_servers.is_empty() => ("DNS: no servers configured".to_string(), Outcome::Fail),
+        Some(true) if testable.is_empty() => ("DNS: no servers of the interface's address family".to_string(), Outcome::Fail),
+        Some(true) => {
+            let outcome = if working == testable.len() { Outcome::Ok } else { Outcome::Fail };
+            (format!("DNS: {}/{} servers OK", working, testable.len()), outcome)
+        }
+        Some(false) => ("DNS: couldn't test servers".to_string(), Outcome::Fail),
+        None => ("DNS: timed out".to_string(), Outcome::Fail),
+    });
+
+    summary.push(match dns.doq_supported {
+        Some(true) => (format!("DoQ: {} OK", dns.doq_server_tested), Outcome::Ok),
+        Some(false) => (format!("DoQ: {} failed", dns.doq_server_tested), Outcome::Warning),
+        None => ("DoQ: timed out".to_string(), Outcome::Warning),
+    });
+
+    let internet = &info.internet_info;
+    summary.push(match (&internet.error, internet.reachable, &internet.public_ip) {
+        (Some(error), _, _) => (format!("Internet: {}", error), Outcome::Fail),
+        (None, Some(true), Some(public_ip)) => {
+            let ping = match (internet.cloudflare_ping, internet.cloudflare_ping_method) {
+                (Some(ping), Some(method)) => format!(", 1.1.1.1 {:.1}ms ({})", ping, method.name()),
+                _ => String::new(),
+            };
+
+            let extra_hops = match (internet.inferred_extra_hops, &internet.extra_hops_compared_with) {
+                (Some(extra_hops), Some(compared_with)) => format!(", {} more hops than via {}", extra_hops, compared_with),
+                _ => String::new(),
+            };
+
+            (format!("Internet: public IP {}{}{}", public_ip, ping, extra_hops), Outcome::Ok)
+        }
+        (None, Some(true), None) => ("Internet: reachable".to_string(), Outcome::Ok),
+        (None, Some(false), _) => ("Internet: unreachable".to_string(), Outcome::Fail),
+        (None, None, _) => ("Internet: timed out".to_string(), Outcome::Fail),
+    });
+
+    // Asked for with --check-port-forward, so a forward that doesn't work is a failure
+    if let Some(ref port_forward) = info.port_forward_info {
+        let port = port_forward.port;
+
+        summary.push(match (&port_forward.error, port_forward.externally_reachable) {
+            (Some(error), _) => (format!("Port forward: {}", error), Outcome::Warning),
+            (None, Some(true)) => (format!("Port forward: {} reachable from outside", port), Outcome::Ok),
+            (None, Some(false)) => (format!("Port forward: {} NOT reachable from outside", port), Outcome::Fail),
+            (None, None) => (format!("Port forward: {} not checked, no public IP", port), Outcome::Warning),
+        });
+    }
+
+    if raw_socket {
+        let traceroute = &info.traceroute;
+        let target = traceroute.target.clone().unwrap_or_default();
+
+        summary.push(match (&traceroute.error, traceroute.complete) {
+            (Some(error), _) => (format!("Traceroute: {}", error), Outcome::Warning),
+            (None, true) => (format!("Traceroute: {} hops to {}", traceroute.hops.len(), target), Outcome::Ok),
+            (None, false) => ("Traceroute: timed out".to_string(), Outcome::Warning),
+        });
+
+        // Some loss is normal enough on Wi-Fi, it takes a fair bit before calls become unusable
+        let packet_loss = &info.packet_loss_info;
+        let lost = packet_loss.sent - packet_loss.received;
+
+        summary.push(match (&packet_loss.error, packet_loss.complete, packet_loss.loss_pattern) {
+            (Some(error), _, _) => (format!("Packet loss: {}", error), Outcome::Warning),
+            (None, true, Some(LossPattern::None)) => (format!("Packet loss: 0/{} lost", packet_loss.sent), Outcome::Ok),
+            (None, true, Some(pattern)) => {
+                let pattern = match pattern {
+                    LossPattern::Bursty(average) => format!("bursty, ~{} in a row", average),
+                    LossPattern::RateLimited => "likely ICMP rate limiting".to_string(),
+                    _ => "random".to_string(),
+                };
+                let outcome = match lost as f64 / packet_loss.sent as f64 {
+                    loss if loss >= 0.05 => Outcome::Fail,
+                    _ => Outcome::Warning,
+                };
+
+                (format!("Packet loss: {}/{} lost ({})", lost, packet_loss.sent, pattern), outcome)
+            }
+            _ => ("Packet loss: timed out".to_string(), Outcome::Warning),
+        });
+    }
+
+    // Some ports are expected to be blocked somewhere, so only nothing getting through is a failure
+    let tcp = &info.tcp_info;
+    let open: Vec<u16> = tcp.attempted_to_talk_on_list.iter().filter(|(_, success)| *success).map(|(port, _)| *port).collect();
+    let blocked: Vec<u16> = tcp.attempted_to_talk_on_list.iter().filter(|(_, success)| !*success).map(|(port, _)| *port).collect();
+    summary.push(port_summary("TCP", &tcp.error, tcp.complete, &open, &blocked));
+
+    let udp = &info.udp_info;
+    let open: Vec<u16> = udp.attempted_to_talk_on_list.iter()
+        .filter(|(_, state)| matches!(state, UdpPortState::Responded))
+        .map(|(port, _)| *port)
+        .collect();
+    let blocked: Vec<u16> = udp.attempted_to_talk_on_list.iter()
+        .filter(|(_, state)| !matches!(state, UdpPortState::Responded))
+        .map(|(port, _)| *port)
+        .collect();
+    summary.push(port_summary("UDP", &udp.error, udp.complete, &open, &blocked));
+
+    let http = &info.http_info;
+    summary.push(match (&http.error, http.complete) {
+        (Some(error), _) => (format!("HTTP: {}", error), Outcome::Fail),
+        (None, false) => ("HTTP: timed out".to_string(), Outcome::Fail),
+        (None, true) if http.captive_portal == Some(true) => {
+            let redirect = http.captive_portal_redirect_url.as_ref()
+                .map(|url| format!(" ({})", url))
+                .unwrap_or_default();
+
+            (format!("HTTP: captive portal detected{}", redirect), Outcome::Fail)
+        }
+        (None, true) => {
+            let ok = http.can_access_1111 == Some(true) && http.can_access_google == Some(true);
+            let outcome = if ok { Outcome::Ok } else { Outcome::Fail };
+
+            (format!("HTTP: 1.1.1.1 {}, Google {}", reachability(http.can_access_1111), reachability(http.can_access_google)), outcome)
+        }
+    });
+
+    let https = &info.https_info;
+    summary.push(match (&https.error, https.complete) {
+        (Some(error), _) => (format!("HTTPS: {}", error), Outcome::Fail),
+        (None, false) => ("HTTPS: timed out".to_string(), Outcome::Fail),
+        (None, true) if https.mitm_detected == Some(true) => ("HTTPS: TLS interception detected".to_string(), Outcome::Fail),
+        (None, true) => {
+            let ok = https.can_access_1111 == Some(true) && https.can_access_google == Some(true);
+            let outcome = if ok { Outcome::Ok } else { Outcome::Fail };
+
+            (format!("HTTPS: 1.1.1.1 {}, Google {}", reachability(https.can_access_1111), reachability(https.can_access_google)), outcome)
+        }
+    });
+
+    // Left out when the host couldn't be reached, which the HTTPS line already covers
+    if let Some(ref host) = https.hsts_host {
+        let days = |max_age: u64| max_age / (24 * 60 * 60);
+
+        summary.push(match https.hsts_max_age {
+            None => (format!("HSTS: {} HSTS not configured", host), Outcome::Warning),
+            Some(max_age) if max_age < fetch_https::HSTS_MIN_MAX_AGE => {
+                (format!("HSTS: {} max-age only {} days", host, days(max_age)), Outcome::Warning)
+            }
+            Some(max_age) if https.hsts_preload_listed == Some(true) => {
+                (format!("HSTS: {} max-age {} days, preloaded so browsers always use HTTPS whatever the certificate", host, days(max_age)), Outcome::Ok)
+            }
+            Some(max_age) => (format!("HSTS: {} max-age {} days", host, days(max_age)), Outcome::Ok),
+        });
+
+        // How a site is configured says nothing about the network, so this is only ever informational
+        if let Some(score) = https.security_score {
+            let missing = match https.missing_headers.is_empty() {
+                true => String::new(),
+                false => format!(", missing {}", https.missing_headers.join(", ")),
+            };
+
+            summary.push((format!("Security headers: {} {}/100{}", host, score, missing), Outcome::Ok));
+        }
+    }
+
+    // Plenty of networks block QUIC and browsers just fall back to TCP
+    let quic = &info.quic_info;
+    summary.push(match (&quic.error, quic.complete, quic.quic_rtt_ms) {
+        (Some(error), _, _) => (format!("QUIC: {}", error), Outcome::Warning),
+        (None, false, _) => ("QUIC: timed out".to_string(), Outcome::Warning),
+        (None, true, Some(rtt)) if quic.http3_supported == Some(false) => (format!("QUIC: handshake OK, {:.1}ms, but HTTP/3 failed", rtt), Outcome::Warning),
+        (None, true, Some(rtt)) => (format!("QUIC: OK, {:.1}ms", rtt), Outcome::Ok),
+        (None, true, None) if quic.quic_port_blocked == Some(true) => ("QUIC: UDP/443 BLOCKED by firewall (TCP/443 works)".to_string(), Outcome::Warning),
+        (None, true, None) => ("QUIC: blocked".to_string(), Outcome::Warning),
+    });
+
+    let ntp = &info.ntp_info;
+    let server = ntp.ntp_server.clone().unwrap_or_default();
+    summary.push(match (&ntp.error, ntp.can_access_ntp, ntp.clock_offset_ms) {
+        (Some(error), _, _) => (format!("NTP: {}", error), Outcome::Fail),
+        // A clock this far out is enough to break certificate validation
+        (None, Some(true), Some(offset)) if offset.abs() > 1000.0 => (format!("NTP: {} clock off by {:+.1}ms", server, offset), Outcome::Fail),
+        (None, Some(true), Some(offset)) => (format!("NTP: {} clock off by {:+.1}ms", server, offset), Outcome::Ok),
+        (None, Some(false), _) => (format!("NTP: {} unreachable", server), Outcome::Fail),
+        _ => ("NTP: timed out".to_string(), Outcome::Fail),
+    });
+
+    summary
+}
+
+fn port_summary(name: &str, error: &Option<String>, complete: bool, open: &[u16], blocked: &[u16]) -> (String, Outcome) {
+    if let Some(ref error) = error {
+        return (format!("{}: {}", name, error), Outcome::Fail);
+    }
+
+    let total = open.len() + blocked.len();
+
+    let mut line = format!("{}: {}/{} ports reachable", name, open.len(), total);
+
+    if !blocked.is_empty() {
+        let blocked: Vec<String> = blocked.iter().map(|port| port.to_string()).collect();
+        line.push_str(&format!(" (no answer on {})", blocked.join(", ")));
+    }
+
+    match (complete, open.is_empty(), blocked.is_empty()) {
+        (false, _, _) => (format!("{}: timed out", name), Outcome::Fail),
+        (true, true, _) => (line, Outcome::Fail),
+        (true, false, true) => (line, Outcome::Ok),
+        (true, false, false) => (line, Outcome::Warning),
+    }
+}
+
+fn reachability(reachable: Option<bool>) -> &'static str {
+    match reachable {
+        Some(true) => "OK",
+        Some(false) => "error",
+        None => "no connection",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::internal_comms::{DNSInfo, DHCPInfo, HTTPInfo, HTTPSInfo, InternetInfo, LocalInfo, NTPInfo, PacketLossInfo, PortForwardInfo, QUICInfo, TCPInfo, Traceroute, UDPInfo};
+
+    /// What a healthy network looks like once every check has finished
+    fn complete_info() -> NetworkInfo {
+        NetworkInfo {
+            local_info: LocalInfo {
+                local_ips: vec![("192.168.1.23".parse().unwrap(), 24)],
+                gateway: Some("192.168.1.1".to_string()),
+                ..Default::default()
+            },
+            dhcp_info: DHCPInfo {
+                complete: true,
+                dhcp_server: Some("192.168.1.1".to_string()),
+                server_on_local_subnet: Some(true),
+                router_resolved: Some(true),
+                ..Default::default()
+            },
+            dns_info: DNSInfo {
+                can_fetch: Some(true),
+                dns_servers: vec![
+                    DNSServer { ip: "192.168.1.1".to_string(), can_resolve: Some(true), ..Default::default() },
+                    // Can't be asked from an IPv4 address, so it shouldn't count against the network
+                    DNSServer { ip: "2001:4860:4860::8888".to_string(), wrong_family: true, ..Default::default() },
+                ],
+                doq_supported: Some(true),
+                doq_server_tested: "dns.adguard-dns.com".to_string(),
+                ..Default::default()
+            },
+            internet_info: InternetInfo {
+                reachable: Some(true),
+                lookup_complete: true,
+                public_ip: Some("203.0.113.7".to_string()),
+                ..Default::default()
+            },
+            traceroute: Traceroute {
+                target: Some("8.8.8.8".to_string()),
+                complete: true,
+                ..Default::default()
+            },
+            packet_loss_info: PacketLossInfo {
+                target: Some("1.1.1.1".to_string()),
+                complete: true,
+                sent: 100,
+                received: 100,
+                loss_pattern: Some(LossPattern::None),
+                ..Default::default()
+            },
+            tcp_info: TCPInfo {
+                complete: true,
+                attempted_to_talk_on_list: vec![(80, true), (443, true)],
+                ..Default::default()
+            },
+            udp_info: UDPInfo {
+                complete: true,
+                attempted_to_talk_on_list: vec![(53, UdpPortState::Responded), (123, UdpPortState::Responded)],
+                ..Default::default()
+            },
+            http_info: HTTPInfo {
+                complete: true,
+                can_access_1111: Some(true),
+                can_access_google: Some(true),
+                captive_portal: Some(false),
+                ..Default::default()
+            },
+            https_info: HTTPSInfo {
+                complete: true,
+                can_access_1111: Some(true),
+                can_access_google: Some(true),
+                mitm_detected: Some(false),
+                ..Default::default()
+            },
+            quic_info: QUICInfo {
+                complete: true,
+                quic_rtt_ms: Some(12.5),
+                ..Default::default()
+            },
+            ntp_info: NTPInfo {
+                complete: true,
+                ntp_server: Some("pool.ntp.org".to_string()),
+                can_access_ntp: Some(true),
+                clock_offset_ms: Some(3.2),
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    fn received_all() -> Vec<Discriminant<FetchedDataMessage>> {
+        vec![
+            discriminant(&FetchedDataMessage::LocalInfo(Default::default())),
+            discriminant(&FetchedDataMessage::DHCPInfo(Default::default())),
+        ]
+    }
+
+    fn outcome(summary: &[(String, Outcome)], prefix: &str) -> Outcome {
+        summary.iter().find(|(line, _)| line.starts_with(prefix)).map(|(_, outcome)| *outcome).unwrap()
+    }
+
+    #[test]
+    fn complete_results_are_complete() {
+        assert!(checks_complete(&complete_info(), &received_all(), true));
+    }
+
+    #[test]
+    fn waits_for_local_and_dhcp_messages() {
+        // Their default values look like a real empty result, so only the message arriving counts
+        let received = vec![discriminant(&FetchedDataMessage::LocalInfo(Default::default()))];
+        assert!(!checks_complete(&complete_info(), &received, true));
+    }
+
+    #[test]
+    fn waits_for_unfinished_checks() {
+        let mut info = complete_info();
+        info.internet_info.lookup_complete = false;
+        assert!(!checks_complete(&info, &received_all(), true));
+
+        let mut info = complete_info();
+        info.dns_info.doq_supported = None;
+        assert!(!checks_complete(&info, &received_all(), true));
+
+        let mut info = complete_info();
+        info.ntp_info.complete = false;
+        assert!(!checks_complete(&info, &received_all(), true));
+    }
+
+    #[test]
+    fn failures_finish_checks_too() {
+        let mut info = complete_info();
+        info.internet_info = InternetInfo { reachable: Some(false), ..Default::default() };
+        info.quic_info = QUICInfo { error: Some("No IP address.".to_string()), ..Default::default() };
+        info.dns_info = DNSInfo { can_fetch: Some(false), ..Default::default() };
+
+        assert!(checks_complete(&info, &received_all(), true));
+    }
+
+    #[test]
+    fn traceroute_only_waited_on_with_raw_sockets() {
+        let mut info = complete_info();
+        info.traceroute = Traceroute::default();
+
+        assert!(!checks_complete(&info, &received_all(), true));
+        assert!(checks_complete(&info, &received_all(), false));
+    }
+
+    #[test]
+    fn packet_loss_only_waited_on_with_raw_sockets() {
+        let mut info = complete_info();
+        info.packet_loss_info = PacketLossInfo::default();
+
+        assert!(!checks_complete(&info, &received_all(), true));
+        assert!(checks_complete(&info, &received_all(), false));
+    }
+
+    #[test]
+    fn packet_loss_fails_past_five_percent() {
+        let mut info = complete_info();
+        info.packet_loss_info.received = 97;
+        info.packet_loss_info.loss_pattern = Some(LossPattern::Random);
+
+        let summary = summarise(&info, true);
+        assert!(summary.iter().any(|(line, outcome)| line == "Packet loss: 3/100 lost (random)" && *outcome == Outcome::Warning));
+
+        info.packet_loss_info.received = 88;
+        info.packet_loss_info.loss_pattern = Some(LossPattern::Bursty(4));
+
+        let summary = summarise(&info, true);
+        assert!(summary.iter().any(|(line, outcome)| line == "Packet loss: 12/100 lost (bursty, ~4 in a row)" && *outcome == Outcome::Fail));
+    }
+
+    #[test]
+    fn unreachable_port_forward_fails() {
+        let mut info = complete_info();
+        assert!(!summarise(&info, true).iter().any(|(line, _)| line.starts_with("Port forward")));
+
+        info.port_forward_info = Some(PortForwardInfo { port: 25565, complete: true, externally_reachable: Some(true), ..Default::default() });
+        let summary = summarise(&info, true);
+        assert!(summary.iter().any(|(line, outcome)| line == "Port forward: 25565 reachable from outside" && *outcome == Outcome::Ok));
+
+        info.port_forward_info = Some(PortForwardInfo { port: 25565, complete: true, externally_reachable: Some(false), ..Default::default() });
+        assert_eq!(outcome(&summarise(&info, true), "Port forward"), Outcome::Fail);
+    }
+
+    #[test]
+    fn missing_or_short_hsts_is_a_warning() {
+        let mut info = complete_info();
+        info.https_info.hsts_host = Some("www.cloudflare.com".to_string());
+
+        let summary = summarise(&info, true);
+        assert!(summary.iter().any(|(line, outcome)| line == "HSTS: www.cloudflare.com HSTS not configured" && *outcome == Outcome::Warning));
+
+        info.https_info.hsts_max_age = Some(86400);
+        assert_eq!(outcome(&summarise(&info, true), "HSTS"), Outcome::Warning);
+
+        info.https_info.hsts_max_age = Some(31536000);
+        assert_eq!(outcome(&summarise(&info, true), "HSTS"), Outcome::Ok);
+    }
+
+    #[test]
+    fn security_headers_never_fail_the_run() {
+        let mut info = complete_info();
+        info.https_info.hsts_host = Some("www.cloudflare.com".to_string());
+        info.https_info.security_score = Some(60);
+        info.https_info.missing_headers = vec!["content-security-policy".to_string(), "x-frame-options".to_string()];
+
+        let summary = summarise(&info, true);
+        assert!(summary.iter().any(|(line, outcome)| {
+            line == "Security headers: www.cloudflare.com 60/100, missing content-security-policy, x-frame-options" && *outcome == Outcome::Ok
+        }));
+    }
+
+    #[test]
+    fn healthy_network_passes() {
+        let summary = summarise(&complete_info(), true);
+
+        assert!(summary.iter().all(|(_, outcome)| *outcome == Outcome::Ok), "{:?}", summary);
+        assert!(summary.iter().any(|(line, _)| line == "DNS: 1/1 servers OK"));
+        assert!(summary.iter().any(|(line, _)| line == "Traceroute: 0 hops to 8.8.8.8"));
+    }
+
+    #[test]
+    fn traceroute_left_out_without_raw_sockets() {
+        let summary = summarise(&complete_info(), false);
+        assert!(!summary.iter().any(|(line, _)| line.starts_with("Traceroute")));
+    }
+
+    #[test]
+    fn partial_results_are_reported_as_timed_out() {
+        let info = NetworkInfo::default();
+        let summary = summarise(&info, true);
+
+        assert_eq!(outcome(&summary, "Local"), Outcome::Fail);
+        assert_eq!(outcome(&summary, "DNS:"), Outcome::Fail);
+        assert!(summary.iter().any(|(line, _)| line == "Internet: timed out"));
+        assert!(summary.iter().any(|(line, _)| line == "TCP: timed out"));
+        // Networks that block these are common enough not to call it broken
+        assert_eq!(outcome(&summary, "DoQ"), Outcome::Warning);
+        assert_eq!(outcome(&summary, "QUIC"), Outcome::Warning);
+    }
+
+    #[test]
+    fn some_blocked_ports_are_a_warning() {
+        let mut info = complete_info();
+        info.udp_info.attempted_to_talk_on_list.push((443, UdpPortState::NoReply));
+
+        let summary = summarise(&info, true);
+        assert_eq!(outcome(&summary, "UDP"), Outcome::Warning);
+        assert!(summary.iter().any(|(line, _)| line == "UDP: 2/3 ports reachable (no answer on 443)"));
+    }
+
+    #[test]
+    fn far_off_clock_fails() {
+        let mut info = complete_info();
+        info.ntp_info.clock_offset_ms = Some(-2500.0);
+
+        assert_eq!(outcome(&summarise(&info, true), "NTP"), Outcome::Fail);
+    }
+
+    #[test]
+    fn failing_dns_server_fails() {
+        let mut info = complete_info();
+        info.dns_info.dns_servers.push(DNSServer { ip: "10.0.0.53".to_string(), can_resolve: Some(false), ..Default::default() });
+
+        let summary = summarise(&info, true);
+        assert!(summary.iter().any(|(line, outcome)| line == "DNS: 1/2 servers OK" && *outcome == Outcome::Fail));
+    }
+
+    #[test]
+    fn extra_dhcp_server_is_a_warning() {
+        let mut info = complete_info();
+        info.dhcp_info.inform_servers = Some(vec!["192.168.1.1".to_string(), "192.168.1.66".to_string()]);
+
+        let summary = summarise(&info, true);
+        assert!(summary.iter().any(|(line, outcome)| line == "DHCP: lease from 192.168.1.1, possible rogue DHCP server" && *outcome == Outcome::Warning));
+    }
+
+    #[test]
+    fn waits_for_the_rogue_dhcp_checks() {
+        let mut info = complete_info();
+        info.dhcp_info.complete = false;
+
+        assert!(!checks_complete(&info, &received_all(), true));
+    }
+
+    #[test]
+    fn quic_without_working_http3_is_a_warning() {
+        let mut info = complete_info();
+        info.quic_info.http3_supported = Some(false);
+
+        let summary = summarise(&info, true);
+        assert!(summary.iter().any(|(line, outcome)| line == "QUIC: handshake OK, 12.5ms, but HTTP/3 failed" && *outcome == Outcome::Warning));
+    }
+}
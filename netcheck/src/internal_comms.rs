@@ -1,3 +1,8 @@
+use std::net::IpAddr;
+use std::sync::mpsc::Sender;
+
+use serde::{Deserialize, Serialize};
+
 pub enum FetchedDataMessage {
     LocalInfo(LocalInfo),
     InternetInfo(InternetInfo),
@@ -10,9 +15,56 @@ pub enum FetchedDataMessage {
     UDPInfo(UDPInfo),
     NTPInfo(NTPInfo),
     QUICInfo(QUICInfo),
+    NDPEntries(Vec<NdpEntry>),
+    ArpEntries(Vec<NdpEntry>),
+    LldpInfo(LldpInfo),
+    MdnsInfo(MdnsInfo),
+    NetworkDevices(NetworkDevices),
+    NetworkStats(NetworkStatsInfo),
+    WifiInfo(WifiInfo),
+    IPv6Info(IPv6Info),
+    PacketLossInfo(PacketLossInfo),
+    BgpPathInfo(BgpPathInfo),
+    PortForwardInfo(PortForwardInfo),
+}
+
+impl FetchedDataMessage {
+    /// The NetworkInfo field the message replaces, which is also the panel that shows it
+    pub fn panel(&self) -> &'static str {
+        match self {
+            FetchedDataMessage::LocalInfo(_) => "local_info",
+            FetchedDataMessage::InternetInfo(_) => "internet_info",
+            FetchedDataMessage::DHCPInfo(_) => "dhcp_info",
+            FetchedDataMessage::DNSInfo(_) => "dns_info",
+            FetchedDataMessage::Traceroute(_) => "traceroute",
+            FetchedDataMessage::TCPInfo(_) => "tcp_info",
+            FetchedDataMessage::HTTPInfo(_) => "http_info",
+            FetchedDataMessage::HTTPSInfo(_) => "https_info",
+            FetchedDataMessage::UDPInfo(_) => "udp_info",
+            FetchedDataMessage::NTPInfo(_) => "ntp_info",
+            FetchedDataMessage::QUICInfo(_) => "quic_info",
+            // Both caches share the neighbours panel
+            FetchedDataMessage::NDPEntries(_) | FetchedDataMessage::ArpEntries(_) => "ndp_entries",
+            FetchedDataMessage::LldpInfo(_) => "lldp_info",
+            FetchedDataMessage::MdnsInfo(_) => "mdns_info",
+            FetchedDataMessage::NetworkDevices(_) => "network_devices",
+            FetchedDataMessage::NetworkStats(_) => "network_stats",
+            FetchedDataMessage::WifiInfo(_) => "wifi_info",
+            FetchedDataMessage::IPv6Info(_) => "ipv6_info",
+            FetchedDataMessage::PacketLossInfo(_) => "packet_loss_info",
+            FetchedDataMessage::BgpPathInfo(_) => "bgp_path_info",
+            FetchedDataMessage::PortForwardInfo(_) => "port_forward_info",
+        }
+    }
 }
 
-#[derive(Debug, Default)]
+/// Passes an update on to the UI. Returns false once nobody is listening (e.g. the user quit
+/// mid-check), which is the fetcher's cue to stop rather than panic over the restored terminal.
+pub fn send_or_stop(tx: &Sender<FetchedDataMessage>, message: FetchedDataMessage) -> bool {
+    tx.send(message).is_ok()
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct NetworkInfo {
     pub local_info: LocalInfo,
     pub internet_info: InternetInfo,
@@ -25,95 +77,431 @@ pub struct NetworkInfo {
     pub udp_info: UDPInfo,
     pub ntp_info: NTPInfo,
     pub quic_info: QUICInfo,
+    pub ndp_entries: Vec<NdpEntry>,
+    pub arp_entries: Vec<NdpEntry>,
+    pub lldp_info: LldpInfo,
+    pub mdns_info: MdnsInfo,
+    pub network_devices: NetworkDevices,
+    pub network_stats: NetworkStatsInfo,
+    pub wifi_info: WifiInfo,
+    pub ipv6_info: IPv6Info,
+    pub packet_loss_info: PacketLossInfo,
+    pub bgp_path_info: BgpPathInfo,
+    // Only checked when --check-port-forward asks for it
+    pub port_forward_info: Option<PortForwardInfo>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct LocalInfo {
-    pub local_ip: Option<String>,
-    pub subnet_mask: Option<String>,
+    pub mac_address: Option<String>,
+    pub is_up: bool,
+    pub is_loopback: bool,
+    pub mtu: Option<u32>,
+    // Every address on the interface with its prefix length, in the order the OS lists them
+    pub local_ips: Vec<(IpAddr, u8)>,
+    // The first of each kind of address in local_ips
+    pub ipv4_addr: Option<IpAddr>,
+    pub ipv6_global: Option<IpAddr>,
+    pub ipv6_link_local: Option<IpAddr>,
     pub gateway: Option<String>,
+    // None until the gateway's been pinged, which needs a raw socket and an IPv4 gateway
+    pub gateway_reachable: Option<bool>,
+    pub gateway_latency_ms: Option<f64>,
+    pub ipv6_pmtud_working: Option<bool>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct InternetInfo {
+    pub error: Option<String>,
+    // None until the public IP lookup has either worked or failed
+    pub reachable: Option<bool>,
+    // Set once reverse DNS and the ASN/ISP lookup have finished
+    pub lookup_complete: bool,
     pub public_ip: Option<String>,
     pub asn: Option<u32>,
     pub reverse_dns: Option<String>,
     pub isp: Option<String>,
     pub location: Option<String>,
+    // Median round trip in milliseconds, and how it was measured
     pub cloudflare_ping: Option<f64>,
+    pub cloudflare_ping_method: Option<PingMethod>,
+    // How many more hops replies from 1.1.1.1 take to reach a tunnel interface than to the
+    // interface it runs over, which a double-hop VPN adds to
+    pub inferred_extra_hops: Option<u8>,
+    pub extra_hops_compared_with: Option<String>,
+}
+
+/// How the rest of the internet routes to the public IP, as RIPE's route collectors see it
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct BgpPathInfo {
+    pub error: Option<String>,
+    pub complete: bool,
+    // The most specific announced prefix covering the public IP
+    pub prefix: Option<String>,
+    pub origin_asn: Option<u32>,
+    // The shortest path a collector had for that prefix, ending with the origin
+    pub as_path: Vec<u32>,
+}
+
+/// Whether a forwarded port on the public IP can be reached from outside the NAT
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PortForwardInfo {
+    pub error: Option<String>,
+    pub complete: bool,
+    pub port: u16,
+    pub externally_reachable: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum PingMethod {
+    Icmp,
+    Tcp,
+}
+
+impl PingMethod {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PingMethod::Icmp => "ICMP",
+            PingMethod::Tcp => "TCP",
+        }
+    }
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DHCPInfo {
+    pub error: Option<String>,
+    // Set once the rogue server checks have finished, the lease itself arrives before them
+    pub complete: bool,
     pub dhcp_server: Option<String>,
     pub lease_time: Option<u64>,
     pub last_renewed: Option<u64>,
     pub dhcp_declared_dns: Option<Vec<String>>,
+    // The default gateway the lease handed out
+    pub dhcp_router: Option<String>,
+    // None if the lease doesn't name its server or the interface has no IPv4 address
+    pub server_on_local_subnet: Option<bool>,
+    // Whether the router's MAC address could be resolved over ARP, None if the cache couldn't be read
+    pub router_resolved: Option<bool>,
+    // Every server that answered a DHCPINFORM, None if one couldn't be sent (it needs root)
+    pub inform_servers: Option<Vec<String>>,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DNSInfo {
     pub can_fetch: Option<bool>,
     pub can_bind_interface: Option<bool>,
     pub dns_servers: Vec<DNSServer>,
+    pub doq_supported: Option<bool>,
+    pub doq_server_tested: String,
+    pub local_hosts_override: Option<String>,
 }
 
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct DNSServer {
     pub ip: String,
     pub can_resolve: Option<bool>,
+    // Only set once the server has answered successfully
+    pub latency_ms: Option<f64>,
+    // Which record types for the test domain came back with answers
+    pub resolves_a: Option<bool>,
+    pub resolves_aaaa: Option<bool>,
+    pub resolves_mx: Option<bool>,
+    // Set when an answer came back truncated and had to be fetched again over TCP
+    pub used_tcp_fallback: Option<bool>,
+    // Set when the server's address family differs from the interface's, so it was never asked
+    pub wrong_family: bool,
+    pub sources: Vec<DnsSource>,
+}
+
+/// Where a DNS server's configuration was found
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DnsSource {
+    ResolvConf,
+    NetworkManager,
+    SystemdResolved,
+    EnvVar,
+}
+
+impl DnsSource {
+    /// Short tag used when listing sources in the DNS panel
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            DnsSource::ResolvConf => "rc",
+            DnsSource::NetworkManager => "nm",
+            DnsSource::SystemdResolved => "sd",
+            DnsSource::EnvVar => "env",
+        }
+    }
+}
+
+/// One server's answer to a query made with the interactive DNS tool
+#[derive(Debug, Clone)]
+pub struct DNSQueryResult {
+    pub server: String,
+    /// None if the server never responded
+    pub rcode: Option<String>,
+    pub answers: Vec<String>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct Traceroute {
+    pub error: Option<String>,
+    pub target: Option<String>,
+    pub complete: bool,
     pub hops: Vec<TracerouteHop>,
 }
 
-#[derive(Debug)]
+// Unresponsive hops have an IP of "*" and negative latency and jitter
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TracerouteHop {
     pub hop_number: u8,
     pub ip: String,
     pub latency: f64,
     pub jitter: f64,
     pub location: Option<String>,
+    // The label stack the packet carried when its TTL ran out, for routers that quote it (RFC 4950)
+    pub mpls_labels: Vec<MplsLabel>,
+    // The largest packet that reached this hop with fragmentation forbidden, from a second pass
+    pub link_mtu: Option<u16>,
+    // The Internet Exchange Point whose peering LAN the address is on
+    pub ixp: Option<String>,
+}
+
+/// One entry of an MPLS label stack
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct MplsLabel {
+    pub label: u32,
+    // Traffic class, formerly the experimental bits
+    pub tc: u8,
+    // Set on the last entry in the stack
+    pub bos: bool,
+    pub ttl: u8,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PacketLossInfo {
+    pub error: Option<String>,
+    pub target: Option<String>,
+    pub complete: bool,
+    pub sent: u16,
+    pub received: u16,
+    // The most replies missed in a row
+    pub longest_burst: u16,
+    pub loss_pattern: Option<LossPattern>,
+}
+
+/// How the lost packets in a burst test were spread out
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LossPattern {
+    // Scattered losses, as interference causes
+    Random,
+    // Losses in runs of about this many, as a full buffer causes
+    Bursty(u8),
+    // Single losses at regular intervals, as an ICMP rate limit causes
+    RateLimited,
+    None,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct TCPInfo {
+    pub error: Option<String>,
+    pub complete: bool,
     pub attempted_to_talk_on_list: Vec<(u16, bool)>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct HTTPInfo {
+    pub error: Option<String>,
+    pub complete: bool,
+    // None if the connection failed, Some(false) if the server answered with an error status
     pub can_access_1111: Option<bool>,
     pub can_access_google: Option<bool>,
+    // None if the portal check couldn't connect at all
     pub captive_portal: Option<bool>,
+    // Where the portal's redirects ended up, and the login page's <title> if it had one
+    pub captive_portal_redirect_url: Option<String>,
+    pub captive_portal_title: Option<String>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct HTTPSInfo {
+    pub error: Option<String>,
+    pub complete: bool,
+    // None if the handshake failed, Some(false) if it completed but the certificate didn't validate
     pub can_access_1111: Option<bool>,
     pub can_access_google: Option<bool>,
     pub mitm_detected: Option<bool>,
+    pub tls_version: Option<String>,
+    // Details of the leaf certificate 1.1.1.1 presented
+    pub cert_fingerprint_seen: Option<String>,
+    pub cert_subject: Option<String>,
+    pub cert_issuer: Option<String>,
+    pub cert_expires: Option<String>,
+    // Set once fetch_https::HSTS_HOST has answered, the policy fields stay None if it sent no valid header
+    pub hsts_host: Option<String>,
+    pub hsts_max_age: Option<u64>,
+    pub hsts_include_subdomains: Option<bool>,
+    pub hsts_preload: Option<bool>,
+    // Whether its domain is on the preload list browsers ship with
+    pub hsts_preload_listed: Option<bool>,
+    // Out of 100 for the other security headers HSTS_HOST sends, with the ones missing or misconfigured
+    pub security_score: Option<u8>,
+    pub missing_headers: Vec<String>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct UDPInfo {
-    pub attempted_to_talk_on_list: Vec<(u16, bool)>,
+    pub error: Option<String>,
+    pub complete: bool,
+    pub attempted_to_talk_on_list: Vec<(u16, UdpPortState)>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum UdpPortState {
+    Responded,
+    // ICMP Port Unreachable came back
+    Closed,
+    // Silence could be a firewall dropping the probe or just a lost packet
+    NoReply,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct NTPInfo {
+    pub error: Option<String>,
+    pub complete: bool,
+    // Whether the system's NTP config names a server
     pub do_use_ntp: Option<bool>,
     pub ntp_server: Option<String>,
     pub can_access_ntp: Option<bool>,
+    // Seconds since the Unix epoch when the reply arrived, by our clock and the server's
     pub local_time: Option<u64>,
     pub server_time: Option<u64>,
+    // Positive when our clock is behind the server's
+    pub clock_offset_ms: Option<f64>,
+    pub round_trip_delay_ms: Option<f64>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct QUICInfo {
+    pub error: Option<String>,
+    pub complete: bool,
+    // None if there was no way to try, Some(false) if the handshake failed or timed out
     pub can_access_1111: Option<bool>,
     pub can_access_google: Option<bool>,
-}
\ No newline at end of file
+    // Whether a GET over HTTP/3 to 1.1.1.1 came back 200 once the handshake worked, None if it didn't
+    pub http3_supported: Option<bool>,
+    pub quic_rtt_ms: Option<f64>,
+    // Worked out from the TCP check once both have finished: Some(true) when 1.1.1.1 answers on
+    // TCP 443 but not over QUIC, i.e. a firewall drops UDP 443 specifically
+    pub quic_port_blocked: Option<bool>,
+}
+
+/// The interface's traffic counters since it came up, and the rate over the last second
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NetworkStatsInfo {
+    pub error: Option<String>,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    // Bytes per second, None until there are two samples to compare
+    pub rx_bps: Option<f64>,
+    pub tx_bps: Option<f64>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct IPv6Info {
+    pub error: Option<String>,
+    pub complete: bool,
+    pub has_global_ipv6: Option<bool>,
+    // None if there was no raw socket to ping with
+    pub can_reach_ipv6_internet: Option<bool>,
+    // The fastest reply from any of the internet targets
+    pub ping_ms: Option<f64>,
+    pub ipv6_gateway: Option<String>,
+    pub gateway_reachable: Option<bool>,
+    pub gateway_ping_ms: Option<f64>,
+    // Each internet target's address and fastest reply in milliseconds, None if it never answered
+    pub ping_results: Vec<(String, Option<f64>)>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct WifiInfo {
+    // The panel is hidden entirely for wired interfaces
+    pub is_wireless: bool,
+    // None while disconnected, or if the details couldn't be read
+    pub ssid: Option<String>,
+    pub signal_dbm: Option<i32>,
+    pub frequency_mhz: Option<u32>,
+    pub bitrate_mbps: Option<f64>,
+}
+
+/// An entry in the neighbour cache, found by NDP for IPv6 neighbours or ARP for IPv4 ones
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NdpEntry {
+    pub ip: String,
+    /// Empty when the neighbour's link-layer address was never resolved
+    pub mac: String,
+    pub state: String,
+}
+
+/// What the switch on the other end of the link advertises over LLDP
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct LldpInfo {
+    pub neighbor_found: bool,
+    pub listen_complete: bool,
+    pub error: Option<String>,
+    pub chassis_id: Option<String>,
+    pub neighbor_name: Option<String>,
+    pub port_id: Option<String>,
+    pub port_description: Option<String>,
+    pub vlan_id: Option<u16>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct MdnsInfo {
+    pub error: Option<String>,
+    pub hostname: Option<String>,
+    pub self_resolution_ok: Option<bool>,
+    pub discovery_complete: bool,
+    pub devices_discovered: u8,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct NetworkDevices {
+    pub error: Option<String>,
+    pub discovery_complete: bool,
+    pub devices: Vec<NetworkDevice>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkDevice {
+    pub device_type: DeviceType,
+    pub ip: String,
+    pub service_name: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DeviceType {
+    Printer,
+    AppleTV,
+    Chromecast,
+    Nas,
+    Router,
+    Other,
+}
+
+impl DeviceType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DeviceType::Printer => "Printer",
+            DeviceType::AppleTV => "Apple TV",
+            DeviceType::Chromecast => "Chromecast",
+            DeviceType::Nas => "NAS",
+            DeviceType::Router => "Router",
+            DeviceType::Other => "Other",
+        }
+    }
+}
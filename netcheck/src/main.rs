@@ -1,3 +1,4 @@
+use clap::Parser;
 use color_eyre::{
     eyre::{bail, WrapErr},
     Result,
@@ -11,28 +12,165 @@ use ratatui::{
         *,
     },
 };
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
 use std::sync::mpsc;
 use std::sync::mpsc::{Receiver, Sender};
 
 use std::thread;
+use std::time::{Duration, Instant};
 
 mod errors;
 mod tui;
+mod recording;
 mod netlib;
+mod headless;
+mod report;
+mod watch;
 mod internal_comms;
-use internal_comms::FetchedDataMessage;
+use internal_comms::{DNSQueryResult, FetchedDataMessage, LossPattern, UdpPortState};
 
 mod fetch_local;
 mod fetch_dns;
+mod fetch_ndp;
+mod fetch_lldp;
+mod fetch_mdns;
+mod fetch_devices;
+mod fetch_internet;
+mod fetch_dhcp_info;
+mod fetch_traceroute;
+mod fetch_tcp;
+mod fetch_http;
+mod fetch_udp;
+mod fetch_https;
+mod fetch_ntp;
+mod fetch_quic;
+mod fetch_network_stats;
+mod fetch_wifi_info;
+mod fetch_ipv6_info;
+mod fetch_packet_loss;
+mod fetch_bgp;
+mod fetch_port_forward;
 
 const BLOCK_HEIGHT: u16 = 10;
 const BLOCK_WIDTH: u16 = 30;
 
+// How long a panel's border flashes after one of its values changes under --refresh-interval
+const CHANGE_FLASH_DURATION: Duration = Duration::from_secs(3);
+// xterm's orange, for values well outside what --refresh-interval has seen before
+const ANOMALY_COLOUR: Color = Color::Indexed(208);
+
+// Record types offered by the interactive DNS query tool
+const DNS_QUERY_TYPES: [rustdns::Type; 6] = [
+    rustdns::Type::A,
+    rustdns::Type::AAAA,
+    rustdns::Type::MX,
+    rustdns::Type::TXT,
+    rustdns::Type::PTR,
+    rustdns::Type::SRV,
+];
+
+/// Check and display network information to help diagnose network issues
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Record the session to an asciinema v2 .cast file
+    #[arg(long, value_name = "FILE")]
+    record: Option<PathBuf>,
+
+    /// Replay a session recorded with --record instead of running checks
+    #[arg(long, value_name = "FILE", conflicts_with = "record")]
+    replay: Option<PathBuf>,
+
+    /// Playback speed multiplier for --replay
+    #[arg(long, default_value_t = 1.0, requires = "replay")]
+    speed: f64,
+
+    /// Check this interface straight away instead of picking one interactively
+    #[arg(long, value_name = "NAME", conflicts_with = "replay")]
+    interface: Option<String>,
+
+    /// Run the checks without the TUI, print a summary and exit with 1 if any failed
+    #[arg(long, conflicts_with_all = ["record", "replay"])]
+    no_tui: bool,
+
+    /// How --no-tui reports results: a text summary, or the full results as JSON
+    #[arg(long, value_enum, default_value_t = headless::OutputFormat::Text, requires = "no_tui")]
+    output: headless::OutputFormat,
+
+    /// Shorthand for --no-tui --output json, for scripts and CI
+    #[arg(long, conflicts_with_all = ["record", "replay", "output"])]
+    json: bool,
+
+    /// How long to wait for each DNS server to answer, in milliseconds. Worth raising on
+    /// high latency links such as satellite, where the default reports working servers as failing
+    #[arg(long, value_name = "MS", default_value_t = fetch_dns::DEFAULT_DNS_TIMEOUT.as_millis() as u64, conflicts_with = "replay")]
+    dns_timeout: u64,
+
+    /// How many echo requests the packet loss test sends, 20ms apart
+    #[arg(long, value_name = "COUNT", default_value_t = fetch_packet_loss::DEFAULT_PROBE_COUNT, conflicts_with = "replay")]
+    loss_count: u16,
+
+    /// Where the packet loss test sends its echo requests
+    #[arg(long, value_name = "IP", default_value_t = fetch_internet::CLOUDFLARE_V4, conflicts_with = "replay")]
+    loss_target: Ipv4Addr,
+
+    /// Ask portchecker.co whether PORT on the public IP can be reached from outside, to confirm a
+    /// port forward on the router works
+    #[arg(long, value_name = "PORT", conflicts_with = "replay")]
+    check_port_forward: Option<u16>,
+
+    /// Also save the results to FILE as JSON, when netcheck exits or once --no-tui has finished
+    #[arg(long, value_name = "FILE", conflicts_with = "replay")]
+    output_file: Option<PathBuf>,
+
+    /// Re-run the checks every SECS seconds, 0 to only run them once
+    #[arg(long, value_name = "SECS", default_value_t = 0, conflicts_with_all = ["replay", "no_tui", "json"])]
+    refresh_interval: u64,
+
+    /// How many refreshes set the baseline for each metric before values well above it are flagged
+    #[arg(long, value_name = "COUNT", default_value_t = watch::DEFAULT_ANOMALY_BASELINE_SAMPLES, requires = "refresh_interval")]
+    anomaly_baseline: u64,
+
+    /// Append each anomaly --refresh-interval flags to FILE, with when it happened and how far out it was
+    #[arg(long, value_name = "FILE", requires = "refresh_interval")]
+    anomaly_log: Option<PathBuf>,
+}
+
 fn main() -> Result<()> {
+    let args = Args::parse();
+
     errors::install_hooks()?;
-    let mut terminal = tui::init()?;
 
-    let mut app = App::default();
+    if let Some(ref replay) = args.replay {
+        if args.speed <= 0.0 {
+            bail!("--speed must be greater than zero");
+        }
+
+        return recording::replay(replay, args.speed);
+    }
+
+    if args.dns_timeout == 0 {
+        bail!("--dns-timeout must be greater than zero");
+    }
+
+    let dns_timeout = Duration::from_millis(args.dns_timeout);
+
+    if args.loss_count == 0 || args.loss_count > fetch_packet_loss::MAX_PROBE_COUNT {
+        bail!("--loss-count must be between 1 and {}", fetch_packet_loss::MAX_PROBE_COUNT);
+    }
+
+    let loss_test = fetch_packet_loss::LossTestSettings {
+        target: args.loss_target,
+        count: args.loss_count,
+    };
+
+    if args.check_port_forward == Some(0) {
+        bail!("--check-port-forward needs a port between 1 and 65535");
+    }
 
     // Get list of network interfaces
     let interface_list = netlib::get_interfaces();
@@ -42,42 +180,184 @@ fn main() -> Result<()> {
         bail!("No network interfaces found");
     }
 
-    // If there is one, automatically select it
-    if interface_list.len() == 1 {
-        app.chosen_interface = Some(interface_list[0].clone());
+    let interface_names: Vec<&str> = interface_list.iter().map(|interface| interface.name.as_str()).collect();
+
+    // Checked before the TUI starts so the error ends up on a normal terminal
+    if let Some(ref interface) = args.interface {
+        if !interface_names.contains(&interface.as_str()) {
+            bail!("Interface '{}' not found. Available: {}", interface, interface_names.join(", "));
+        }
+    }
+
+    if args.no_tui || args.json {
+        let interface = match (args.interface, interface_list.len()) {
+            (Some(interface), _) => interface,
+            (None, 1) => interface_list[0].name.clone(),
+            (None, _) => bail!("--no-tui and --json need --interface when there's more than one. Available: {}", interface_names.join(", ")),
+        };
+
+        let output = match args.json {
+            true => headless::OutputFormat::Json,
+            false => args.output,
+        };
+
+        let mut app = App {
+            capabilities: netlib::detect_capabilities(),
+            dns_timeout,
+            loss_test,
+            check_port_forward: args.check_port_forward,
+            chosen_interface: Some(interface),
+            interface_list,
+            stage: ApplicationStage::Running,
+            ..Default::default()
+        };
+
+        app.initialise_interface_fetching();
+
+        let all_passed = headless::run(&mut app, output);
+
+        // On stderr so they don't end up mixed into the summary
+        for warning in &app.documentation_ip_warnings {
+            eprintln!("{}", warning);
+        }
+
+        if let Some(ref path) = args.output_file {
+            report::save(&app, path)?;
+        }
+
+        std::process::exit(if all_passed { 0 } else { 1 });
+    }
+
+    if args.anomaly_baseline < 2 {
+        bail!("--anomaly-baseline needs at least 2 samples to measure any spread");
+    }
+
+    let anomaly_log = match args.anomaly_log {
+        Some(ref path) => Some(
+            OpenOptions::new().create(true).append(true).open(path)
+                .wrap_err_with(|| format!("couldn't open anomaly log {}", path.display()))?
+        ),
+        None => None,
+    };
+
+    let recorder = match args.record {
+        Some(ref path) => {
+            let (width, height) = crossterm::terminal::size()?;
+            Some(recording::CastRecorder::create(path, width, height)?)
+        }
+        None => None,
+    };
+
+    let mut terminal = tui::init(recorder)?;
+
+    let mut app = App {
+        // Work out what we're allowed to do before any checks start
+        capabilities: netlib::detect_capabilities(),
+        dns_timeout,
+        loss_test,
+        check_port_forward: args.check_port_forward,
+        refresh_interval: match args.refresh_interval {
+            0 => None,
+            secs => Some(Duration::from_secs(secs)),
+        },
+        anomaly_baseline_samples: args.anomaly_baseline,
+        anomaly_log,
+        ..Default::default()
+    };
+
+    if let Some(interface) = args.interface {
+        app.chosen_interface = Some(interface);
+        app.interface_list = interface_list;
+        app.stage = ApplicationStage::Running;
+        app.initialise_interface_fetching();
+    } else if interface_list.len() == 1 {
+        // If there is one, automatically select it
+        app.chosen_interface = Some(interface_list[0].name.clone());
         app.interface_list = interface_list;
         app.stage = ApplicationStage::Running;
+        app.initialise_interface_fetching();
     } else {
         app.interface_list = interface_list;
     }
 
     app.run(&mut terminal)?;
     tui::restore()?;
+
+    // Only safe to print once the terminal is back to normal
+    for warning in &app.documentation_ip_warnings {
+        eprintln!("{}", warning);
+    }
+
+    if let Some(ref path) = args.output_file {
+        // Quitting from the picker means there's nothing to save
+        match app.chosen_interface {
+            Some(_) => report::save(&app, path)?,
+            None => eprintln!("No interface was checked, so nothing was written to {}", path.display()),
+        }
+    }
+
     Ok(())
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 enum ApplicationStage {
+    #[default]
     PickInterface,
     Running,
 }
 
-impl Default for ApplicationStage {
-    fn default() -> Self {
-        ApplicationStage::PickInterface
-    }
-}
-
 #[derive(Debug, Default)]
 pub struct App {
     exit: bool,
     network_info: internal_comms::NetworkInfo,
     stage: ApplicationStage,
-    interface_list: Vec<String>,
+    interface_list: Vec<netlib::InterfaceSummary>,
     interface_hover_index: usize,
+    // Index of the first interface shown when the list is taller than the picker
+    interface_scroll_offset: usize,
+    // The picker hides loopback and down interfaces unless this is toggled on
+    show_all_interfaces: bool,
+    // How many rows of panels are scrolled off the top, clamped when rendering
+    scroll_offset: usize,
+    last_fetch_started: Option<Instant>,
+    // Set by --refresh-interval to re-run the checks periodically
+    refresh_interval: Option<Duration>,
+    // The last refresh's results, to spot what changed in this one
+    previous_network_info: Option<internal_comms::NetworkInfo>,
+    // What's changed so far this refresh and when it was noticed, so the panels can flash
+    network_info_changes: Vec<(watch::NetworkInfoChange, Instant)>,
+    // A baseline per metric, kept across refreshes, and how many refreshes go into one
+    anomaly_detectors: HashMap<(&'static str, String), watch::AnomalyDetector>,
+    anomaly_baseline_samples: u64,
+    // Metrics already sampled this refresh, since later messages repeat the same values
+    sampled_metrics: Vec<(&'static str, String)>,
+    anomalies: Vec<watch::Anomaly>,
+    anomaly_log: Option<File>,
+    // Set from --dns-timeout, which defaults to fetch_dns::DEFAULT_DNS_TIMEOUT
+    dns_timeout: Duration,
+    // Set from --loss-count and --loss-target
+    loss_test: fetch_packet_loss::LossTestSettings,
+    // Set from --check-port-forward
+    check_port_forward: Option<u16>,
     chosen_interface: Option<String>,
     receive_new_data_channel: Option<mpsc::Receiver<FetchedDataMessage>>,
     block_width_practice: u32,
+    dns_query_popup: Option<DnsQueryPopup>,
+    // Whether the neighbours panel shows the ARP cache rather than NDP
+    show_arp_entries: bool,
+    documentation_ip_warnings: Vec<String>,
+    capabilities: netlib::Capabilities,
+}
+
+/// State of the interactive DNS query tool opened with 'd'
+#[derive(Debug, Default)]
+struct DnsQueryPopup {
+    domain: String,
+    record_type_index: usize,
+    servers_queried: usize,
+    results: Vec<DNSQueryResult>,
+    // Present once a query has been submitted
+    receive_results_channel: Option<mpsc::Receiver<DNSQueryResult>>,
 }
 
 impl App {
@@ -86,16 +366,28 @@ impl App {
         while !self.exit {
             // Pull in any new data from the channel
             if let Some(ref receive_new_data_channel) = self.receive_new_data_channel {
-                for message in receive_new_data_channel.try_iter() {
-                    match message {
-                        FetchedDataMessage::LocalInfo(local_info) => {
-                            self.network_info.local_info = local_info;
-                        }
-                        FetchedDataMessage::DNSInfo(dns_info) => {
-                            self.network_info.dns_info = dns_info;
-                        }
-                        _ => {}
-                    }
+                let messages: Vec<FetchedDataMessage> = receive_new_data_channel.try_iter().collect();
+
+                for message in messages {
+                    self.apply_message(message);
+                }
+            }
+
+            if let (ApplicationStage::Running, Some(refresh_interval), Some(last_fetch_started)) = (&self.stage, self.refresh_interval, self.last_fetch_started) {
+                // Unlike a manual re-run the old results stay up until new ones replace them, so the
+                // panels don't blank every interval. Swapping the channel still stops the old fetchers
+                if last_fetch_started.elapsed() >= refresh_interval {
+                    self.previous_network_info = Some(self.network_info.clone());
+                    self.network_info_changes.clear();
+                    self.sampled_metrics.clear();
+                    self.anomalies.clear();
+                    self.initialise_interface_fetching();
+                }
+            }
+
+            if let Some(ref mut popup) = self.dns_query_popup {
+                if let Some(ref receive_results_channel) = popup.receive_results_channel {
+                    popup.results.extend(receive_results_channel.try_iter());
                 }
             }
 
@@ -111,9 +403,25 @@ impl App {
             ApplicationStage::PickInterface => self.pick_interface_render_frame(frame),
             ApplicationStage::Running => self.running_render_frame(frame),
         }
+
+        if self.dns_query_popup.is_some() {
+            self.dns_query_render_popup(frame);
+        }
+    }
+
+    /// The interfaces the picker lists. If hiding the unusable ones would leave nothing,
+    /// everything is shown so there's still something to pick.
+    fn visible_interfaces(&self) -> Vec<&netlib::InterfaceSummary> {
+        let usable: Vec<&netlib::InterfaceSummary> = self.interface_list.iter().filter(|interface| interface.is_usable()).collect();
+
+        if self.show_all_interfaces || usable.is_empty() {
+            self.interface_list.iter().collect()
+        } else {
+            usable
+        }
     }
 
-    fn pick_interface_render_frame(&self, frame: &mut Frame) {
+    fn pick_interface_render_frame(&mut self, frame: &mut Frame) {
         let area = frame.size();
         let buf = frame.buffer_mut();
     
@@ -121,9 +429,10 @@ impl App {
     
         let instructions = Title::from(Line::from(vec![
             " Quit ".into(), "<Q> ".blue().bold(),
-            " Up ".into(), "↑".blue().bold(),
-            " Down ".into(), "↓".blue().bold(),
+            " Up ".into(), "↑/k".blue().bold(),
+            " Down ".into(), "↓/j".blue().bold(),
             " Select ".into(), "<Enter>".blue().bold(),
+            " Show all ".into(), "<A> ".blue().bold(),
         ]));
     
         let exterior_block = Block::default()
@@ -149,13 +458,46 @@ impl App {
             .title(subtitle);
     
         let interface_area = interfaces_block.inner(inner_area);
+
+        // Move the window only once the cursor reaches its edge, and never past the end of the list
+        let list_height = (interface_area.height as usize).max(1);
+        let interface_count = self.visible_interfaces().len();
+
+        if self.interface_hover_index < self.interface_scroll_offset {
+            self.interface_scroll_offset = self.interface_hover_index;
+        } else if self.interface_hover_index >= self.interface_scroll_offset + list_height {
+            self.interface_scroll_offset = self.interface_hover_index + 1 - list_height;
+        }
+
+        self.interface_scroll_offset = self.interface_scroll_offset.min(interface_count.saturating_sub(list_height));
     
-        let interface_items: Vec<ListItem> = self.interface_list.iter().enumerate().map(|(i, interface)| {
-            let content = if self.interface_hover_index == i {
-                Line::from(vec![Span::styled(format!("> {}", interface).to_string(), Style::default().add_modifier(Modifier::BOLD))])
+        let interface_items: Vec<ListItem> = self.visible_interfaces().into_iter().enumerate().skip(self.interface_scroll_offset).take(list_height).map(|(i, interface)| {
+            // Down interfaces are greyed out, since checks run on them will just fail
+            let name_style = match interface.is_up {
+                true => Style::default(),
+                false => Style::default().fg(Color::DarkGray),
+            };
+
+            let mut content = if self.interface_hover_index == i {
+                Line::from(vec![Span::styled(format!("> {}", netlib::display_name(&interface.name)).to_string(), name_style.add_modifier(Modifier::BOLD))])
             } else {
-                Line::from(interface.to_string())
+                Line::from(Span::styled(netlib::display_name(&interface.name), name_style))
             };
+
+            match interface.primary_ip {
+                Some(ip) => content.spans.push(Span::raw(format!("  {}", ip))),
+                None => content.spans.push(Span::styled("  no IP", Style::default().fg(Color::Yellow))),
+            }
+
+            match interface.is_up {
+                true => content.spans.push(Span::styled("  [up]", Style::default().fg(Color::Green))),
+                false => content.spans.push(Span::styled("  [down]", Style::default().fg(Color::Red))),
+            }
+
+            if let Some(description) = netlib::describe_interface(&interface.name) {
+                content.spans.push(Span::styled(format!("  {}", description), Style::default().fg(Color::DarkGray)));
+            }
+
             ListItem::new(content)
         }).collect();
     
@@ -171,47 +513,98 @@ impl App {
         let area = frame.size();
         let buf = frame.buffer_mut();
 
-        let interface_name = self.chosen_interface.as_ref().unwrap();
+        let interface_name = netlib::display_name(self.chosen_interface.as_ref().unwrap());
 
-        let title = Title::from(format!(" NETCHECK | {} ", interface_name).bold());
-        let instructions = Title::from(Line::from(vec![" Quit ".into(), "<Q> ".blue().bold()]));
-        let exterior_block = Block::default()
-            .title(title.alignment(Alignment::Center))
+        let title = match self.capabilities.raw_socket {
+            true => Title::from(format!(" NETCHECK | {} ", interface_name).bold()),
+            false => Title::from(Line::from(vec![
+                format!(" NETCHECK | {} | ", interface_name).bold(),
+                "Running in restricted mode (no raw socket access) ".yellow().bold(),
+            ])),
+        };
+        let mut instructions = vec![
+            " Quit ".into(), "<Q> ".blue().bold(),
+            " DNS Query ".into(), "<D> ".blue().bold(),
+            " Neighbours ".into(), "<N> ".blue().bold(),
+            " Re-run ".into(), "<R> ".blue().bold(),
+            " Back ".into(), "<Esc> ".blue().bold(),
+        ];
+
+        if let Some(last_fetch_started) = self.last_fetch_started {
+            instructions.push(format!(" Last run: {}s ago ", last_fetch_started.elapsed().as_secs()).into());
+        }
+
+        let exterior_block = |instructions: Vec<Span<'static>>| Block::default()
+            .title(title.clone().alignment(Alignment::Center))
             .title(
-                instructions
+                Title::from(Line::from(instructions))
                     .alignment(Alignment::Center)
                     .position(Position::Bottom),
             )
             .borders(Borders::TOP)
             .border_set(border::THICK);
 
-        let inner_area = exterior_block.inner(area);
-        exterior_block.render(area, buf);
+        // The title text doesn't change the inner area, so measure before the scroll position is known
+        let inner_area = exterior_block(Vec::new()).inner(area);
+
+        // Panels above, the health summary in the last two lines
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(2)].as_ref())
+            .split(inner_area);
+
+        let rows = chunks[0];
 
         let columns = inner_area.width / BLOCK_WIDTH;
         let column_width = inner_area.width / columns;
         self.block_width_practice = column_width as u32;
 
-        let mut blocks = Vec::new();
-
-        blocks.push(self.render_network_info(inner_area));
-        blocks.push(self.render_internet_info(inner_area));
-        blocks.push(self.render_dhcp_info(inner_area));
-        blocks.push(self.render_dns_info(inner_area));
-        blocks.push(self.render_traceroute_info(inner_area));
-        blocks.push(self.render_tcp_info(inner_area));
-        blocks.push(self.render_http_info(inner_area));
-        blocks.push(self.render_https_info(inner_area));
-        blocks.push(self.render_udp_info(inner_area));
-        blocks.push(self.render_ntp_info(inner_area));
-        blocks.push(self.render_quic_info(inner_area));
+        // Each panel goes with the NetworkInfo field it shows, which is how changes are tracked
+        let mut blocks = vec![("local_info", self.render_network_info(inner_area))];
+
+        // Wired interfaces have no Wi-Fi panel at all
+        blocks.extend(self.render_wifi_info(inner_area).map(|block| ("wifi_info", block)));
+
+        blocks.extend([
+            ("internet_info", self.render_internet_info(inner_area)),
+            ("ipv6_info", self.render_ipv6_info(inner_area)),
+            ("dhcp_info", self.render_dhcp_info(inner_area)),
+            ("dns_info", self.render_dns_info(inner_area)),
+            ("traceroute", self.render_traceroute_info(inner_area)),
+            ("packet_loss_info", self.render_packet_loss_info(inner_area)),
+            ("bgp_path_info", self.render_bgp_path_info(inner_area)),
+        ]);
+
+        // Only there when --check-port-forward asks for it
+        blocks.extend(self.render_port_forward_info(inner_area).map(|block| ("port_forward_info", block)));
+
+        blocks.extend([
+            ("tcp_info", self.render_tcp_info(inner_area)),
+            ("http_info", self.render_http_info(inner_area)),
+            ("https_info", self.render_https_info(inner_area)),
+            ("udp_info", self.render_udp_info(inner_area)),
+            ("ntp_info", self.render_ntp_info(inner_area)),
+            ("quic_info", self.render_quic_info(inner_area)),
+            ("ndp_entries", self.render_ndp_info(inner_area)),
+            ("lldp_info", self.render_lldp_info(inner_area)),
+            ("mdns_info", self.render_mdns_info(inner_area)),
+            ("network_devices", self.render_network_devices(inner_area)),
+            ("network_stats", self.render_network_stats_info(inner_area)),
+        ]);
+
+        // Can't scroll past the point where the last row of panels is at the bottom
+        let total_rows = blocks.len().div_ceil(columns as usize);
+        let visible_rows = ((rows.height / BLOCK_HEIGHT) as usize).max(1);
+        let scroll_offset = self.scroll_offset.min(total_rows.saturating_sub(visible_rows));
+
+        if total_rows > visible_rows {
+            instructions.push(" Scroll ".into());
+            instructions.push("<↑/↓/PgUp/PgDn> ".blue().bold());
+            instructions.push(format!(" Scroll: row {}/{} ", scroll_offset + 1, total_rows).into());
+        }
 
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([Constraint::Length(area.height - 2), Constraint::Length(2)].as_ref())
-            .split(inner_area);
+        exterior_block(instructions).render(area, buf);
 
-        let rows = chunks[0];
         let columns_layout = Layout::default()
             .direction(Direction::Horizontal)
             .constraints(
@@ -221,13 +614,18 @@ impl App {
             )
             .split(rows);
 
-        for (i, block) in blocks.into_iter().enumerate() {
+        for (i, (panel, block)) in blocks.into_iter().enumerate() {
             let col = i % columns as usize;
             let row = i / columns as usize;
-            let y_position = columns_layout[col].y + row as u16 * BLOCK_HEIGHT;
 
-            // Ensure the block is within the terminal area
-            if y_position + BLOCK_HEIGHT <= area.height {
+            if row < scroll_offset {
+                continue;
+            }
+
+            let y_position = columns_layout[col].y + (row - scroll_offset) as u16 * BLOCK_HEIGHT;
+
+            // Ensure the block is within the panel area, clear of the status bar
+            if y_position + BLOCK_HEIGHT <= rows.y + rows.height {
                 let rect = Rect::new(
                     columns_layout[col].x,
                     y_position,
@@ -235,305 +633,2273 @@ impl App {
                     BLOCK_HEIGHT,
                 );
                 block.render(rect, buf); // Render each block directly
+
+                if let Some(kind) = self.panel_flash(panel) {
+                    flash_border(rect, buf, kind);
+                }
+
+                if self.anomalies.iter().any(|anomaly| anomaly.panel == panel) {
+                    mark_anomaly(rect, buf);
+                }
             }
         }
+
+        self.render_status_bar().render(chunks[1], buf);
+
+        self.scroll_offset = scroll_offset;
+    }
+
+    /// One mark per area of the network, so problems stand out without reading every panel
+    fn render_status_bar(&self) -> Paragraph<'_> {
+        let mut spans = Vec::new();
+
+        for (name, status) in compute_health(&self.network_info) {
+            let (mark, colour) = match status {
+                HealthStatus::Good => ("✓", Color::Green),
+                HealthStatus::Degraded => ("~", Color::Yellow),
+                HealthStatus::Failed => ("✗", Color::Red),
+                HealthStatus::Unknown => ("?", Color::Yellow),
+            };
+
+            spans.push(Span::styled(format!("{} ", mark), Style::default().fg(colour).bold()));
+            spans.push(Span::raw(format!("{}  ", name)));
+        }
+
+        if let (Some(refresh_interval), Some(last_fetch_started)) = (self.refresh_interval, self.last_fetch_started) {
+            let remaining = refresh_interval.saturating_sub(last_fetch_started.elapsed());
+            spans.push(Span::styled(format!("Next refresh in {}s", remaining.as_secs()), Style::default().fg(Color::DarkGray)));
+        }
+
+        Paragraph::new(Line::from(spans)).alignment(Alignment::Center)
     }
 
     fn handle_events(&mut self) -> Result<()> {
-        let now = std::time::Instant::now();
-
-        while now.elapsed() < std::time::Duration::from_millis(50) {
-            match event::poll(std::time::Duration::from_millis(50))? {
-                true => {
-                    return match event::read()? {
-                        // it's important to check that the event is a key press event as
-                        // crossterm also emits key release and repeat events on Windows.
-                        Event::Key(key_event) if key_event.kind == KeyEventKind::Press => self
-                            .handle_key_event(key_event)
-                            .wrap_err_with(|| format!("handling key event failed:\n{key_event:#?}")),
-                        _ => Ok(()),
-                    };
-                }
-                false => {
-                    break;
-                }
-            }
+        if event::poll(std::time::Duration::from_millis(50))? {
+            return match event::read()? {
+                // it's important to check that the event is a key press event as
+                // crossterm also emits key release and repeat events on Windows.
+                Event::Key(key_event) if key_event.kind == KeyEventKind::Press => self
+                    .handle_key_event(key_event)
+                    .wrap_err_with(|| format!("handling key event failed:\n{key_event:#?}")),
+                _ => Ok(()),
+            };
         }
 
         Ok(())
     }
 
     fn handle_key_event(&mut self, key_event: KeyEvent) -> Result<()> {
+        // The DNS query popup takes all input while it's open so domains can contain 'q' etc.
+        if self.dns_query_popup.is_some() {
+            self.handle_dns_query_key_event(key_event);
+            return Ok(());
+        }
+
         match key_event.code {
             KeyCode::Char('q') => self.exit(),
             KeyCode::Char('Q') => self.exit(),
-            KeyCode::Up => {
-                if let ApplicationStage::PickInterface = self.stage {
-                    if self.interface_hover_index > 0 {
-                        self.interface_hover_index -= 1;
+            KeyCode::Up | KeyCode::Char('k') => {
+                match self.stage {
+                    ApplicationStage::PickInterface => {
+                        if self.interface_hover_index > 0 {
+                            self.interface_hover_index -= 1;
+                        }
+                    }
+                    ApplicationStage::Running => {
+                        self.scroll_offset = self.scroll_offset.saturating_sub(1);
                     }
                 }
             },
-            KeyCode::Down => {
-                if let ApplicationStage::PickInterface = self.stage {
-                    if self.interface_hover_index < self.interface_list.len() - 1 {
-                        self.interface_hover_index += 1;
+            KeyCode::Down | KeyCode::Char('j') => {
+                match self.stage {
+                    ApplicationStage::PickInterface => {
+                        if self.interface_hover_index < self.visible_interfaces().len() - 1 {
+                            self.interface_hover_index += 1;
+                        }
                     }
+                    // Scrolling past the end is undone when the next frame is drawn
+                    ApplicationStage::Running => {
+                        self.scroll_offset = self.scroll_offset.saturating_add(1);
+                    }
+                }
+            },
+            KeyCode::Char('g') => {
+                if let ApplicationStage::PickInterface = self.stage {
+                    self.interface_hover_index = 0;
+                }
+            },
+            KeyCode::Char('G') => {
+                if let ApplicationStage::PickInterface = self.stage {
+                    self.interface_hover_index = self.visible_interfaces().len() - 1;
                 }
             },
             KeyCode::Enter => {
                 if let ApplicationStage::PickInterface = self.stage {
-                    self.chosen_interface = Some(self.interface_list[self.interface_hover_index].clone());
+                    self.chosen_interface = Some(self.visible_interfaces()[self.interface_hover_index].name.clone());
                     self.stage = ApplicationStage::Running;
 
                     // Initialise fetching of network information
                     self.initialise_interface_fetching();
                 }
             },
+            KeyCode::Char('r') | KeyCode::Char('R') => {
+                if let ApplicationStage::Running = self.stage {
+                    self.stop_fetching();
+                    self.initialise_interface_fetching();
+                }
+            },
+            KeyCode::Esc | KeyCode::Char('b') | KeyCode::Char('B') => {
+                if let ApplicationStage::Running = self.stage {
+                    self.stop_fetching();
+                    self.chosen_interface = None;
+                    self.scroll_offset = 0;
+                    self.last_fetch_started = None;
+                    self.stage = ApplicationStage::PickInterface;
+                }
+            },
+            KeyCode::PageUp => {
+                if let ApplicationStage::Running = self.stage {
+                    self.scroll_offset = self.scroll_offset.saturating_sub(1);
+                }
+            },
+            // Scrolling past the end is undone when the next frame is drawn
+            KeyCode::PageDown => {
+                if let ApplicationStage::Running = self.stage {
+                    self.scroll_offset = self.scroll_offset.saturating_add(1);
+                }
+            },
+            KeyCode::Home => {
+                if let ApplicationStage::Running = self.stage {
+                    self.scroll_offset = 0;
+                }
+            },
+            KeyCode::End => {
+                if let ApplicationStage::Running = self.stage {
+                    self.scroll_offset = usize::MAX;
+                }
+            },
+            KeyCode::Char('a') | KeyCode::Char('A') => {
+                if let ApplicationStage::PickInterface = self.stage {
+                    self.show_all_interfaces = !self.show_all_interfaces;
+                    self.interface_hover_index = 0;
+                    self.interface_scroll_offset = 0;
+                }
+            },
+            KeyCode::Char('d') | KeyCode::Char('D') => {
+                if let ApplicationStage::Running = self.stage {
+                    self.dns_query_popup = Some(DnsQueryPopup::default());
+                }
+            },
+            KeyCode::Char('n') | KeyCode::Char('N') => {
+                if let ApplicationStage::Running = self.stage {
+                    self.show_arp_entries = !self.show_arp_entries;
+                }
+            },
             _ => {}
         }
         Ok(())
     }
 
-    fn initialise_interface_fetching(&mut self) {
-        let (send, receive): (Sender<FetchedDataMessage>, Receiver<FetchedDataMessage>) = mpsc::channel();
+    fn handle_dns_query_key_event(&mut self, key_event: KeyEvent) {
+        let popup = match self.dns_query_popup {
+            Some(ref mut popup) => popup,
+            None => return,
+        };
+
+        // Showing results: Enter goes back to editing the query, Esc closes
+        if popup.receive_results_channel.is_some() {
+            match key_event.code {
+                KeyCode::Esc => self.dns_query_popup = None,
+                KeyCode::Enter => {
+                    popup.receive_results_channel = None;
+                    popup.results.clear();
+                },
+                _ => {}
+            }
+            return;
+        }
 
-        self.receive_new_data_channel = Some(receive);
+        match key_event.code {
+            KeyCode::Esc => self.dns_query_popup = None,
+            KeyCode::Char(c) if !c.is_whitespace() => popup.domain.push(c),
+            KeyCode::Backspace => {
+                popup.domain.pop();
+            },
+            KeyCode::Tab | KeyCode::Right | KeyCode::Down => {
+                popup.record_type_index = (popup.record_type_index + 1) % DNS_QUERY_TYPES.len();
+            },
+            KeyCode::BackTab | KeyCode::Left | KeyCode::Up => {
+                popup.record_type_index = (popup.record_type_index + DNS_QUERY_TYPES.len() - 1) % DNS_QUERY_TYPES.len();
+            },
+            KeyCode::Enter => {
+                if popup.domain.is_empty() {
+                    return;
+                }
 
-        let chosen_interface = self.chosen_interface.clone().unwrap();
+                // Servers of the other address family can't be reached from the interface's socket
+                let servers: Vec<String> = self.network_info.dns_info.dns_servers.iter()
+                    .filter(|server| !server.wrong_family)
+                    .map(|server| server.ip.clone())
+                    .collect();
 
-        let send_1 = send.clone();
-        let chosen_interface_1 = chosen_interface.clone();
+                let (send, receive): (Sender<DNSQueryResult>, Receiver<DNSQueryResult>) = mpsc::channel();
 
-        thread::spawn(move || {
-            fetch_local::fetch_and_return_local_info(send_1, chosen_interface_1);
-        });
+                popup.servers_queried = servers.len();
+                popup.receive_results_channel = Some(receive);
 
-        thread::spawn(move || {
-            fetch_dns::fetch_and_return_dns_info(send, chosen_interface);
-        });
-    }
+                let interface = self.chosen_interface.clone().unwrap();
+                let domain = popup.domain.clone();
+                let record_type = DNS_QUERY_TYPES[popup.record_type_index];
+                let timeout = self.dns_timeout;
 
-    fn exit(&mut self) {
-        self.exit = true;
+                thread::spawn(move || {
+                    fetch_dns::run_dns_query(send, servers, interface, domain, record_type, timeout);
+                });
+            },
+            _ => {}
+        }
     }
 
-    fn render_network_info(&self, area: Rect) -> Paragraph {
-        let mut text = Vec::with_capacity(3);
-
-        let max_width = self.block_width_practice as usize - 2;
-        
-        match &self.network_info.local_info.local_ip {
-            Some(local_ip) => {
-                let ip_str = local_ip.to_string();
-                let padding = max_width.saturating_sub("Local IP: ".len() + ip_str.len());
-
-                text.push(Line::from(vec![
-                    Span::styled("Local IP: ", Style::default().bold()),
-                    Span::raw(" ".repeat(padding)),
-                    Span::styled(ip_str, Style::default().fg(Color::Green)),
-                ]));
+    /// Stores a fetcher's update in the matching panel's info
+    fn apply_message(&mut self, message: FetchedDataMessage) {
+        // Only these panels hold the addresses check_for_documentation_ips looks at
+        let may_hold_documentation_ips = matches!(
+            message,
+            FetchedDataMessage::LocalInfo(_) | FetchedDataMessage::InternetInfo(_) | FetchedDataMessage::DNSInfo(_)
+        );
+        let panel = message.panel();
+
+        match message {
+            FetchedDataMessage::LocalInfo(local_info) => {
+                self.network_info.local_info = local_info;
             }
-            None => {
-                let padding = max_width.saturating_sub("Local IP: Unknown".len());
-                text.push(Line::from(vec![
-                    Span::styled("Local IP: ", Style::default().bold()),
-                    Span::raw(" ".repeat(padding)),
-                    Span::styled("Unknown", Style::default().fg(Color::Red)),
-                ]));
+            FetchedDataMessage::InternetInfo(internet_info) => {
+                self.network_info.internet_info = internet_info;
             }
-        }
-    
-        match &self.network_info.local_info.subnet_mask {
-            Some(subnet_mask) => {
-                let mask_str = subnet_mask.to_string();
-                let padding = max_width.saturating_sub("Subnet Mask: ".len() + mask_str.len());
-                text.push(Line::from(vec![
-                    Span::styled("Subnet Mask: ", Style::default().bold()),
-                    Span::raw(" ".repeat(padding)),
-                    Span::styled(mask_str, Style::default().fg(Color::Green)),
-                ]));
+            FetchedDataMessage::DHCPInfo(dhcp_info) => {
+                self.network_info.dhcp_info = dhcp_info;
             }
-            None => {
-                let padding = max_width.saturating_sub("Subnet Mask: Unknown".len());
-                text.push(Line::from(vec![
-                    Span::styled("Subnet Mask: ", Style::default().bold()),
-                    Span::raw(" ".repeat(padding)),
-                    Span::styled("Unknown", Style::default().fg(Color::Red)),
-                ]));
+            FetchedDataMessage::Traceroute(traceroute) => {
+                self.network_info.traceroute = traceroute;
             }
-        }
-    
-        match &self.network_info.local_info.gateway {
-            Some(gateway) => {
-                let gateway_str = gateway.to_string();
-                let padding = max_width.saturating_sub("Gateway: ".len() + gateway_str.len());
-                text.push(Line::from(vec![
-                    Span::styled("Gateway: ", Style::default().bold()),
-                    Span::raw(" ".repeat(padding)),
-                    Span::styled(gateway_str, Style::default().fg(Color::Green)),
-                ]));
+            FetchedDataMessage::TCPInfo(tcp_info) => {
+                self.network_info.tcp_info = tcp_info;
+                self.network_info.quic_info.quic_port_blocked = fetch_quic::is_quic_port_blocked(&self.network_info.quic_info, &self.network_info.tcp_info);
             }
-            None => {
-                let padding = max_width.saturating_sub("Gateway: Unknown".len());
-                text.push(Line::from(vec![
-                    Span::styled("Gateway: ", Style::default().bold()),
-                    Span::raw(" ".repeat(padding)),
-                    Span::styled("Unknown", Style::default().fg(Color::Red)),
-                ]));
+            FetchedDataMessage::HTTPInfo(http_info) => {
+                self.network_info.http_info = http_info;
+            }
+            FetchedDataMessage::HTTPSInfo(https_info) => {
+                self.network_info.https_info = https_info;
+            }
+            FetchedDataMessage::NTPInfo(ntp_info) => {
+                self.network_info.ntp_info = ntp_info;
+            }
+            FetchedDataMessage::QUICInfo(quic_info) => {
+                self.network_info.quic_info = quic_info;
+                self.network_info.quic_info.quic_port_blocked = fetch_quic::is_quic_port_blocked(&self.network_info.quic_info, &self.network_info.tcp_info);
+            }
+            FetchedDataMessage::UDPInfo(udp_info) => {
+                self.network_info.udp_info = udp_info;
+            }
+            FetchedDataMessage::DNSInfo(dns_info) => {
+                self.network_info.dns_info = dns_info;
+            }
+            FetchedDataMessage::NDPEntries(ndp_entries) => {
+                self.network_info.ndp_entries = ndp_entries;
+            }
+            FetchedDataMessage::ArpEntries(arp_entries) => {
+                self.network_info.arp_entries = arp_entries;
+            }
+            FetchedDataMessage::LldpInfo(lldp_info) => {
+                self.network_info.lldp_info = lldp_info;
+            }
+            FetchedDataMessage::MdnsInfo(mdns_info) => {
+                self.network_info.mdns_info = mdns_info;
+            }
+            FetchedDataMessage::NetworkDevices(network_devices) => {
+                self.network_info.network_devices = network_devices;
+            }
+            FetchedDataMessage::NetworkStats(network_stats) => {
+                self.network_info.network_stats = network_stats;
+            }
+            FetchedDataMessage::WifiInfo(wifi_info) => {
+                self.network_info.wifi_info = wifi_info;
+            }
+            FetchedDataMessage::IPv6Info(ipv6_info) => {
+                self.network_info.ipv6_info = ipv6_info;
+            }
+            FetchedDataMessage::PacketLossInfo(packet_loss_info) => {
+                self.network_info.packet_loss_info = packet_loss_info;
+            }
+            FetchedDataMessage::BgpPathInfo(bgp_path_info) => {
+                self.network_info.bgp_path_info = bgp_path_info;
+            }
+            FetchedDataMessage::PortForwardInfo(port_forward_info) => {
+                self.network_info.port_forward_info = Some(port_forward_info);
             }
         }
 
-        let title = Span::styled("Network Info", Style::default().add_modifier(Modifier::BOLD));
-    
-        Paragraph::new(Text::from(text))
-            .block(Block::default().title(title).borders(Borders::ALL))
-    }
-
-    fn render_internet_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("Public IP: 203.0.113.1"),
-            Line::from("ASN: 12345"),
-            Line::from("Reverse DNS: example.com"),
-            Line::from("ISP: Example ISP"),
-            Line::from("Location: Somewhere, Earth"),
-            Line::from("Cloudflare Ping: 1ms")
-        ];
-        Paragraph::new(Text::from(text)).block(
-            Block::default()
-                .title("Internet Info")
-                .borders(Borders::ALL),
-        )
-    }
-
-    fn render_dhcp_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("DHCP Server: 192.168.0.1"),
-            Line::from("Lease Time: 86400"),
-            Line::from("Last Renewed: 43200"),
-        ];
-        Paragraph::new(Text::from(text))
-            .block(Block::default().title("DHCP Info").borders(Borders::ALL))
-    }
-
-    fn render_dns_info(&self, _area: Rect) -> Paragraph {
-        if self.network_info.dns_info.can_fetch == None {
-            return Paragraph::new(Text::from(vec![Line::from("Fetching list...")]))
-                .block(Block::default().title("DNS Info").borders(Borders::ALL));
+        if may_hold_documentation_ips {
+            self.check_for_documentation_ips();
         }
 
-        if self.network_info.dns_info.can_bind_interface == Some(false) {
-            return Paragraph::new(Text::from(vec![Line::from("Can't bind to interface.")]).bold().red())
-                .block(Block::default().title("DNS Info").borders(Borders::ALL));
+        if let Some(ref previous_network_info) = self.previous_network_info {
+            for change in watch::diff_network_info(previous_network_info, &self.network_info) {
+                // Every later message this refresh sees the same change, which shouldn't restart the flash
+                if !self.network_info_changes.iter().any(|(noticed, _)| *noticed == change) {
+                    self.network_info_changes.push((change, Instant::now()));
+                }
+            }
         }
 
-        if self.network_info.dns_info.can_fetch == Some(false) {
-            return Paragraph::new(Text::from(vec![Line::from("Failed to get list.")]).bold().red())
-                .block(Block::default().title("DNS Info").borders(Borders::ALL));
+        // Only a run repeated on an interval builds up a baseline to compare against
+        if self.refresh_interval.is_some() {
+            self.detect_anomalies(panel);
         }
+    }
 
-        let mut text = Vec::new();
+    /// Samples the metrics the panel just got final values for, once per refresh. The other panels
+    /// still show the last refresh's values until their own messages arrive, so they're left alone.
+    fn detect_anomalies(&mut self, panel: &'static str) {
+        for (metric_panel, field, value) in watch::numeric_metrics(&self.network_info) {
+            if metric_panel != panel || self.sampled_metrics.iter().any(|(sampled_panel, sampled_field)| *sampled_panel == panel && *sampled_field == field) {
+                continue;
+            }
 
-        let max_width = self.block_width_practice as usize - 2;
+            self.sampled_metrics.push((panel, field.clone()));
 
-        if self.network_info.dns_info.dns_servers.len() == 0 {
-            text.push(Line::from("No DNS servers found."));
-        } else {
-            text.push(Line::from(vec![Span::styled("Servers (/etc/resolv.conf):", Style::default().bold())]));
+            let detector = self.anomaly_detectors.entry((panel, field.clone())).or_default();
 
-            for server in &self.network_info.dns_info.dns_servers {
-                let colour = match server.can_resolve {
-                    Some(true) => Color::Green,
-                    Some(false) => Color::Red,
+            let (mean, stddev) = (detector.mean(), detector.stddev());
+
+            if !detector.observe(value, self.anomaly_baseline_samples) {
+                continue;
+            }
+
+            let anomaly = watch::Anomaly { panel, field, value, mean, stddev };
+
+            if let Some(ref mut anomaly_log) = self.anomaly_log {
+                let timestamp = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .map(|duration| duration.as_secs())
+                    .unwrap_or(0);
+
+                // There's nowhere to show a failed write while the TUI is up, the panel still flags it
+                let _ = writeln!(anomaly_log, "{}", anomaly.log_line(timestamp));
+            }
+
+            self.anomalies.push(anomaly);
+        }
+    }
+
+    fn is_anomalous(&self, panel: &str, field: &str) -> bool {
+        self.anomalies.iter().any(|anomaly| anomaly.panel == panel && anomaly.field == field)
+    }
+
+    /// The colour to show a metric's value in, orange while it's an anomaly
+    fn anomaly_colour(&self, panel: &str, field: &str, colour: Color) -> Color {
+        match self.is_anomalous(panel, field) {
+            true => ANOMALY_COLOUR,
+            false => colour,
+        }
+    }
+
+    /// How a panel's border should flash if one of its values changed in the last few seconds.
+    /// A degradation wins over an improvement when both happened.
+    fn panel_flash(&self, panel: &str) -> Option<watch::ChangeKind> {
+        let recent = self.network_info_changes.iter()
+            .filter(|(change, noticed)| change.panel == panel && noticed.elapsed() < CHANGE_FLASH_DURATION)
+            .map(|(change, _)| change.kind);
+
+        recent.fold(None, |flash, kind| match (flash, kind) {
+            (Some(watch::ChangeKind::Degraded), _) => flash,
+            _ => Some(kind),
+        })
+    }
+
+    /// Drops the results so far and disconnects the running fetchers. Their next send fails once
+    /// the receiver is gone, which is their cue to stop, so nothing stale reaches a later run.
+    fn stop_fetching(&mut self) {
+        self.receive_new_data_channel = None;
+        self.network_info = internal_comms::NetworkInfo::default();
+        // Going back may pick another interface, whose results shouldn't be compared with these
+        self.previous_network_info = None;
+        self.network_info_changes.clear();
+        self.anomaly_detectors.clear();
+        self.sampled_metrics.clear();
+        self.anomalies.clear();
+    }
+
+    fn initialise_interface_fetching(&mut self) {
+        let (send, receive): (Sender<FetchedDataMessage>, Receiver<FetchedDataMessage>) = mpsc::channel();
+
+        self.receive_new_data_channel = Some(receive);
+        self.last_fetch_started = Some(Instant::now());
+
+        let chosen_interface = self.chosen_interface.clone().unwrap();
+
+        let send_1 = send.clone();
+        let chosen_interface_1 = chosen_interface.clone();
+        let raw_socket = self.capabilities.raw_socket;
+
+        thread::spawn(move || {
+            fetch_local::fetch_and_return_local_info(send_1, chosen_interface_1, raw_socket);
+        });
+
+        let send_2 = send.clone();
+        let chosen_interface_2 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_ndp::fetch_and_return_ndp_entries(send_2, chosen_interface_2);
+        });
+
+        // Listening for LLDP frames needs a raw socket
+        if self.capabilities.raw_socket {
+            let send_3 = send.clone();
+            let chosen_interface_3 = chosen_interface.clone();
+
+            thread::spawn(move || {
+                fetch_lldp::fetch_and_return_lldp_info(send_3, chosen_interface_3);
+            });
+        }
+
+        let send_4 = send.clone();
+        let chosen_interface_4 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_mdns::fetch_and_return_mdns_info(send_4, chosen_interface_4);
+        });
+
+        let send_5 = send.clone();
+        let chosen_interface_5 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_devices::fetch_and_return_network_devices(send_5, chosen_interface_5);
+        });
+
+        let send_6 = send.clone();
+        let chosen_interface_6 = chosen_interface.clone();
+        let check_port_forward = self.check_port_forward;
+
+        thread::spawn(move || {
+            fetch_internet::fetch_and_return_internet_info(send_6, chosen_interface_6, check_port_forward);
+        });
+
+        let send_7 = send.clone();
+        let chosen_interface_7 = chosen_interface.clone();
+        let admin = self.capabilities.admin;
+
+        thread::spawn(move || {
+            fetch_dhcp_info::fetch_and_return_dhcp_info(send_7, chosen_interface_7, admin);
+        });
+
+        // Raw ICMP is needed to see the Time Exceeded messages
+        if self.capabilities.raw_socket {
+            let send_8 = send.clone();
+            let chosen_interface_8 = chosen_interface.clone();
+
+            thread::spawn(move || {
+                fetch_traceroute::fetch_and_return_traceroute(send_8, chosen_interface_8);
+            });
+        }
+
+        let send_9 = send.clone();
+        let chosen_interface_9 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_tcp::fetch_and_return_tcp_info(send_9, chosen_interface_9);
+        });
+
+        let send_10 = send.clone();
+        let chosen_interface_10 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_http::fetch_and_return_http_info(send_10, chosen_interface_10);
+        });
+
+        let send_11 = send.clone();
+        let chosen_interface_11 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_udp::fetch_and_return_udp_info(send_11, chosen_interface_11);
+        });
+
+        let send_12 = send.clone();
+        let chosen_interface_12 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_https::fetch_and_return_https_info(send_12, chosen_interface_12);
+        });
+
+        let send_13 = send.clone();
+        let chosen_interface_13 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_ntp::fetch_and_return_ntp_info(send_13, chosen_interface_13);
+        });
+
+        let send_14 = send.clone();
+        let chosen_interface_14 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_quic::fetch_and_return_quic_info(send_14, chosen_interface_14);
+        });
+
+        let send_15 = send.clone();
+        let chosen_interface_15 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_network_stats::fetch_and_return_network_stats(send_15, chosen_interface_15);
+        });
+
+        let send_16 = send.clone();
+        let chosen_interface_16 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_wifi_info::fetch_and_return_wifi_info(send_16, chosen_interface_16);
+        });
+
+        let send_17 = send.clone();
+        let chosen_interface_17 = chosen_interface.clone();
+
+        thread::spawn(move || {
+            fetch_ipv6_info::fetch_and_return_ipv6_info(send_17, chosen_interface_17, raw_socket);
+        });
+
+        // Sending the echo requests needs a raw socket too
+        if self.capabilities.raw_socket {
+            let send_18 = send.clone();
+            let chosen_interface_18 = chosen_interface.clone();
+            let loss_test = self.loss_test;
+
+            thread::spawn(move || {
+                fetch_packet_loss::fetch_and_return_packet_loss_info(send_18, chosen_interface_18, loss_test);
+            });
+        }
+
+        let dns_timeout = self.dns_timeout;
+
+        thread::spawn(move || {
+            fetch_dns::fetch_and_return_dns_info(send, chosen_interface, dns_timeout);
+        });
+    }
+
+    fn exit(&mut self) {
+        self.exit = true;
+    }
+
+    /// Shown in place of results for checks skipped because we lack raw socket access
+    fn privileges_required_message(&self) -> Vec<Line<'_>> {
+        vec![
+            Line::from("Requires elevated privileges").yellow(),
+            Line::from(self.privileges_hint()).dark_gray(),
+        ]
+    }
+
+    fn privileges_hint(&self) -> &'static str {
+        // Root without raw sockets means a container or VM that's had the capability dropped
+        match self.capabilities.admin {
+            true => "Needs CAP_NET_RAW.",
+            false => "Try running as root.",
+        }
+    }
+
+    /// Warns about results holding documentation-range IPs, which means placeholder
+    /// data has leaked into a field that should contain something real.
+    fn check_for_documentation_ips(&mut self) {
+        let info = &self.network_info;
+
+        let local_ips: Vec<String> = info.local_info.local_ips.iter().map(|(ip, _)| ip.to_string()).collect();
+
+        let mut candidates = vec![
+            info.local_info.gateway.as_deref(),
+            info.internet_info.public_ip.as_deref(),
+        ];
+        candidates.extend(local_ips.iter().map(|ip| Some(ip.as_str())));
+        candidates.extend(info.dns_info.dns_servers.iter().map(|server| Some(server.ip.as_str())));
+
+        for candidate in candidates.into_iter().flatten() {
+            let ip = match candidate.parse::<std::net::IpAddr>() {
+                Ok(ip) => ip,
+                Err(_) => continue,
+            };
+
+            let warning = format!("Warning: {} is a documentation IP, not real data", ip);
+
+            if netlib::is_documentation_ip(&ip) && !self.documentation_ip_warnings.contains(&warning) {
+                self.documentation_ip_warnings.push(warning);
+            }
+        }
+    }
+
+    fn dns_query_render_popup(&self, frame: &mut Frame) {
+        let popup = match self.dns_query_popup {
+            Some(ref popup) => popup,
+            None => return,
+        };
+
+        let area = frame.size();
+
+        // Centre the popup, leaving a margin of the surrounding view visible
+        let width = (area.width * 7 / 10).max(40).min(area.width);
+        let height = (area.height * 6 / 10).max(10).min(area.height);
+        let popup_area = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        let record_type = DNS_QUERY_TYPES[popup.record_type_index];
+
+        let mut text = Vec::new();
+
+        let instructions = if popup.receive_results_channel.is_none() {
+            text.push(Line::from(vec![
+                Span::styled("Domain: ", Style::default().bold()),
+                Span::raw(popup.domain.clone()),
+                Span::styled("█", Style::default().add_modifier(Modifier::SLOW_BLINK)),
+            ]));
+
+            let mut type_line = vec![Span::styled("Type:   ", Style::default().bold())];
+            for (i, query_type) in DNS_QUERY_TYPES.iter().enumerate() {
+                let style = if i == popup.record_type_index {
+                    Style::default().add_modifier(Modifier::REVERSED).bold()
+                } else {
+                    Style::default()
+                };
+                type_line.push(Span::styled(format!(" {} ", query_type), style));
+                type_line.push(Span::raw(" "));
+            }
+            text.push(Line::from(type_line));
+
+            if record_type == rustdns::Type::PTR {
+                text.push(Line::from(""));
+                text.push(Line::from(Span::styled("IP addresses are converted to reverse lookup names.", Style::default().fg(Color::DarkGray))));
+            }
+
+            Line::from(vec![
+                " Query ".into(), "<Enter> ".blue().bold(),
+                " Type ".into(), "<Tab> ".blue().bold(),
+                " Close ".into(), "<Esc> ".blue().bold(),
+            ])
+        } else {
+            text.push(Line::from(vec![
+                Span::styled(format!("{} {}", record_type, popup.domain), Style::default().bold()),
+            ]));
+            text.push(Line::from(""));
+
+            if popup.servers_queried == 0 {
+                text.push(Line::from(Span::styled("No DNS servers to query.", Style::default().fg(Color::Red))));
+            }
+
+            for result in &popup.results {
+                let (colour, status) = match result.rcode {
+                    Some(ref rcode) if rcode == "NoError" => (Color::Green, rcode.clone()),
+                    Some(ref rcode) => (Color::Yellow, rcode.clone()),
+                    None => (Color::Red, "No response".to_string()),
+                };
+
+                text.push(Line::from(vec![
+                    Span::styled(format!("{}  ", result.server), Style::default().bold()),
+                    Span::styled(status, Style::default().fg(colour)),
+                ]));
+
+                if result.rcode.is_some() && result.answers.is_empty() {
+                    text.push(Line::from(Span::styled("  (no answers)", Style::default().fg(Color::DarkGray))));
+                }
+
+                for answer in &result.answers {
+                    text.push(Line::from(format!("  {}", answer)));
+                }
+            }
+
+            let outstanding = popup.servers_queried.saturating_sub(popup.results.len());
+            if outstanding > 0 {
+                text.push(Line::from(Span::styled(format!("Waiting for {} server(s)...", outstanding), Style::default().fg(Color::Yellow))));
+            }
+
+            Line::from(vec![
+                " New Query ".into(), "<Enter> ".blue().bold(),
+                " Close ".into(), "<Esc> ".blue().bold(),
+            ])
+        };
+
+        let block = Block::default()
+            .title(Title::from(" DNS Query ".bold()).alignment(Alignment::Center))
+            .title(Title::from(instructions).alignment(Alignment::Center).position(Position::Bottom))
+            .borders(Borders::ALL)
+            .border_set(border::THICK);
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(
+            Paragraph::new(Text::from(text)).wrap(Wrap { trim: false }).block(block),
+            popup_area,
+        );
+    }
+
+    fn render_network_info(&self, _area: Rect) -> Paragraph<'_> {
+        // The panel has BLOCK_HEIGHT - 2 lines, so everything fits in one line each and the
+        // fixed rows come before the addresses, which are the ones allowed to run off the bottom
+        let mut text = Vec::with_capacity(8);
+
+        let max_width = self.block_width_practice as usize - 2;
+        let local_info = &self.network_info.local_info;
+
+        let row = |label: &'static str, value: String, colour: Color| {
+            let padding = max_width.saturating_sub(label.len() + value.len());
+            Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ])
+        };
+
+        // Only worth shouting about once we've actually looked at the interface
+        let local_info_arrived = !local_info.local_ips.is_empty() || local_info.mac_address.is_some();
+        let down = local_info_arrived && !local_info.is_up;
+
+        let mut header = Vec::new();
+
+        if let Some(description) = self.chosen_interface.as_deref().and_then(netlib::describe_interface) {
+            header.push(Span::styled(description, Style::default().fg(Color::DarkGray)));
+        }
+
+        if down {
+            if !header.is_empty() {
+                header.push(Span::raw(" "));
+            }
+            header.push(Span::styled("[DOWN]", Style::default().bold().fg(Color::Red)));
+        }
+
+        if !header.is_empty() {
+            text.push(Line::from(header));
+        }
+
+        match &local_info.mac_address {
+            Some(mac_address) => text.push(row("MAC: ", mac_address.clone(), Color::Green)),
+            // Loopback and most tunnels have no hardware address at all
+            None => text.push(row("MAC: ", "Unknown".to_string(), Color::Red)),
+        }
+
+        match &local_info.gateway {
+            Some(gateway) => {
+                let colour = match local_info.gateway_reachable {
+                    Some(false) => Color::Red,
+                    _ => Color::Green,
+                };
+
+                text.push(row("Gateway: ", gateway.clone(), colour));
+
+                // Not pinged (yet) leaves the line out, otherwise it's how long the gateway took to answer
+                match (local_info.gateway_reachable, local_info.gateway_latency_ms) {
+                    (Some(true), Some(latency)) => {
+                        let colour = if latency < 5.0 {
+                            Color::Green
+                        } else if latency < 20.0 {
+                            Color::Yellow
+                        } else {
+                            Color::Red
+                        };
+
+                        text.push(row("Gateway ping: ", format!("{:.1}ms", latency), self.anomaly_colour("local_info", "gateway_latency_ms", colour)));
+                    }
+                    (Some(false), _) => text.push(row("Gateway ping: ", "No reply".to_string(), Color::Red)),
+                    _ => {}
+                }
+            }
+            None => text.push(row("Gateway: ", "Unknown".to_string(), Color::Red)),
+        }
+
+        match local_info.mtu {
+            // Below Ethernet's 1500 usually means a tunnel or PPPoE somewhere, worth a second look
+            Some(mtu) => text.push(row("MTU: ", mtu.to_string(), if mtu >= 1500 { Color::Green } else { Color::Yellow })),
+            None => text.push(row("MTU: ", "Unknown".to_string(), Color::Red)),
+        }
+
+        match local_info.ipv6_pmtud_working {
+            Some(true) => text.push(row("IPv6 PMTUD: ", "OK".to_string(), Color::Green)),
+            // Large transfers over IPv6 will stall
+            Some(false) => text.push(row("IPv6 PMTUD: ", "Blocked".to_string(), Color::Red)),
+            None => {}
+        }
+
+        let local_ips = &local_info.local_ips;
+
+        if local_ips.is_empty() {
+            text.push(row("Local IP: ", "No IP assigned".to_string(), Color::Yellow));
+        }
+
+        // Split by family so a dual-stack machine missing one of them stands out. Only one address of
+        // each is shown, preferring a global IPv6 address over the link-local one every interface has
+        for (label, is_ipv4) in [("IPv4: ", true), ("IPv6: ", false)] {
+            if local_ips.is_empty() {
+                break;
+            }
+
+            let family: Vec<&(std::net::IpAddr, u8)> = local_ips.iter().filter(|(ip, _)| ip.is_ipv4() == is_ipv4).collect();
+
+            let preferred = family.iter()
+                .find(|(ip, _)| ip.is_ipv4() || fetch_local::is_global_ipv6(ip))
+                .or(family.first());
+
+            let (ip, prefix) = match preferred {
+                Some(address) => address,
+                None => {
+                    text.push(row(label, "Unknown".to_string(), Color::Red));
+                    continue;
+                }
+            };
+
+            let more = match family.len() - 1 {
+                0 => String::new(),
+                others => format!(" +{} more", others),
+            };
+
+            let address = netlib::fit_width(&format!("{}/{}", ip, prefix), max_width.saturating_sub(label.len() + more.len()));
+            let padding = max_width.saturating_sub(label.len() + address.chars().count() + more.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(address, Style::default().fg(Color::Green)),
+                Span::styled(more, Style::default().fg(Color::DarkGray)),
+            ]));
+        }
+
+        let title = Span::styled("Network Info", Style::default().add_modifier(Modifier::BOLD));
+    
+        Paragraph::new(Text::from(text))
+            .wrap(Wrap { trim: true })
+            .block(Block::default().title(title).borders(Borders::ALL))
+    }
+
+    fn render_internet_info(&self, _area: Rect) -> Paragraph<'_> {
+        let internet_info = &self.network_info.internet_info;
+
+        if let Some(ref error) = internet_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .wrap(Wrap { trim: true })
+                .block(Block::default().title("Internet Info").borders(Borders::ALL));
+        }
+
+        match internet_info.reachable {
+            None => {
+                return Paragraph::new(Text::from(vec![Line::from("Fetching public IP...")]))
+                    .block(Block::default().title("Internet Info").borders(Borders::ALL));
+            }
+            Some(false) => {
+                return Paragraph::new(Text::from(vec![Line::from("No internet access")]).bold().red())
+                    .block(Block::default().title("Internet Info").borders(Borders::ALL));
+            }
+            Some(true) => {}
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let fields = [
+            ("Public IP: ", internet_info.public_ip.clone()),
+            ("ASN: ", internet_info.asn.map(|asn| format!("AS{}", asn))),
+            ("Reverse DNS: ", internet_info.reverse_dns.clone()),
+            ("ISP: ", internet_info.isp.clone()),
+            ("Location: ", internet_info.location.clone()),
+        ];
+
+        let mut text = Vec::with_capacity(fields.len() + 1);
+
+        for (label, value) in fields {
+            let (value, colour) = match value {
+                Some(value) => (value, Color::Reset),
+                None if internet_info.lookup_complete => ("Unknown".to_string(), Color::Yellow),
+                None => ("Waiting".to_string(), Color::Yellow),
+            };
+
+            let padding = max_width.saturating_sub(label.len() + value.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
+        let (ping, colour) = match (internet_info.cloudflare_ping, internet_info.cloudflare_ping_method) {
+            (Some(ping), Some(method)) => (format!("{:.1}ms ({})", ping, method.name()), self.anomaly_colour("internet_info", "cloudflare_ping", Color::Reset)),
+            _ if internet_info.lookup_complete => ("N/A".to_string(), Color::Red),
+            _ => ("Waiting".to_string(), Color::Yellow),
+        };
+
+        let padding = max_width.saturating_sub("Cloudflare Ping: ".len() + ping.len());
+
+        text.push(Line::from(vec![
+            Span::styled("Cloudflare Ping: ", Style::default().bold()),
+            Span::raw(" ".repeat(padding)),
+            Span::styled(ping, Style::default().fg(colour)),
+        ]));
+
+        // Only measured for tunnel interfaces, where the hops through the VPN servers are worth knowing
+        if let (Some(extra_hops), Some(ref compared_with)) = (internet_info.inferred_extra_hops, &internet_info.extra_hops_compared_with) {
+            let value = netlib::fit_width(&format!("{} (vs {})", extra_hops, compared_with), max_width.saturating_sub("Extra Hops: ".len()));
+            let padding = max_width.saturating_sub("Extra Hops: ".len() + value.chars().count());
+
+            text.push(Line::from(vec![
+                Span::styled("Extra Hops: ", Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::raw(value),
+            ]));
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("Internet Info").borders(Borders::ALL))
+    }
+
+    fn render_dhcp_info(&self, _area: Rect) -> Paragraph<'_> {
+        let dhcp_info = &self.network_info.dhcp_info;
+
+        if let Some(ref error) = dhcp_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("DHCP Info").borders(Borders::ALL));
+        }
+
+        if dhcp_info.dhcp_server.is_none() && dhcp_info.lease_time.is_none() && dhcp_info.last_renewed.is_none() {
+            return Paragraph::new(Text::from(vec![Line::from("Reading lease...")]))
+                .block(Block::default().title("DHCP Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        let fields = [
+            ("DHCP Server: ", dhcp_info.dhcp_server.clone()),
+            ("Lease Time: ", dhcp_info.lease_time.map(netlib::format_duration)),
+            ("Last Renewed: ", dhcp_info.last_renewed.map(|renewed| format!("{} ago", netlib::format_duration(now.saturating_sub(renewed))))),
+        ];
+
+        let mut text = Vec::with_capacity(fields.len() + 1);
+
+        for (label, value) in fields {
+            let (value, colour) = match value {
+                Some(value) => (value, Color::Reset),
+                None => ("Unknown".to_string(), Color::Yellow),
+            };
+
+            let padding = max_width.saturating_sub(label.len() + value.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
+        // Above the DNS servers, which can run off the bottom of the panel
+        if fetch_dhcp_info::possible_rogue_server(dhcp_info) {
+            text.push(Line::from("Possible rogue DHCP server").bold().red());
+        }
+
+        if let Some(ref dns_servers) = dhcp_info.dhcp_declared_dns {
+            text.push(Line::from(Span::styled("DNS: ", Style::default().bold())));
+
+            for server in dns_servers {
+                text.push(Line::from(format!("  {}", server)));
+            }
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("DHCP Info").borders(Borders::ALL))
+    }
+
+    fn render_dns_info(&self, _area: Rect) -> Paragraph<'_> {
+        if self.network_info.dns_info.can_fetch.is_none() {
+            return Paragraph::new(Text::from(vec![Line::from("Fetching list...")]))
+                .block(Block::default().title("DNS Info").borders(Borders::ALL));
+        }
+
+        if self.network_info.dns_info.can_bind_interface == Some(false) {
+            return Paragraph::new(Text::from(vec![Line::from("Can't bind to interface.")]).bold().red())
+                .block(Block::default().title("DNS Info").borders(Borders::ALL));
+        }
+
+        if self.network_info.dns_info.can_fetch == Some(false) {
+            return Paragraph::new(Text::from(vec![Line::from("Failed to get list.")]).bold().red())
+                .block(Block::default().title("DNS Info").borders(Borders::ALL));
+        }
+
+        let mut text = Vec::new();
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        if let Some(ref hosts_override) = self.network_info.dns_info.local_hosts_override {
+            text.push(Line::from(format!("Hosts file override: {}", hosts_override)).yellow());
+        }
+
+        if self.network_info.dns_info.dns_servers.is_empty() {
+            text.push(Line::from("No DNS servers found."));
+        } else {
+            text.push(Line::from(vec![Span::styled("Servers:", Style::default().bold())]));
+
+            for server in &self.network_info.dns_info.dns_servers {
+                let colour = match server.can_resolve {
+                    _ if server.wrong_family => Color::DarkGray,
+                    Some(true) => Color::Green,
+                    Some(false) => Color::Red,
                     None => Color::Yellow,
                 };
 
-                let message = match server.can_resolve {
-                    Some(true) => "OK",
-                    Some(false) => "Failure",
-                    None => "Waiting",
-                };
+                let (message, message_colour) = match (server.can_resolve, server.latency_ms) {
+                    _ if server.wrong_family => ("N/A".to_string(), colour),
+                    (Some(true), Some(latency)) if latency < 50.0 => (format!("{:.0}ms", latency), Color::Green),
+                    (Some(true), Some(latency)) if latency < 200.0 => (format!("{:.0}ms", latency), Color::Yellow),
+                    (Some(true), Some(latency)) => (format!("{:.0}ms", latency), Color::Red),
+                    (Some(true), None) => ("OK".to_string(), colour),
+                    (Some(false), _) => ("Fail".to_string(), colour),
+                    (None, _) => ("Waiting".to_string(), colour),
+                };
+
+                let message_colour = self.anomaly_colour("dns_info", &format!("dns_servers[{}].latency_ms", server.ip), message_colour);
+
+                let sources = format!(
+                    " ({})",
+                    server.sources.iter().map(|source| source.short_name()).collect::<Vec<&str>>().join(",")
+                );
+
+                let padding = max_width.saturating_sub(server.ip.len() + sources.len() + message.len());
+
+                text.push(Line::from(vec![
+                    Span::styled(server.ip.clone(), Style::default().fg(colour)),
+                    Span::styled(sources, Style::default().fg(Color::DarkGray)),
+                    Span::styled(format!("{}{}", " ".repeat(padding), message), Style::default().fg(message_colour)),
+                ]));
+
+                let record_types = [("A", server.resolves_a), ("AAAA", server.resolves_aaaa), ("MX", server.resolves_mx)];
+
+                if record_types.iter().any(|(_, resolves)| resolves.is_some()) {
+                    let mut spans = vec![Span::styled("  [", Style::default().fg(Color::DarkGray))];
+
+                    for (i, (name, resolves)) in record_types.iter().enumerate() {
+                        let (mark, mark_colour) = match resolves {
+                            Some(true) => ("✓", Color::Green),
+                            Some(false) => ("✗", Color::Red),
+                            None => ("?", Color::Yellow),
+                        };
+
+                        if i > 0 {
+                            spans.push(Span::raw(" "));
+                        }
+
+                        spans.push(Span::styled(format!("{}{}", name, mark), Style::default().fg(mark_colour)));
+                    }
+
+                    spans.push(Span::styled("]", Style::default().fg(Color::DarkGray)));
+
+                    if server.used_tcp_fallback == Some(true) {
+                        spans.push(Span::styled(" via TCP", Style::default().fg(Color::DarkGray)));
+                    }
+                    text.push(Line::from(spans));
+                }
+            }
+        }
+
+        let (doq_colour, doq_message) = match self.network_info.dns_info.doq_supported {
+            Some(true) => (Color::Green, "OK"),
+            Some(false) => (Color::Red, "Failure"),
+            None => (Color::Yellow, "Waiting"),
+        };
+
+        // Only name the resolver if there's room for it next to the result
+        let mut doq_server = format!(" ({})", self.network_info.dns_info.doq_server_tested);
+        if "DoQ".len() + doq_server.len() + 1 + doq_message.len() > max_width {
+            doq_server.clear();
+        }
+
+        let padding = max_width.saturating_sub("DoQ".len() + doq_server.len() + doq_message.len());
+
+        text.push(Line::from(vec![
+            Span::styled("DoQ", Style::default().bold()),
+            Span::styled(doq_server, Style::default().fg(Color::DarkGray)),
+            Span::raw(" ".repeat(padding)),
+            Span::styled(doq_message, Style::default().fg(doq_colour)),
+        ]));
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("DNS Info").borders(Borders::ALL))
+    }
+
+    fn render_traceroute_info(&self, _area: Rect) -> Paragraph<'_> {
+        let traceroute = &self.network_info.traceroute;
+
+        if !self.capabilities.raw_socket {
+            return Paragraph::new(Text::from(self.privileges_required_message()))
+                .block(Block::default().title("Traceroute Info").borders(Borders::ALL));
+        }
+
+        if let Some(ref error) = traceroute.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("Traceroute Info").borders(Borders::ALL));
+        }
+
+        let target = match traceroute.target {
+            Some(ref target) => target,
+            None => {
+                return Paragraph::new(Text::from(vec![Line::from("Starting traceroute...")]))
+                    .block(Block::default().title("Traceroute Info").borders(Borders::ALL));
+            }
+        };
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let mut text = Vec::with_capacity(traceroute.hops.len() + 2);
+
+        text.push(Line::from(vec![
+            Span::styled("Target: ", Style::default().bold()),
+            Span::raw(" ".repeat(max_width.saturating_sub("Target: ".len() + target.len()))),
+            Span::raw(target.clone()),
+        ]));
+
+        // The hop where the path MTU first drops is the bottleneck link
+        let interface_mtu = self.network_info.local_info.mtu;
+        let mut previous_mtu = interface_mtu;
+
+        for hop in &traceroute.hops {
+            let label = format!("{:>2} {}", hop.hop_number, hop.ip);
+
+            if hop.latency < 0.0 {
+                text.push(Line::from(Span::styled(label, Style::default().fg(Color::DarkGray))));
+                continue;
+            }
+
+            let mut value = format!("{:.1}ms", hop.latency);
+
+            // The rest is added in order of importance while there's room, the address matters more
+            // when space is short. A label stack shows its top label and traffic class
+            let mut extras = Vec::new();
+            let mut bottleneck = false;
+
+            // Where the traffic is handed between networks is the most telling, so it goes first
+            if let Some(ref ixp) = hop.ixp {
+                extras.push(format!(" [{}]", ixp));
+            }
+
+            if let Some(link_mtu) = hop.link_mtu {
+                bottleneck = previous_mtu.is_some_and(|previous_mtu| (link_mtu as u32) < previous_mtu);
+                previous_mtu = Some(link_mtu as u32);
+
+                if interface_mtu != Some(link_mtu as u32) {
+                    extras.push(format!(" MTU {}", link_mtu));
+                }
+            }
+
+            if let Some(top) = hop.mpls_labels.first() {
+                extras.push(format!(" [MPLS {}/{}]", top.label, top.tc));
+            }
+
+            extras.push(format!(" ±{:.1}", hop.jitter));
+
+            for extra in extras {
+                if label.len() + value.chars().count() + extra.chars().count() < max_width {
+                    value.push_str(&extra);
+                }
+            }
+
+            let padding = max_width.saturating_sub(label.len() + value.chars().count());
+
+            let style = match bottleneck {
+                true => Style::default().fg(Color::Yellow),
+                false => Style::default(),
+            };
+
+            text.push(Line::from(vec![
+                Span::styled(label, style),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, style),
+            ]));
+        }
+
+        if !traceroute.complete {
+            text.push(Line::from("Tracing...").yellow());
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("Traceroute Info").borders(Borders::ALL))
+    }
+
+    fn render_packet_loss_info(&self, _area: Rect) -> Paragraph<'_> {
+        let packet_loss_info = &self.network_info.packet_loss_info;
+
+        if !self.capabilities.raw_socket {
+            return Paragraph::new(Text::from(self.privileges_required_message()))
+                .block(Block::default().title("Packet Loss").borders(Borders::ALL));
+        }
+
+        if let Some(ref error) = packet_loss_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("Packet Loss").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let lost = packet_loss_info.sent - packet_loss_info.received;
+        let loss_percent = match packet_loss_info.sent {
+            0 => 0.0,
+            sent => lost as f64 / sent as f64 * 100.0,
+        };
+
+        let waiting = || match packet_loss_info.complete {
+            true => ("N/A".to_string(), Color::Red),
+            false => ("Waiting".to_string(), Color::Yellow),
+        };
+
+        let fields = [
+            ("Target: ", match packet_loss_info.target {
+                Some(ref target) => (target.clone(), Color::Reset),
+                None => ("Waiting".to_string(), Color::Yellow),
+            }),
+            ("Replies: ", (format!("{}/{}", packet_loss_info.received, packet_loss_info.sent), Color::Reset)),
+            // A couple of percent is already enough to be heard on a call
+            ("Loss: ", match packet_loss_info.complete {
+                true if lost == 0 => ("0.0%".to_string(), Color::Green),
+                true if loss_percent < 2.0 => (format!("{:.1}%", loss_percent), self.anomaly_colour("packet_loss_info", "loss_percent", Color::Yellow)),
+                true => (format!("{:.1}%", loss_percent), self.anomaly_colour("packet_loss_info", "loss_percent", Color::Red)),
+                false => waiting(),
+            }),
+            ("Longest Burst: ", match packet_loss_info.complete {
+                true => (packet_loss_info.longest_burst.to_string(), Color::Reset),
+                false => waiting(),
+            }),
+            ("Pattern: ", match packet_loss_info.loss_pattern {
+                Some(LossPattern::None) => ("No loss".to_string(), Color::Green),
+                Some(LossPattern::Random) => ("Random".to_string(), Color::Yellow),
+                Some(LossPattern::Bursty(average)) => (format!("Bursty (~{} in a row)", average), Color::Red),
+                Some(LossPattern::RateLimited) => ("Rate limited".to_string(), Color::Yellow),
+                None => waiting(),
+            }),
+        ];
+
+        let mut text = Vec::with_capacity(fields.len() + 1);
+
+        for (label, (value, colour)) in fields {
+            let value = netlib::fit_width(&value, max_width.saturating_sub(label.len()));
+            let padding = max_width.saturating_sub(label.len() + value.chars().count());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
+        // Rate limiting is usually the target going easy on ICMP rather than the network losing anything
+        if packet_loss_info.loss_pattern == Some(LossPattern::RateLimited) {
+            text.push(Line::from("Likely ICMP rate limiting").dark_gray());
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("Packet Loss").borders(Borders::ALL))
+    }
+
+    fn render_bgp_path_info(&self, _area: Rect) -> Paragraph<'_> {
+        let bgp_path_info = &self.network_info.bgp_path_info;
+        let internet_info = &self.network_info.internet_info;
+
+        if let Some(ref error) = bgp_path_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("BGP Path").borders(Borders::ALL));
+        }
+
+        // The lookup is done by the internet check once it has the public IP
+        if !bgp_path_info.complete {
+            let message = match internet_info.lookup_complete || internet_info.reachable == Some(false) || internet_info.error.is_some() {
+                true => Line::from("Needs a public IP").dark_gray(),
+                false => Line::from("Looking up BGP path..."),
+            };
+
+            return Paragraph::new(Text::from(vec![message]))
+                .block(Block::default().title("BGP Path").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let fields = [
+            ("Prefix: ", bgp_path_info.prefix.clone().unwrap_or_default(), Color::Reset),
+            // ipinfo.io going by a different ASN than the routing table is worth a second look
+            ("Origin: ", bgp_path_info.origin_asn.map(|asn| format!("AS{}", asn)).unwrap_or_default(), match (bgp_path_info.origin_asn, internet_info.asn) {
+                (Some(origin), Some(asn)) if origin != asn => Color::Yellow,
+                _ => Color::Reset,
+            }),
+            ("Path Length: ", bgp_path_info.as_path.len().to_string(), Color::Reset),
+        ];
+
+        let mut text = Vec::with_capacity(8);
+
+        for (label, value, colour) in fields {
+            let value = netlib::fit_width(&value, max_width.saturating_sub(label.len()));
+            let padding = max_width.saturating_sub(label.len() + value.chars().count());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
+        text.push(Line::from(Span::styled("Path:", Style::default().bold())));
+
+        // The path wraps across the remaining lines, from the collector's side to the origin
+        let mut line = String::new();
+
+        for asn in &bgp_path_info.as_path {
+            let asn = format!("AS{}", asn);
+
+            if !line.is_empty() && line.chars().count() + 1 + asn.len() > max_width {
+                text.push(Line::from(std::mem::take(&mut line)));
+            }
+
+            if !line.is_empty() {
+                line.push(' ');
+            }
+
+            line.push_str(&asn);
+        }
+
+        if !line.is_empty() {
+            text.push(Line::from(line));
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("BGP Path").borders(Borders::ALL))
+    }
+
+    fn render_port_forward_info(&self, _area: Rect) -> Option<Paragraph<'_>> {
+        let port_forward_info = self.network_info.port_forward_info.as_ref()?;
+        let internet_info = &self.network_info.internet_info;
+
+        if let Some(ref error) = port_forward_info.error {
+            return Some(Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("Port Forward").borders(Borders::ALL)));
+        }
+
+        // Like the BGP path, this waits on the internet check for the public IP
+        if !port_forward_info.complete {
+            let message = match internet_info.lookup_complete || internet_info.reachable == Some(false) || internet_info.error.is_some() {
+                true => Line::from("Needs a public IP").dark_gray(),
+                false => Line::from("Checking from outside..."),
+            };
+
+            return Some(Paragraph::new(Text::from(vec![message]))
+                .block(Block::default().title("Port Forward").borders(Borders::ALL)));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let (reachable, reachable_colour) = match port_forward_info.externally_reachable {
+            Some(true) => ("Reachable", Color::Green),
+            Some(false) => ("Not reachable", Color::Red),
+            None => ("Unknown", Color::Reset),
+        };
+
+        let fields = [
+            ("Port: ", port_forward_info.port.to_string(), Color::Reset),
+            ("Public IP: ", internet_info.public_ip.clone().unwrap_or_default(), Color::Reset),
+            ("From Outside: ", reachable.to_string(), reachable_colour),
+        ];
+
+        let mut text = Vec::with_capacity(8);
+
+        for (label, value, colour) in fields {
+            let value = netlib::fit_width(&value, max_width.saturating_sub(label.len()));
+            let padding = max_width.saturating_sub(label.len() + value.chars().count());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
+        // The usual suspects, in the order they tend to be the problem
+        if port_forward_info.externally_reachable == Some(false) {
+            for hint in ["Check the forward points at", "this device, that something", "is listening on the port and", "the firewall allows it. ISPs", "using CGNAT can't forward."] {
+                text.push(Line::from(netlib::fit_width(hint, max_width)).dark_gray());
+            }
+        }
+
+        Some(Paragraph::new(Text::from(text))
+            .block(Block::default().title("Port Forward").borders(Borders::ALL)))
+    }
+
+    fn render_tcp_info(&self, _area: Rect) -> Paragraph<'_> {
+        let tcp_info = &self.network_info.tcp_info;
+
+        if let Some(ref error) = tcp_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("TCP Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let mut text = Vec::with_capacity(tcp_info.attempted_to_talk_on_list.len() + 1);
+
+        for (port, success) in &tcp_info.attempted_to_talk_on_list {
+            let label = format!("Port {}: ", port);
+
+            let (colour, message) = match success {
+                true => (Color::Green, "Success"),
+                false => (Color::Red, "Failure"),
+            };
+
+            let padding = max_width.saturating_sub(label.len() + message.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(message, Style::default().fg(colour)),
+            ]));
+        }
+
+        if !tcp_info.complete {
+            text.push(Line::from("Testing ports...").yellow());
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("TCP Info").borders(Borders::ALL))
+    }
 
-                let padding = max_width.saturating_sub(server.ip.len() + message.len());
+    fn render_http_info(&self, _area: Rect) -> Paragraph<'_> {
+        let http_info = &self.network_info.http_info;
 
-                text.push(Line::from(vec![
-                    Span::styled(format!("{}{}{}", server.ip.to_string(), " ".repeat(padding), message), Style::default().fg(colour)),
-                ]));
-            }
+        if let Some(ref error) = http_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("HTTP Info").borders(Borders::ALL));
+        }
+
+        if !http_info.complete {
+            return Paragraph::new(Text::from(vec![Line::from("Testing HTTP...")]))
+                .block(Block::default().title("HTTP Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let reachability = |reachable: Option<bool>| match reachable {
+            Some(true) => (Color::Green, "OK"),
+            Some(false) => (Color::Yellow, "HTTP Error"),
+            None => (Color::Red, "No Connection"),
+        };
+
+        let fields = [
+            ("1.1.1.1: ", reachability(http_info.can_access_1111)),
+            ("Google: ", reachability(http_info.can_access_google)),
+            ("Captive Portal: ", match http_info.captive_portal {
+                Some(true) => (Color::Red, "Detected"),
+                Some(false) => (Color::Green, "None"),
+                None => (Color::Yellow, "Unknown"),
+            }),
+        ];
+
+        let mut text = Vec::with_capacity(fields.len() + 2);
+
+        for (label, (colour, message)) in fields {
+            let padding = max_width.saturating_sub(label.len() + message.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(message, Style::default().fg(colour)),
+            ]));
+        }
+
+        // Cut short to fit on one line, the start of the address is the part worth knowing
+        let fit = |value: &str| netlib::fit_width(value, max_width);
+
+        if let Some(ref title) = http_info.captive_portal_title {
+            text.push(Line::from(Span::styled(fit(title), Style::default().bold())));
+        }
+
+        if let Some(ref url) = http_info.captive_portal_redirect_url {
+            text.push(Line::from(Span::styled(fit(url), Style::default().fg(Color::DarkGray))));
         }
 
         Paragraph::new(Text::from(text))
-            .block(Block::default().title("DNS Info").borders(Borders::ALL))
+            .block(Block::default().title("HTTP Info").borders(Borders::ALL))
     }
 
-    fn render_traceroute_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("Hop 1: 192.168.0.1 - Latency: 1ms"),
-            Line::from("Hop 2: 203.0.113.1 - Latency: 10ms"),
-            Line::from("Hop 3: 198.51.100.1 - Latency: 20ms"),
+    fn render_https_info(&self, _area: Rect) -> Paragraph<'_> {
+        let https_info = &self.network_info.https_info;
+
+        if let Some(ref error) = https_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("HTTPS Info").borders(Borders::ALL));
+        }
+
+        if !https_info.complete {
+            return Paragraph::new(Text::from(vec![Line::from("Testing HTTPS...")]))
+                .block(Block::default().title("HTTPS Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let reachability = |valid: Option<bool>| match valid {
+            Some(true) => (Color::Green, "OK".to_string()),
+            Some(false) => (Color::Red, "Invalid Cert".to_string()),
+            None => (Color::Red, "No Connection".to_string()),
+        };
+
+        // The version is from the 1.1.1.1 handshake, so it goes alongside that rather than on its own row
+        let mut cloudflare = reachability(https_info.can_access_1111);
+
+        if let Some(ref tls_version) = https_info.tls_version {
+            cloudflare.1 = format!("{}, {}", cloudflare.1, tls_version);
+        }
+
+        let hsts_days = |max_age: u64| format!("{}d", max_age / (24 * 60 * 60));
+
+        let statuses = [
+            ("1.1.1.1: ", cloudflare),
+            ("Google: ", reachability(https_info.can_access_google)),
+            ("MITM: ", match https_info.mitm_detected {
+                Some(true) => (Color::Red, "Detected".to_string()),
+                Some(false) => (Color::Green, "None".to_string()),
+                None => (Color::Yellow, "Unknown".to_string()),
+            }),
+            // Preloaded means browsers only ever use HTTPS for it, even with a bad certificate
+            ("HSTS: ", match (&https_info.hsts_host, https_info.hsts_max_age) {
+                (None, _) => (Color::Yellow, "Unknown".to_string()),
+                (Some(_), None) => (Color::Yellow, "Not configured".to_string()),
+                (Some(_), Some(max_age)) if max_age < fetch_https::HSTS_MIN_MAX_AGE => (Color::Yellow, format!("{}, too short", hsts_days(max_age))),
+                (Some(_), Some(max_age)) if https_info.hsts_preload_listed == Some(true) => (Color::Green, format!("{}, preloaded", hsts_days(max_age))),
+                (Some(_), Some(max_age)) => (Color::Green, hsts_days(max_age)),
+            }),
+            ("Headers: ", match https_info.security_score {
+                None => (Color::Yellow, "Unknown".to_string()),
+                Some(score) if https_info.missing_headers.is_empty() => (Color::Green, format!("{}/100", score)),
+                Some(score) => {
+                    let missing: Vec<&str> = https_info.missing_headers.iter().map(|header| fetch_https::security_header_abbreviation(header)).collect();
+                    let colour = if score >= 60 { Color::Yellow } else { Color::Red };
+
+                    (colour, format!("{}, no {}", score, missing.join(" ")))
+                }
+            }),
         ];
-        Paragraph::new(Text::from(text)).block(
-            Block::default()
-                .title("Traceroute Info")
-                .borders(Borders::ALL),
-        )
-    }
-
-    fn render_tcp_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("Port 80: Success"),
-            Line::from("Port 443: Success"),
-            Line::from("Port 8080: Fail"),
+
+        let mut text = Vec::with_capacity(8);
+
+        for (label, (colour, message)) in statuses {
+            let message = netlib::fit_width(&message, max_width.saturating_sub(label.len()));
+            let padding = max_width.saturating_sub(label.len() + message.chars().count());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(message, Style::default().fg(colour)),
+            ]));
+        }
+
+        // The certificate fills whatever room is left, most telling first. An intercepting proxy's
+        // own CA shows up as the issuer, while the subject is 1.1.1.1's either way
+        let details = [
+            ("Issuer: ", &https_info.cert_issuer),
+            ("SHA-256: ", &https_info.cert_fingerprint_seen.as_deref().map(netlib::short_fingerprint)),
+            ("Expires: ", &https_info.cert_expires),
+            ("Subject: ", &https_info.cert_subject),
         ];
+
+        for (label, value) in details {
+            if text.len() >= (BLOCK_HEIGHT - 2) as usize {
+                break;
+            }
+
+            let value = match value {
+                Some(value) => value.clone(),
+                None => continue,
+            };
+
+            // Long names get cut off rather than wrapping onto the next field
+            let value: String = value.chars().take(max_width.saturating_sub(label.len())).collect();
+            let padding = max_width.saturating_sub(label.len() + value.chars().count());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::raw(value),
+            ]));
+        }
+
         Paragraph::new(Text::from(text))
-            .block(Block::default().title("TCP Info").borders(Borders::ALL))
+            .block(Block::default().title("HTTPS Info").borders(Borders::ALL))
+    }
+
+    fn render_udp_info(&self, _area: Rect) -> Paragraph<'_> {
+        let udp_info = &self.network_info.udp_info;
+
+        if let Some(ref error) = udp_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("UDP Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let mut text = Vec::with_capacity(udp_info.attempted_to_talk_on_list.len() + 1);
+
+        for (port, state) in &udp_info.attempted_to_talk_on_list {
+            let label = format!("Port {}: ", port);
+
+            let (colour, message) = match state {
+                UdpPortState::Responded => (Color::Green, "Success"),
+                UdpPortState::Closed => (Color::Red, "Closed"),
+                UdpPortState::NoReply => (Color::Yellow, "?"),
+            };
+
+            let padding = max_width.saturating_sub(label.len() + message.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(message, Style::default().fg(colour)),
+            ]));
+        }
+
+        if !udp_info.complete {
+            text.push(Line::from("Waiting for replies...").yellow());
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("UDP Info").borders(Borders::ALL))
     }
 
-    fn render_http_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("Can Access 1.1.1.1: Yes"),
-            Line::from("Can Access Google: Yes"),
+    fn render_ntp_info(&self, _area: Rect) -> Paragraph<'_> {
+        let ntp_info = &self.network_info.ntp_info;
+
+        if let Some(ref error) = ntp_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("NTP Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let waiting = || match ntp_info.complete {
+            true => ("N/A".to_string(), Color::Red),
+            false => ("Waiting".to_string(), Color::Yellow),
+        };
+
+        let fields = [
+            ("Server: ", match ntp_info.ntp_server {
+                Some(ref server) => (server.clone(), Color::Reset),
+                None => ("Waiting".to_string(), Color::Yellow),
+            }),
+            ("Configured: ", match ntp_info.do_use_ntp {
+                Some(true) => ("Yes".to_string(), Color::Green),
+                Some(false) => ("No".to_string(), Color::Yellow),
+                None => ("Waiting".to_string(), Color::Yellow),
+            }),
+            ("Reachable: ", match ntp_info.can_access_ntp {
+                Some(true) => ("Yes".to_string(), Color::Green),
+                Some(false) => ("No".to_string(), Color::Red),
+                None => ("Waiting".to_string(), Color::Yellow),
+            }),
+            ("Clock Offset: ", match ntp_info.clock_offset_ms {
+                Some(offset) if offset.abs() > 1000.0 => (format!("{:+.1}ms", offset), Color::Red),
+                Some(offset) if offset.abs() > 500.0 => (format!("{:+.1}ms", offset), Color::Yellow),
+                Some(offset) => (format!("{:+.1}ms", offset), Color::Green),
+                None => waiting(),
+            }),
+            ("Round Trip: ", match ntp_info.round_trip_delay_ms {
+                Some(delay) => (format!("{:.1}ms", delay), self.anomaly_colour("ntp_info", "round_trip_delay_ms", Color::Reset)),
+                None => waiting(),
+            }),
         ];
+
+        let mut text = Vec::with_capacity(fields.len());
+
+        for (label, (value, colour)) in fields {
+            let padding = max_width.saturating_sub(label.len() + value.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
         Paragraph::new(Text::from(text))
-            .block(Block::default().title("HTTP Info").borders(Borders::ALL))
+            .block(Block::default().title("NTP Info").borders(Borders::ALL))
     }
 
-    fn render_https_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("Can Access 1.1.1.1: Yes"),
-            Line::from("Can Access Google: Yes"),
+    fn render_quic_info(&self, _area: Rect) -> Paragraph<'_> {
+        let quic_info = &self.network_info.quic_info;
+
+        if let Some(ref error) = quic_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("QUIC Info").borders(Borders::ALL));
+        }
+
+        if !quic_info.complete {
+            return Paragraph::new(Text::from(vec![Line::from("Testing QUIC...")]))
+                .block(Block::default().title("QUIC Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let reachability = |reachable: Option<bool>| match reachable {
+            Some(true) => ("OK".to_string(), Color::Green),
+            Some(false) => ("Blocked".to_string(), Color::Red),
+            None => ("Untested".to_string(), Color::Yellow),
+        };
+
+        let fields = [
+            ("1.1.1.1: ", reachability(quic_info.can_access_1111)),
+            // Only tried once the handshake has worked, so a failure here is the application layer
+            ("1.1.1.1 HTTP/3: ", match quic_info.http3_supported {
+                Some(true) => ("OK".to_string(), Color::Green),
+                Some(false) => ("Failed".to_string(), Color::Red),
+                None => ("Untested".to_string(), Color::Yellow),
+            }),
+            ("Google: ", reachability(quic_info.can_access_google)),
+            ("RTT: ", match quic_info.quic_rtt_ms {
+                Some(rtt) => (format!("{:.1}ms", rtt), self.anomaly_colour("quic_info", "quic_rtt_ms", Color::Reset)),
+                None => ("N/A".to_string(), Color::Yellow),
+            }),
         ];
+
+        let mut text = Vec::with_capacity(fields.len() + 1);
+
+        for (label, (value, colour)) in fields {
+            let padding = max_width.saturating_sub(label.len() + value.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
+        // Explains why HTTP/3 capable apps are falling back to HTTP/2 here
+        if quic_info.quic_port_blocked == Some(true) {
+            text.push(Line::from("QUIC blocked by firewall (TCP/443 works)").bold().red());
+        }
+
         Paragraph::new(Text::from(text))
-            .block(Block::default().title("HTTPS Info").borders(Borders::ALL))
+            .wrap(Wrap { trim: true })
+            .block(Block::default().title("QUIC Info").borders(Borders::ALL))
     }
 
-    fn render_udp_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("Port 53: Success"),
-            Line::from("Port 123: Success"),
+    /// IPv6 addressing and whether the IPv6 internet can be reached, independently of IPv4
+    fn render_ipv6_info(&self, _area: Rect) -> Paragraph<'_> {
+        let ipv6_info = &self.network_info.ipv6_info;
+        let local_info = &self.network_info.local_info;
+
+        if let Some(ref error) = ipv6_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("IPv6 Info").borders(Borders::ALL));
+        }
+
+        if !ipv6_info.complete {
+            return Paragraph::new(Text::from(vec![Line::from("Testing IPv6...")]))
+                .block(Block::default().title("IPv6 Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let address = |address: Option<std::net::IpAddr>| match address {
+            Some(address) => (address.to_string(), Color::Reset),
+            None => ("None".to_string(), Color::Red),
+        };
+
+        let ping = |latency: Option<f64>| match latency {
+            Some(latency) => (format!("{:.1}ms", latency), Color::Green),
+            None => ("No reply".to_string(), Color::Red),
+        };
+
+        let mut fields = vec![
+            ("Global: ".to_string(), address(local_info.ipv6_global)),
+            ("Link-local: ".to_string(), address(local_info.ipv6_link_local)),
+            ("Gateway: ".to_string(), match ipv6_info.ipv6_gateway {
+                // Red when it didn't answer its ping, like the IPv4 gateway
+                Some(ref gateway) if ipv6_info.gateway_reachable == Some(false) => (gateway.clone(), Color::Red),
+                Some(ref gateway) => (gateway.clone(), Color::Reset),
+                None => ("None".to_string(), Color::Red),
+            }),
         ];
+
+        // Only there when a raw socket and a global address meant the pings could be sent
+        if ipv6_info.gateway_reachable.is_some() {
+            fields.push(("Gateway ping: ".to_string(), ping(ipv6_info.gateway_ping_ms)));
+        }
+
+        for (name, target) in fetch_ipv6_info::IPV6_PING_TARGETS {
+            let result = ipv6_info.ping_results.iter().find(|(ip, _)| *ip == target.to_string());
+
+            if let Some((_, latency)) = result {
+                fields.push((format!("{}: ", name), ping(*latency)));
+            }
+        }
+
+        let mut text = Vec::with_capacity(fields.len() + 1);
+
+        for (label, (value, colour)) in fields {
+            // A global address is usually too long for the panel, its prefix is the part worth seeing
+            let value = netlib::fit_width(&value, max_width.saturating_sub(label.len()));
+            let padding = max_width.saturating_sub(label.len() + value.chars().count());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
+        if ipv6_info.has_global_ipv6 == Some(false) {
+            text.push(Line::from("IPv6 not configured").yellow());
+        } else if !self.capabilities.raw_socket {
+            // The pings are the only part that needs a raw socket, so the rest is still worth showing
+            text.push(Line::from(self.privileges_hint()).dark_gray());
+        } else if ipv6_info.can_reach_ipv6_internet == Some(false) && self.network_info.internet_info.reachable == Some(true) {
+            // A global address but no way out usually means the ISP or router filters IPv6
+            text.push(Line::from("IPv4 works, IPv6 filtered?").red());
+        }
+
         Paragraph::new(Text::from(text))
-            .block(Block::default().title("UDP Info").borders(Borders::ALL))
+            .block(Block::default().title("IPv6 Info").borders(Borders::ALL))
+    }
+
+    /// None for wired interfaces, which leaves the panel out of the grid
+    fn render_wifi_info(&self, _area: Rect) -> Option<Paragraph<'_>> {
+        let wifi_info = &self.network_info.wifi_info;
+
+        if !wifi_info.is_wireless {
+            return None;
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let mut text = Vec::new();
+
+        let ssid = match wifi_info.ssid {
+            Some(ref ssid) => (ssid.clone(), Color::Reset),
+            None => ("Not connected".to_string(), Color::Red),
+        };
+
+        let padding = max_width.saturating_sub("SSID: ".len() + ssid.0.chars().count());
+
+        text.push(Line::from(vec![
+            Span::styled("SSID: ", Style::default().bold()),
+            Span::raw(" ".repeat(padding)),
+            Span::styled(ssid.0, Style::default().fg(ssid.1)),
+        ]));
+
+        match wifi_info.signal_dbm {
+            Some(signal_dbm) => {
+                // Rough thresholds: above -55 dBm is excellent, below -85 dBm barely usable
+                let (bars, colour) = match signal_dbm {
+                    s if s >= -55 => (4, Color::Green),
+                    s if s >= -67 => (3, Color::Green),
+                    s if s >= -75 => (2, Color::Yellow),
+                    s if s >= -85 => (1, Color::Red),
+                    _ => (0, Color::Red),
+                };
+
+                let value = format!("{} dBm", signal_dbm);
+                // Four bar characters and a space before the reading
+                let padding = max_width.saturating_sub("Signal: ".len() + 5 + value.len());
+
+                let mut line = vec![
+                    Span::styled("Signal: ", Style::default().bold()),
+                    Span::raw(" ".repeat(padding)),
+                ];
+
+                for (i, bar) in ["▂", "▄", "▆", "█"].into_iter().enumerate() {
+                    let bar_colour = if i < bars { colour } else { Color::DarkGray };
+                    line.push(Span::styled(bar, Style::default().fg(bar_colour)));
+                }
+
+                line.push(Span::styled(format!(" {}", value), Style::default().fg(colour)));
+
+                text.push(Line::from(line));
+            }
+            None => {
+                let padding = max_width.saturating_sub("Signal: ".len() + "Unknown".len());
+
+                text.push(Line::from(vec![
+                    Span::styled("Signal: ", Style::default().bold()),
+                    Span::raw(" ".repeat(padding)),
+                    Span::styled("Unknown", Style::default().fg(Color::Red)),
+                ]));
+            }
+        }
+
+        let fields = [
+            ("Frequency: ", wifi_info.frequency_mhz.map(|freq| match freq {
+                f if f >= 5925 => format!("{} MHz (6 GHz)", f),
+                f if f >= 5000 => format!("{} MHz (5 GHz)", f),
+                f => format!("{} MHz (2.4 GHz)", f),
+            })),
+            ("Link speed: ", wifi_info.bitrate_mbps.map(|bitrate| format!("{:.1} Mbit/s", bitrate))),
+        ];
+
+        for (label, value) in fields {
+            let (value, colour) = match value {
+                Some(value) => (value, Color::Reset),
+                None => ("Unknown".to_string(), Color::Red),
+            };
+
+            let padding = max_width.saturating_sub(label.len() + value.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
+        Some(Paragraph::new(Text::from(text))
+            .block(Block::default().title("Wi-Fi Info").borders(Borders::ALL)))
     }
 
-    fn render_ntp_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("Use NTP: Yes"),
-            Line::from("NTP Server: 129.6.15.28"),
-            Line::from("Can Access NTP: Yes"),
-            Line::from("Local Time: 1627890123"),
-            Line::from("Server Time: 1627890124"),
+    fn render_network_stats_info(&self, _area: Rect) -> Paragraph<'_> {
+        let network_stats = &self.network_info.network_stats;
+
+        if let Some(ref error) = network_stats.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("Traffic Info").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let rate = |bps: Option<f64>, field: &str| match bps {
+            Some(bps) => (format!("{}/s", netlib::format_bytes(bps)), self.anomaly_colour("network_stats", field, Color::Green)),
+            None => ("Measuring...".to_string(), Color::Yellow),
+        };
+
+        let errors = network_stats.rx_errors + network_stats.tx_errors;
+
+        let fields = [
+            ("RX: ", rate(network_stats.rx_bps, "rx_bps")),
+            ("TX: ", rate(network_stats.tx_bps, "tx_bps")),
+            ("RX total: ", (netlib::format_bytes(network_stats.rx_bytes as f64), Color::Reset)),
+            ("TX total: ", (netlib::format_bytes(network_stats.tx_bytes as f64), Color::Reset)),
+            ("RX packets: ", (network_stats.rx_packets.to_string(), Color::Reset)),
+            ("TX packets: ", (network_stats.tx_packets.to_string(), Color::Reset)),
+            ("Errors: ", (format!("{} rx / {} tx", network_stats.rx_errors, network_stats.tx_errors), if errors > 0 { Color::Red } else { Color::Green })),
         ];
+
+        let mut text = Vec::with_capacity(fields.len());
+
+        for (label, (value, colour)) in fields {
+            let padding = max_width.saturating_sub(label.len() + value.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
         Paragraph::new(Text::from(text))
-            .block(Block::default().title("NTP Info").borders(Borders::ALL))
+            .block(Block::default().title("Traffic Info").borders(Borders::ALL))
+    }
+
+    /// The neighbour cache, with a tab each for IPv6 (NDP) and IPv4 (ARP) switched between with N
+    fn render_ndp_info(&self, _area: Rect) -> Paragraph<'_> {
+        let mut text = Vec::new();
+
+        let tab_style = |selected: bool| match selected {
+            true => Style::default().bold().reversed(),
+            false => Style::default().fg(Color::DarkGray),
+        };
+
+        text.push(Line::from(vec![
+            Span::styled(" IPv6 ", tab_style(!self.show_arp_entries)),
+            Span::raw(" "),
+            Span::styled(" IPv4 ", tab_style(self.show_arp_entries)),
+        ]));
+
+        let (entries, empty_message) = match self.show_arp_entries {
+            true => (&self.network_info.arp_entries, "No IPv4 neighbours."),
+            false => (&self.network_info.ndp_entries, "No IPv6 neighbours."),
+        };
+
+        if entries.is_empty() {
+            text.push(Line::from(empty_message));
+        }
+
+        for entry in entries {
+            // FAILED neighbours are unreachable, STALE ones haven't been verified recently
+            let colour = match entry.state.as_str() {
+                "REACHABLE" | "PERMANENT" | "NOARP" => Color::Green,
+                "FAILED" | "INCOMPLETE" => Color::Red,
+                _ => Color::Yellow,
+            };
+
+            let mac = if entry.mac.is_empty() { "no lladdr" } else { entry.mac.as_str() };
+
+            text.push(Line::from(Span::styled(entry.ip.clone(), Style::default().fg(colour))));
+            text.push(Line::from(vec![
+                Span::styled(format!("  {} ", mac), Style::default().fg(Color::DarkGray)),
+                Span::styled(entry.state.clone(), Style::default().fg(colour)),
+            ]));
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("Neighbours").borders(Borders::ALL))
     }
 
-    fn render_quic_info(&self, _area: Rect) -> Paragraph {
-        let text = vec![
-            Line::from("Can Access 1.1.1.1: Yes"),
-            Line::from("Can Access Google: Yes"),
+    fn render_lldp_info(&self, _area: Rect) -> Paragraph<'_> {
+        let lldp_info = &self.network_info.lldp_info;
+
+        if !self.capabilities.raw_socket {
+            return Paragraph::new(Text::from(self.privileges_required_message()))
+                .block(Block::default().title("LLDP Neighbour").borders(Borders::ALL));
+        }
+
+        if let Some(ref error) = lldp_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("LLDP Neighbour").borders(Borders::ALL));
+        }
+
+        if !lldp_info.neighbor_found {
+            let text = match lldp_info.listen_complete {
+                true => Line::from("No LLDP neighbour found."),
+                false => Line::from("Listening for LLDP...").yellow(),
+            };
+
+            return Paragraph::new(Text::from(vec![text]))
+                .block(Block::default().title("LLDP Neighbour").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let fields = [
+            ("Switch: ", lldp_info.neighbor_name.clone()),
+            ("Port: ", lldp_info.port_id.clone()),
+            ("Port Desc: ", lldp_info.port_description.clone()),
+            ("VLAN: ", lldp_info.vlan_id.map(|vlan_id| vlan_id.to_string())),
+            ("Chassis: ", lldp_info.chassis_id.clone()),
         ];
+
+        let mut text = Vec::with_capacity(fields.len());
+
+        for (label, value) in fields {
+            let (value, colour) = match value {
+                Some(value) => (value, Color::Green),
+                None => ("Unknown".to_string(), Color::Yellow),
+            };
+
+            let padding = max_width.saturating_sub(label.len() + value.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::styled(value, Style::default().fg(colour)),
+            ]));
+        }
+
         Paragraph::new(Text::from(text))
-            .block(Block::default().title("QUIC Info").borders(Borders::ALL))
+            .block(Block::default().title("LLDP Neighbour").borders(Borders::ALL))
+    }
+
+    fn render_mdns_info(&self, _area: Rect) -> Paragraph<'_> {
+        let mdns_info = &self.network_info.mdns_info;
+
+        if let Some(ref error) = mdns_info.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("mDNS Info").borders(Borders::ALL));
+        }
+
+        let hostname = match mdns_info.hostname {
+            Some(ref hostname) => hostname,
+            None => {
+                return Paragraph::new(Text::from(vec![Line::from("Fetching hostname...")]))
+                    .block(Block::default().title("mDNS Info").borders(Borders::ALL));
+            }
+        };
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let mut text = Vec::with_capacity(3);
+
+        text.push(Line::from(Span::styled(hostname.clone(), Style::default().bold())));
+
+        let (colour, message) = match mdns_info.self_resolution_ok {
+            Some(true) => (Color::Green, "OK"),
+            Some(false) => (Color::Red, "Failure"),
+            None => (Color::Yellow, "Waiting"),
+        };
+
+        let padding = max_width.saturating_sub("Self-resolve: ".len() + message.len());
+
+        text.push(Line::from(vec![
+            Span::styled("Self-resolve: ", Style::default().bold()),
+            Span::raw(" ".repeat(padding)),
+            Span::styled(message, Style::default().fg(colour)),
+        ]));
+
+        let (colour, devices) = match mdns_info.discovery_complete {
+            true => (Color::Reset, mdns_info.devices_discovered.to_string()),
+            false => (Color::Yellow, "Waiting".to_string()),
+        };
+
+        let padding = max_width.saturating_sub("Devices seen: ".len() + devices.len());
+
+        text.push(Line::from(vec![
+            Span::styled("Devices seen: ", Style::default().bold()),
+            Span::raw(" ".repeat(padding)),
+            Span::styled(devices, Style::default().fg(colour)),
+        ]));
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("mDNS Info").borders(Borders::ALL))
+    }
+
+    fn render_network_devices(&self, _area: Rect) -> Paragraph<'_> {
+        let network_devices = &self.network_info.network_devices;
+
+        if let Some(ref error) = network_devices.error {
+            return Paragraph::new(Text::from(vec![Line::from(error.clone())]).bold().red())
+                .block(Block::default().title("Network Devices").borders(Borders::ALL));
+        }
+
+        let max_width = self.block_width_practice as usize - 2;
+
+        let mut text = Vec::with_capacity(network_devices.devices.len() * 2 + 1);
+
+        for device in &network_devices.devices {
+            let label = format!("{}: ", device.device_type.name());
+            let padding = max_width.saturating_sub(label.len() + device.ip.len());
+
+            text.push(Line::from(vec![
+                Span::styled(label, Style::default().bold()),
+                Span::raw(" ".repeat(padding)),
+                Span::raw(device.ip.clone()),
+            ]));
+            text.push(Line::from(Span::styled(format!("  {}", device.service_name), Style::default().fg(Color::DarkGray))));
+        }
+
+        if !network_devices.discovery_complete {
+            text.push(Line::from("Discovering devices...").yellow());
+        } else if network_devices.devices.is_empty() {
+            text.push(Line::from("No devices found."));
+        }
+
+        Paragraph::new(Text::from(text))
+            .block(Block::default().title("Network Devices").borders(Borders::ALL))
+    }
+}
+
+/// Recolours a panel's border, alternating bright and dim every half second so it pulses
+fn flash_border(area: Rect, buf: &mut Buffer, kind: watch::ChangeKind) {
+    let colour = match kind {
+        watch::ChangeKind::Improved => Color::Green,
+        watch::ChangeKind::Degraded => Color::Red,
+    };
+
+    let bright = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|now| now.as_millis() / 500 % 2 == 0)
+        .unwrap_or(true);
+
+    let style = if bright {
+        Style::default().fg(colour).add_modifier(Modifier::BOLD)
+    } else {
+        Style::default().fg(colour).add_modifier(Modifier::DIM)
+    };
+
+    for x in area.left()..area.right() {
+        buf.get_mut(x, area.top()).set_style(style);
+        buf.get_mut(x, area.bottom() - 1).set_style(style);
+    }
+
+    for y in area.top()..area.bottom() {
+        buf.get_mut(area.left(), y).set_style(style);
+        buf.get_mut(area.right() - 1, y).set_style(style);
+    }
+}
+
+/// Puts a warning sign in the top right corner of a panel's border
+fn mark_anomaly(area: Rect, buf: &mut Buffer) {
+    buf.get_mut(area.right() - 3, area.top())
+        .set_symbol("⚠")
+        .set_style(Style::default().fg(ANOMALY_COLOUR).add_modifier(Modifier::BOLD));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum HealthStatus {
+    Good,
+    // Working, but not everything it should be
+    Degraded,
+    Failed,
+    // Still being checked, or couldn't be checked at all
+    Unknown,
+}
+
+/// Boils the results down to a verdict per area for the status bar
+fn compute_health(info: &internal_comms::NetworkInfo) -> Vec<(String, HealthStatus)> {
+    let mut health = Vec::new();
+
+    let local = &info.local_info;
+    let local_info_arrived = !local.local_ips.is_empty() || local.mac_address.is_some();
+    health.push(("Local".to_string(), match (local_info_arrived, local.local_ips.is_empty(), &local.gateway) {
+        (false, _, _) => HealthStatus::Unknown,
+        (true, true, _) => HealthStatus::Failed,
+        (true, false, None) => HealthStatus::Degraded,
+        (true, false, Some(_)) if local.gateway_reachable == Some(false) => HealthStatus::Degraded,
+        (true, false, Some(_)) => HealthStatus::Good,
+    }));
+
+    let dns = &info.dns_info;
+    // Servers of the other address family can't be asked from this interface, so they don't count either way
+    let testable: Vec<&internal_comms::DNSServer> = dns.dns_servers.iter().filter(|server| !server.wrong_family).collect();
+    let working = testable.iter().filter(|server| server.can_resolve == Some(true)).count();
+    health.push(("DNS".to_string(), match dns.can_fetch {
+        None => HealthStatus::Unknown,
+        Some(false) => HealthStatus::Failed,
+        Some(true) if testable.is_empty() => HealthStatus::Failed,
+        Some(true) if testable.iter().any(|server| server.can_resolve.is_none()) => HealthStatus::Unknown,
+        Some(true) if working == testable.len() => HealthStatus::Good,
+        Some(true) if working > 0 => HealthStatus::Degraded,
+        Some(true) => HealthStatus::Failed,
+    }));
+
+    health.push(("Internet".to_string(), match info.internet_info.reachable {
+        Some(true) => HealthStatus::Good,
+        Some(false) => HealthStatus::Failed,
+        None => HealthStatus::Unknown,
+    }));
+
+    // A traceroute failing says more about ICMP filtering than about the connection
+    let traceroute = &info.traceroute;
+    health.push(("Traceroute".to_string(), match (&traceroute.error, traceroute.complete) {
+        (Some(_), _) => HealthStatus::Degraded,
+        (None, true) => HealthStatus::Good,
+        (None, false) => HealthStatus::Unknown,
+    }));
+
+    let http = &info.http_info;
+    health.push(("HTTP".to_string(), match (&http.error, http.complete) {
+        (Some(_), _) => HealthStatus::Failed,
+        (None, false) => HealthStatus::Unknown,
+        (None, true) if http.captive_portal == Some(true) => HealthStatus::Failed,
+        (None, true) => reachability_health(http.can_access_1111, http.can_access_google),
+    }));
+
+    let https = &info.https_info;
+    health.push(("HTTPS".to_string(), match (&https.error, https.complete) {
+        (Some(_), _) => HealthStatus::Failed,
+        (None, false) => HealthStatus::Unknown,
+        (None, true) if https.mitm_detected == Some(true) => HealthStatus::Failed,
+        (None, true) => reachability_health(https.can_access_1111, https.can_access_google),
+    }));
+
+    health
+}
+
+/// Good if both sites worked, Degraded if only one did
+fn reachability_health(first: Option<bool>, second: Option<bool>) -> HealthStatus {
+    match (first == Some(true), second == Some(true)) {
+        (true, true) => HealthStatus::Good,
+        (false, false) => HealthStatus::Failed,
+        _ => HealthStatus::Degraded,
     }
 }
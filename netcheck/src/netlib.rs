@@ -1,11 +1,341 @@
 extern crate pnet;
 
+use std::net::IpAddr;
+#[cfg(windows)]
+use std::sync::OnceLock;
+
+/// An interface as offered in the picker
+#[derive(Debug, Clone)]
+pub struct InterfaceSummary {
+    pub name: String,
+    pub is_up: bool,
+    pub is_loopback: bool,
+    // The address checks would run from, if it has a usable one
+    pub primary_ip: Option<IpAddr>,
+}
+
+impl InterfaceSummary {
+    /// Whether the picker shows it without being asked to show everything
+    pub fn is_usable(&self) -> bool {
+        self.is_up && !self.is_loopback
+    }
+}
+
 // Get list of network interfaces
-pub fn get_interfaces() -> Vec<String> {
+pub fn get_interfaces() -> Vec<InterfaceSummary> {
     let interfaces = pnet::datalink::interfaces();
-    let mut interface_names = Vec::new();
+    let mut interface_summaries = Vec::new();
     for interface in interfaces {
-        interface_names.push(interface.name);
+        interface_summaries.push(InterfaceSummary {
+            is_up: interface.is_up(),
+            is_loopback: interface.is_loopback(),
+            primary_ip: crate::fetch_local::preferred_address(&interface.ips).map(|address| address.ip()),
+            name: interface.name,
+        });
+    }
+    interface_summaries
+}
+
+/// The name to show for an interface in the UI. On Windows pnet names interfaces after their
+/// NPF device path ("\Device\NPF_{GUID}"), so this swaps in the connection name users know,
+/// like "Ethernet" or "Wi-Fi". Everywhere else the real name is already the familiar one.
+#[cfg(windows)]
+pub fn display_name(name: &str) -> String {
+    static FRIENDLY_NAMES: OnceLock<Vec<(String, String)>> = OnceLock::new();
+
+    let friendly_names = FRIENDLY_NAMES.get_or_init(get_friendly_names);
+
+    let guid = match name.find('{') {
+        Some(start) => &name[start..],
+        None => return name.to_string(),
+    };
+
+    friendly_names.iter()
+        .find(|(transport, _)| transport.ends_with(guid))
+        .map(|(_, friendly_name)| friendly_name.clone())
+        .unwrap_or_else(|| name.to_string())
+}
+
+#[cfg(not(windows))]
+pub fn display_name(name: &str) -> String {
+    name.to_string()
+}
+
+/// Pairs each adapter's transport ("\Device\Tcpip_{GUID}") with its connection name, as
+/// listed by getmac. Disconnected adapters have no transport and so get left out.
+#[cfg(windows)]
+fn get_friendly_names() -> Vec<(String, String)> {
+    let output = match std::process::Command::new("getmac").args(["/v", "/fo", "csv", "/nh"]).output() {
+        Ok(output) => output,
+        Err(_) => return Vec::new(),
+    };
+
+    // "Connection Name","Network Adapter","Physical Address","Transport Name"
+    String::from_utf8_lossy(&output.stdout).lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.trim().split("\",\"").map(|field| field.trim_matches('"')).collect();
+
+            match fields.as_slice() {
+                [connection_name, _, _, transport_name, ..] => Some((transport_name.to_string(), connection_name.to_string())),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// What the process is allowed to do, some checks need raw sockets
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Capabilities {
+    pub raw_socket: bool,
+    pub admin: bool,
+}
+
+/// Works out whether we can use raw sockets. In containers even root may lack CAP_NET_RAW,
+/// so this actually tries to open one rather than going by the user alone.
+pub fn detect_capabilities() -> Capabilities {
+    let raw_socket = socket2::Socket::new(
+        socket2::Domain::IPV4,
+        socket2::Type::RAW,
+        Some(socket2::Protocol::ICMPV4),
+    ).is_ok();
+
+    Capabilities {
+        raw_socket,
+        admin: is_admin(),
+    }
+}
+
+#[cfg(unix)]
+fn is_admin() -> bool {
+    // SAFETY: geteuid has no preconditions and cannot fail
+    unsafe { libc::geteuid() == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_admin() -> bool {
+    false
+}
+
+/// Whether `ip` falls in a range reserved for documentation (RFC 5737's TEST-NET-1/2/3,
+/// or 2001:db8::/32 for IPv6). These turn up in examples but are never routed.
+pub fn is_documentation_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => matches!(ip.octets(), [192, 0, 2, _] | [198, 51, 100, _] | [203, 0, 113, _]),
+        IpAddr::V6(ip) => ip.segments()[0] == 0x2001 && ip.segments()[1] == 0x0db8,
+    }
+}
+
+/// Describes the hardware behind an interface name, decoding systemd's predictable
+/// naming scheme (e.g. "enp3s0" -> "Ethernet [PCI bus 3, slot 0]") where possible.
+#[cfg(not(target_os = "macos"))]
+pub fn describe_interface(name: &str) -> Option<String> {
+    if name == "lo" {
+        return Some("Loopback".to_string());
+    }
+
+    // Kernel-assigned names carry no location information
+    for (prefix, kind) in [("eth", "Ethernet"), ("wlan", "WLAN"), ("wwan", "WWAN")] {
+        if let Some(index) = name.strip_prefix(prefix) {
+            if !index.is_empty() && index.chars().all(|c| c.is_ascii_digit()) {
+                return Some(kind.to_string());
+            }
+        }
+    }
+
+    let kind = match name.get(..2) {
+        Some("en") => "Ethernet",
+        Some("wl") => "WLAN",
+        Some("ww") => "WWAN",
+        Some("ib") => "InfiniBand",
+        Some("sl") => "Serial line IP",
+        _ => return None,
+    };
+
+    match describe_location(&name[2..]) {
+        Some(location) => Some(format!("{} [{}]", kind, location)),
+        None => Some(kind.to_string()),
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn describe_interface(name: &str) -> Option<String> {
+    let description = match name {
+        "en0" => "Ethernet / Wi-Fi (built-in)",
+        "lo0" => "Loopback",
+        "awdl0" => "Apple Wireless Direct Link",
+        _ if name.starts_with("en") => "Ethernet / Wi-Fi",
+        _ if name.starts_with("utun") => "VPN tunnel",
+        _ if name.starts_with("bridge") => "Bridge",
+        _ => return None,
+    };
+
+    Some(description.to_string())
+}
+
+/// Whether the name is one VPN software gives its tunnel interfaces: OpenVPN and most others
+/// (tun/tap), WireGuard (wg, and NordVPN's nordlynx), macOS (utun), PPP based VPNs and Tailscale
+pub fn is_tunnel_interface(name: &str) -> bool {
+    ["tun", "tap", "wg", "utun", "ppp", "ipsec", "nordlynx", "tailscale"].iter().any(|prefix| name.starts_with(prefix))
+}
+
+/// Decodes the part of a predictable interface name after the type prefix
+#[cfg(not(target_os = "macos"))]
+fn describe_location(location: &str) -> Option<String> {
+    // enx001122334455 - named after the MAC address
+    if let Some(mac) = location.strip_prefix('x') {
+        if mac.len() != 12 || !mac.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let octets: Vec<&str> = (0..12).step_by(2).map(|i| &mac[i..i + 2]).collect();
+        return Some(format!("MAC {}", octets.join(":").to_uppercase()));
+    }
+
+    // Everything else is a series of single letter keys, each followed by a number
+    let mut fields = Vec::new();
+    let mut chars = location.chars().peekable();
+
+    while let Some(key) = chars.next() {
+        let mut value = String::new();
+
+        // USB port chains are written as u1.2.3
+        while let Some(&c) = chars.peek() {
+            if c.is_ascii_digit() || (key == 'u' && c == '.') {
+                value.push(c);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        if value.is_empty() {
+            return None;
+        }
+
+        fields.push((key, value));
+    }
+
+    match fields.first() {
+        Some(('o', _)) | Some(('s', _)) | Some(('p', _)) | Some(('P', _)) => {}
+        _ => return None,
+    }
+
+    let mut parts = Vec::new();
+
+    for (i, (key, value)) in fields.iter().enumerate() {
+        let part = match key {
+            'o' => format!("onboard device {}", value),
+            's' if i == 0 => format!("hotplug slot {}", value),
+            's' => format!("slot {}", value),
+            'P' => format!("PCI domain {}", value),
+            'p' => format!("PCI bus {}", value),
+            'f' => format!("function {}", value),
+            'd' => format!("port {}", value),
+            'u' => format!("USB port {}", value),
+            // USB configuration and interface numbers aren't useful to show
+            'c' | 'i' => continue,
+            _ => return None,
+        };
+
+        parts.push(part);
+    }
+
+    Some(parts.join(", "))
+}
+
+/// Formats a byte count with a unit that keeps it short, e.g. "512 B" or "1.4 MB"
+pub fn format_bytes(bytes: f64) -> String {
+    let units = [("GB", 1e9), ("MB", 1e6), ("KB", 1e3)];
+
+    for (suffix, size) in units {
+        if bytes >= size {
+            return format!("{:.1} {}", bytes / size, suffix);
+        }
+    }
+
+    format!("{:.0} B", bytes)
+}
+
+/// Formats a number of seconds as the largest two units, e.g. "1d 4h" or "12m 30s"
+pub fn format_duration(seconds: u64) -> String {
+    let units = [("d", 86400), ("h", 3600), ("m", 60), ("s", 1)];
+
+    let mut parts = Vec::new();
+    let mut remaining = seconds;
+
+    for (suffix, size) in units {
+        if remaining >= size || (parts.is_empty() && size == 1) {
+            parts.push(format!("{}{}", remaining / size, suffix));
+            remaining %= size;
+        } else if !parts.is_empty() {
+            // Keep the two units adjacent, "1d 30s" reads oddly
+            break;
+        }
+
+        if parts.len() == 2 {
+            break;
+        }
+    }
+
+    parts.join(" ")
+}
+
+/// Cuts `value` short with an ellipsis so it takes up at most `max_width` characters
+pub fn fit_width(value: &str, max_width: usize) -> String {
+    match value.chars().count() > max_width {
+        true => format!("{}…", value.chars().take(max_width.saturating_sub(1)).collect::<String>()),
+        false => value.to_string(),
+    }
+}
+
+/// Shortens a "AB:CD:..." fingerprint to its first and last 8 hex digits, e.g. "ABCDEF01…23456789",
+/// which is plenty to compare by eye and fits on one line of a panel
+pub fn short_fingerprint(fingerprint: &str) -> String {
+    let hex: Vec<char> = fingerprint.chars().filter(|c| c.is_ascii_hexdigit()).collect();
+
+    match hex.len() > 16 {
+        true => format!("{}…{}", hex[..8].iter().collect::<String>(), hex[hex.len() - 8..].iter().collect::<String>()),
+        false => hex.into_iter().collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_under_a_minute() {
+        assert_eq!(format_duration(0), "0s");
+        assert_eq!(format_duration(59), "59s");
+    }
+
+    #[test]
+    fn durations_keep_the_largest_two_units() {
+        assert_eq!(format_duration(90), "1m 30s");
+        assert_eq!(format_duration(93_784), "1d 2h");
+    }
+
+    #[test]
+    fn durations_skip_units_that_are_not_adjacent() {
+        assert_eq!(format_duration(3600), "1h");
+        assert_eq!(format_duration(86_430), "1d");
+        assert_eq!(format_duration(3_630), "1h");
+    }
+
+    #[test]
+    fn fingerprints_keep_both_ends() {
+        let fingerprint = "AB:CD:EF:01:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:00:23:45:67:89";
+        assert_eq!(short_fingerprint(fingerprint), "ABCDEF01…23456789");
+        assert_eq!(short_fingerprint("AB:CD"), "ABCD");
+    }
+
+    #[test]
+    fn vpn_interfaces_are_tunnels() {
+        for name in ["tun0", "wg0", "utun3", "ppp0", "nordlynx", "tailscale0"] {
+            assert!(is_tunnel_interface(name), "{} isn't a tunnel", name);
+        }
+
+        assert!(!is_tunnel_interface("eth0"));
+        assert!(!is_tunnel_interface("wlp2s0"));
     }
-    interface_names
-}
\ No newline at end of file
+}
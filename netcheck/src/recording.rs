@@ -0,0 +1,164 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Stdout, Write};
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use color_eyre::{eyre::WrapErr, Result};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use serde_json::{json, Value};
+
+use crate::tui;
+
+/// Writes terminal output to an asciinema v2 .cast file as it's drawn
+pub struct CastRecorder {
+    file: BufWriter<File>,
+    started: Instant,
+}
+
+impl CastRecorder {
+    pub fn create(path: &Path, width: u16, height: u16) -> Result<Self> {
+        let file = File::create(path)
+            .wrap_err_with(|| format!("couldn't create recording file {}", path.display()))?;
+        let mut file = BufWriter::new(file);
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        let header = json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": timestamp,
+            "env": { "TERM": std::env::var("TERM").unwrap_or_default() },
+        });
+
+        writeln!(file, "{}", header).wrap_err("couldn't write recording header")?;
+
+        Ok(CastRecorder {
+            file,
+            started: Instant::now(),
+        })
+    }
+
+    fn record_output(&mut self, output: &[u8]) -> io::Result<()> {
+        let event = json!([
+            self.started.elapsed().as_secs_f64(),
+            "o",
+            String::from_utf8_lossy(output),
+        ]);
+
+        writeln!(self.file, "{}", event)?;
+        self.file.flush()
+    }
+}
+
+/// Terminal output that's optionally copied into a recording.
+/// Each flush is one frame, so it becomes one event in the cast file.
+pub struct TuiWriter {
+    stdout: Stdout,
+    recorder: Option<CastRecorder>,
+    pending: Vec<u8>,
+}
+
+impl TuiWriter {
+    pub fn new(stdout: Stdout, recorder: Option<CastRecorder>) -> Self {
+        TuiWriter {
+            stdout,
+            recorder,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Write for TuiWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.stdout.write(buf)?;
+
+        if self.recorder.is_some() {
+            self.pending.extend_from_slice(&buf[..written]);
+        }
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stdout.flush()?;
+
+        if let Some(ref mut recorder) = self.recorder {
+            if !self.pending.is_empty() {
+                // A failing recording shouldn't take the UI down with it
+                let _ = recorder.record_output(&self.pending);
+                self.pending.clear();
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Plays back a .cast file at `speed` times real time. Q stops playback early.
+pub fn replay(path: &Path, speed: f64) -> Result<()> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("couldn't open recording {}", path.display()))?;
+    let mut lines = BufReader::new(file).lines();
+
+    // The first line is the header, which we don't need for playback
+    match lines.next() {
+        Some(header) => {
+            header.wrap_err("couldn't read recording header")?;
+        }
+        None => color_eyre::eyre::bail!("{} is empty", path.display()),
+    }
+
+    let mut terminal = tui::init(None)?;
+
+    // Put the terminal back however playback ends, before any error gets printed
+    let played = play(&mut terminal, lines, speed);
+    tui::restore()?;
+
+    played
+}
+
+/// Writes each output event to the terminal once its time comes. Returns early if Q is pressed.
+fn play(terminal: &mut tui::Tui, lines: impl Iterator<Item = io::Result<String>>, speed: f64) -> Result<()> {
+    let mut previous_time = 0.0;
+
+    for line in lines {
+        let line = line.wrap_err("couldn't read recording")?;
+
+        let event: Value = match serde_json::from_str(&line) {
+            Ok(event) => event,
+            Err(_) => continue,
+        };
+
+        let (time, kind, data) = match (event.get(0).and_then(Value::as_f64), event.get(1).and_then(Value::as_str), event.get(2).and_then(Value::as_str)) {
+            (Some(time), Some(kind), Some(data)) => (time, kind, data),
+            _ => continue,
+        };
+
+        // Only output events matter, input events weren't shown on screen
+        if kind != "o" {
+            continue;
+        }
+
+        let deadline = Instant::now() + Duration::from_secs_f64(((time - previous_time) / speed).max(0.0));
+        previous_time = time;
+
+        // Any other key, or a resize, mustn't cut the wait short
+        while event::poll(deadline.saturating_duration_since(Instant::now()))? {
+            if let Event::Key(key_event) = event::read()? {
+                if key_event.kind == KeyEventKind::Press && matches!(key_event.code, KeyCode::Char('q') | KeyCode::Char('Q')) {
+                    return Ok(());
+                }
+            }
+        }
+
+        let backend = terminal.backend_mut();
+        backend.write_all(data.as_bytes())?;
+        backend.flush()?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,37 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use color_eyre::{eyre::WrapErr, Result};
+use serde_json::json;
+
+use crate::App;
+
+/// Writes the results as they stand to `path` as JSON, along with when they were taken and
+/// which interface they're for
+pub fn save(app: &App, path: &Path) -> Result<()> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let report = json!({
+        "timestamp": timestamp,
+        "interface": app.chosen_interface,
+        "network_info": app.network_info,
+    });
+
+    let file = File::create(path)
+        .wrap_err_with(|| format!("couldn't create report file {}", path.display()))?;
+    let mut file = BufWriter::new(file);
+
+    serde_json::to_writer_pretty(&mut file, &report)
+        .wrap_err_with(|| format!("couldn't write report to {}", path.display()))?;
+
+    writeln!(file)
+        .and_then(|_| file.flush())
+        .wrap_err_with(|| format!("couldn't write report to {}", path.display()))?;
+
+    Ok(())
+}
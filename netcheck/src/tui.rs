@@ -1,16 +1,18 @@
-use std::io::{self, stdout, Stdout};
+use std::io::{self, stdout};
 
 use crossterm::{execute, terminal::*};
 use ratatui::prelude::*;
 
+use crate::recording::{CastRecorder, TuiWriter};
+
 /// A type alias for the terminal type used in this application
-pub type Tui = Terminal<CrosstermBackend<Stdout>>;
+pub type Tui = Terminal<CrosstermBackend<TuiWriter>>;
 
-/// Initialize the terminal
-pub fn init() -> io::Result<Tui> {
+/// Initialize the terminal, recording everything drawn if a recorder is given
+pub fn init(recorder: Option<CastRecorder>) -> io::Result<Tui> {
     execute!(stdout(), EnterAlternateScreen)?;
     enable_raw_mode()?;
-    Terminal::new(CrosstermBackend::new(stdout()))
+    Terminal::new(CrosstermBackend::new(TuiWriter::new(stdout(), recorder)))
 }
 
 /// Restore the terminal to its original state
@@ -18,4 +20,4 @@ pub fn restore() -> io::Result<()> {
     execute!(stdout(), LeaveAlternateScreen)?;
     disable_raw_mode()?;
     Ok(())
-}
\ No newline at end of file
+}
@@ -0,0 +1,349 @@
+use crate::internal_comms::NetworkInfo;
+
+// Latencies wobble a little on every run, so only a move this large (and at least a quarter of the
+// old value) counts as a change worth pointing out
+const LATENCY_CHANGE_THRESHOLD_MS: f64 = 5.0;
+
+pub const DEFAULT_ANOMALY_BASELINE_SAMPLES: u64 = 10;
+// How many standard deviations above the mean a sample has to be to count as an anomaly
+const ANOMALY_THRESHOLD_STDDEVS: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChangeKind {
+    Improved,
+    Degraded,
+}
+
+/// A check whose result differs from the previous run under --refresh-interval
+#[derive(Debug, Clone, PartialEq)]
+pub struct NetworkInfoChange {
+    // The NetworkInfo field holding the value, which is also the panel that shows it
+    pub panel: &'static str,
+    pub field: String,
+    pub kind: ChangeKind,
+}
+
+/// Every Option<bool> check and Option<f64> latency that has a value in both runs and changed
+/// between them. Values only present in one run are still in progress rather than changed.
+pub fn diff_network_info(old: &NetworkInfo, new: &NetworkInfo) -> Vec<NetworkInfoChange> {
+    let mut changes = Vec::new();
+
+    // (panel, field, old, new, whether true is the good outcome)
+    let mut flags = vec![
+        ("local_info", "gateway_reachable".to_string(), old.local_info.gateway_reachable, new.local_info.gateway_reachable, true),
+        ("local_info", "ipv6_pmtud_working".to_string(), old.local_info.ipv6_pmtud_working, new.local_info.ipv6_pmtud_working, true),
+        ("internet_info", "reachable".to_string(), old.internet_info.reachable, new.internet_info.reachable, true),
+        ("dhcp_info", "server_on_local_subnet".to_string(), old.dhcp_info.server_on_local_subnet, new.dhcp_info.server_on_local_subnet, true),
+        ("dhcp_info", "router_resolved".to_string(), old.dhcp_info.router_resolved, new.dhcp_info.router_resolved, true),
+        ("dns_info", "can_fetch".to_string(), old.dns_info.can_fetch, new.dns_info.can_fetch, true),
+        ("dns_info", "can_bind_interface".to_string(), old.dns_info.can_bind_interface, new.dns_info.can_bind_interface, true),
+        ("dns_info", "doq_supported".to_string(), old.dns_info.doq_supported, new.dns_info.doq_supported, true),
+        ("http_info", "can_access_1111".to_string(), old.http_info.can_access_1111, new.http_info.can_access_1111, true),
+        ("http_info", "can_access_google".to_string(), old.http_info.can_access_google, new.http_info.can_access_google, true),
+        ("http_info", "captive_portal".to_string(), old.http_info.captive_portal, new.http_info.captive_portal, false),
+        ("https_info", "can_access_1111".to_string(), old.https_info.can_access_1111, new.https_info.can_access_1111, true),
+        ("https_info", "can_access_google".to_string(), old.https_info.can_access_google, new.https_info.can_access_google, true),
+        ("https_info", "mitm_detected".to_string(), old.https_info.mitm_detected, new.https_info.mitm_detected, false),
+        ("ntp_info", "can_access_ntp".to_string(), old.ntp_info.can_access_ntp, new.ntp_info.can_access_ntp, true),
+        ("quic_info", "can_access_1111".to_string(), old.quic_info.can_access_1111, new.quic_info.can_access_1111, true),
+        ("quic_info", "http3_supported".to_string(), old.quic_info.http3_supported, new.quic_info.http3_supported, true),
+        ("quic_info", "can_access_google".to_string(), old.quic_info.can_access_google, new.quic_info.can_access_google, true),
+        ("quic_info", "quic_port_blocked".to_string(), old.quic_info.quic_port_blocked, new.quic_info.quic_port_blocked, false),
+        ("ipv6_info", "gateway_reachable".to_string(), old.ipv6_info.gateway_reachable, new.ipv6_info.gateway_reachable, true),
+        ("ipv6_info", "has_global_ipv6".to_string(), old.ipv6_info.has_global_ipv6, new.ipv6_info.has_global_ipv6, true),
+        ("ipv6_info", "can_reach_ipv6_internet".to_string(), old.ipv6_info.can_reach_ipv6_internet, new.ipv6_info.can_reach_ipv6_internet, true),
+        ("mdns_info", "self_resolution_ok".to_string(), old.mdns_info.self_resolution_ok, new.mdns_info.self_resolution_ok, true),
+        ("port_forward_info", "externally_reachable".to_string(),
+            old.port_forward_info.as_ref().and_then(|info| info.externally_reachable),
+            new.port_forward_info.as_ref().and_then(|info| info.externally_reachable), true),
+    ];
+
+    let mut latencies = vec![
+        ("local_info", "gateway_latency_ms".to_string(), old.local_info.gateway_latency_ms, new.local_info.gateway_latency_ms),
+        ("internet_info", "cloudflare_ping".to_string(), old.internet_info.cloudflare_ping, new.internet_info.cloudflare_ping),
+        ("ntp_info", "round_trip_delay_ms".to_string(), old.ntp_info.round_trip_delay_ms, new.ntp_info.round_trip_delay_ms),
+        ("quic_info", "quic_rtt_ms".to_string(), old.quic_info.quic_rtt_ms, new.quic_info.quic_rtt_ms),
+        ("ipv6_info", "gateway_ping_ms".to_string(), old.ipv6_info.gateway_ping_ms, new.ipv6_info.gateway_ping_ms),
+        ("ipv6_info", "ping_ms".to_string(), old.ipv6_info.ping_ms, new.ipv6_info.ping_ms),
+    ];
+
+    // Servers are matched up by address since the list can be reordered or grow between runs
+    for new_server in &new.dns_info.dns_servers {
+        let old_server = match old.dns_info.dns_servers.iter().find(|server| server.ip == new_server.ip) {
+            Some(server) => server,
+            None => continue,
+        };
+
+        for (name, old_value, new_value) in [
+            ("can_resolve", old_server.can_resolve, new_server.can_resolve),
+            ("resolves_a", old_server.resolves_a, new_server.resolves_a),
+            ("resolves_aaaa", old_server.resolves_aaaa, new_server.resolves_aaaa),
+            ("resolves_mx", old_server.resolves_mx, new_server.resolves_mx),
+        ] {
+            flags.push(("dns_info", format!("dns_servers[{}].{}", new_server.ip, name), old_value, new_value, true));
+        }
+
+        latencies.push(("dns_info", format!("dns_servers[{}].latency_ms", new_server.ip), old_server.latency_ms, new_server.latency_ms));
+    }
+
+    for (panel, field, old_value, new_value, good_value) in flags {
+        if let (Some(old_value), Some(new_value)) = (old_value, new_value) {
+            if old_value != new_value {
+                let kind = if new_value == good_value { ChangeKind::Improved } else { ChangeKind::Degraded };
+                changes.push(NetworkInfoChange { panel, field, kind });
+            }
+        }
+    }
+
+    for (panel, field, old_value, new_value) in latencies {
+        if let (Some(old_value), Some(new_value)) = (old_value, new_value) {
+            let difference = new_value - old_value;
+
+            if difference.abs() > LATENCY_CHANGE_THRESHOLD_MS && difference.abs() > old_value / 4.0 {
+                let kind = if difference < 0.0 { ChangeKind::Improved } else { ChangeKind::Degraded };
+                changes.push(NetworkInfoChange { panel, field, kind });
+            }
+        }
+    }
+
+    changes
+}
+
+/// Running mean and variance of one metric across refreshes, kept with Welford's online algorithm
+/// so no samples need storing
+#[derive(Debug, Default, Clone)]
+pub struct AnomalyDetector {
+    mean: f64,
+    // Population variance of the samples so far
+    variance: f64,
+    n: u64,
+}
+
+impl AnomalyDetector {
+    /// Takes the next sample. Once `baseline_samples` have been taken, returns whether this one is
+    /// more than three standard deviations above the mean. Anomalies are kept out of the baseline,
+    /// otherwise a bad spell would teach it that bad is normal.
+    pub fn observe(&mut self, value: f64, baseline_samples: u64) -> bool {
+        if self.n >= baseline_samples && value > self.mean + ANOMALY_THRESHOLD_STDDEVS * self.stddev() {
+            return true;
+        }
+
+        self.n += 1;
+
+        let delta = value - self.mean;
+        self.mean += delta / self.n as f64;
+        self.variance += (delta * (value - self.mean) - self.variance) / self.n as f64;
+
+        false
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    pub fn stddev(&self) -> f64 {
+        self.variance.sqrt()
+    }
+}
+
+/// A sample well outside its metric's baseline
+#[derive(Debug, Clone, PartialEq)]
+pub struct Anomaly {
+    pub panel: &'static str,
+    pub field: String,
+    pub value: f64,
+    pub mean: f64,
+    pub stddev: f64,
+}
+
+impl Anomaly {
+    /// One line for the --anomaly-log file
+    pub fn log_line(&self, timestamp: u64) -> String {
+        // A baseline that never moved has no spread to measure against
+        let deviation = match self.stddev > 0.0 {
+            true => format!("{:.1} standard deviations above", (self.value - self.mean) / self.stddev),
+            false => "above a steady".to_string(),
+        };
+
+        format!("{} {}.{} {:.1}, {} the mean of {:.1}", timestamp, self.panel, self.field, self.value, deviation, self.mean)
+    }
+}
+
+/// Every metric where higher means something's changed for the worse (or, for traffic, that
+/// something unusual is going on), once it has its final value for the run
+pub fn numeric_metrics(info: &NetworkInfo) -> Vec<(&'static str, String, f64)> {
+    let mut metrics = vec![
+        ("local_info", "gateway_latency_ms".to_string(), info.local_info.gateway_latency_ms),
+        ("internet_info", "cloudflare_ping".to_string(), info.internet_info.cloudflare_ping),
+        ("ntp_info", "round_trip_delay_ms".to_string(), info.ntp_info.round_trip_delay_ms),
+        ("quic_info", "quic_rtt_ms".to_string(), info.quic_info.quic_rtt_ms),
+        ("network_stats", "rx_bps".to_string(), info.network_stats.rx_bps),
+        ("network_stats", "tx_bps".to_string(), info.network_stats.tx_bps),
+    ];
+
+    for server in &info.dns_info.dns_servers {
+        metrics.push(("dns_info", format!("dns_servers[{}].latency_ms", server.ip), server.latency_ms));
+    }
+
+    // Partway through, the count of replies is still catching up with the count sent
+    let packet_loss = &info.packet_loss_info;
+    if packet_loss.complete && packet_loss.sent > 0 {
+        let loss_percent = (packet_loss.sent - packet_loss.received) as f64 / packet_loss.sent as f64 * 100.0;
+        metrics.push(("packet_loss_info", "loss_percent".to_string(), Some(loss_percent)));
+    }
+
+    metrics.into_iter()
+        .filter_map(|(panel, field, value)| value.map(|value| (panel, field, value)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::internal_comms::DNSServer;
+
+    fn dns_server(ip: &str, can_resolve: Option<bool>) -> DNSServer {
+        DNSServer {
+            ip: ip.to_string(),
+            can_resolve,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn unchanged_runs_have_no_changes() {
+        let mut info = NetworkInfo::default();
+        info.internet_info.reachable = Some(true);
+        info.local_info.gateway_latency_ms = Some(3.0);
+
+        assert!(diff_network_info(&info, &info.clone()).is_empty());
+    }
+
+    #[test]
+    fn flags_going_from_fail_to_pass_are_improvements() {
+        let mut old = NetworkInfo::default();
+        old.internet_info.reachable = Some(false);
+        let mut new = NetworkInfo::default();
+        new.internet_info.reachable = Some(true);
+
+        assert_eq!(
+            diff_network_info(&old, &new),
+            vec![NetworkInfoChange { panel: "internet_info", field: "reachable".to_string(), kind: ChangeKind::Improved }]
+        );
+    }
+
+    #[test]
+    fn flags_where_true_is_bad_count_as_degraded() {
+        let mut old = NetworkInfo::default();
+        old.https_info.mitm_detected = Some(false);
+        let mut new = NetworkInfo::default();
+        new.https_info.mitm_detected = Some(true);
+
+        let changes = diff_network_info(&old, &new);
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].kind, ChangeKind::Degraded);
+    }
+
+    #[test]
+    fn values_still_in_progress_are_not_changes() {
+        let mut old = NetworkInfo::default();
+        old.quic_info.can_access_1111 = Some(true);
+        old.ntp_info.round_trip_delay_ms = Some(20.0);
+
+        assert!(diff_network_info(&old, &NetworkInfo::default()).is_empty());
+    }
+
+    #[test]
+    fn small_latency_wobbles_are_ignored() {
+        let mut old = NetworkInfo::default();
+        old.internet_info.cloudflare_ping = Some(12.0);
+        let mut new = NetworkInfo::default();
+        new.internet_info.cloudflare_ping = Some(15.0);
+
+        assert!(diff_network_info(&old, &new).is_empty());
+
+        new.internet_info.cloudflare_ping = Some(40.0);
+        assert_eq!(diff_network_info(&old, &new)[0].kind, ChangeKind::Degraded);
+
+        old.internet_info.cloudflare_ping = Some(80.0);
+        assert_eq!(diff_network_info(&old, &new)[0].kind, ChangeKind::Improved);
+    }
+
+    #[test]
+    fn dns_servers_are_matched_by_address() {
+        let mut old = NetworkInfo::default();
+        old.dns_info.dns_servers = vec![dns_server("1.1.1.1", Some(true)), dns_server("8.8.8.8", Some(true))];
+        let mut new = NetworkInfo::default();
+        new.dns_info.dns_servers = vec![dns_server("8.8.8.8", Some(false)), dns_server("1.1.1.1", Some(true))];
+
+        assert_eq!(
+            diff_network_info(&old, &new),
+            vec![NetworkInfoChange { panel: "dns_info", field: "dns_servers[8.8.8.8].can_resolve".to_string(), kind: ChangeKind::Degraded }]
+        );
+    }
+
+    #[test]
+    fn welford_matches_the_direct_calculation() {
+        let samples = [12.0, 15.0, 11.0, 14.0, 13.0];
+        let mut detector = AnomalyDetector::default();
+
+        for sample in samples {
+            assert!(!detector.observe(sample, 10));
+        }
+
+        // Mean 13, squared deviations 1 + 4 + 4 + 1 + 0 over 5
+        assert!((detector.mean() - 13.0).abs() < 1e-9);
+        assert!((detector.stddev() - 2.0_f64.sqrt()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn nothing_is_an_anomaly_during_the_baseline() {
+        let mut detector = AnomalyDetector::default();
+
+        assert!(!detector.observe(10.0, 3));
+        assert!(!detector.observe(10.0, 3));
+        assert!(!detector.observe(500.0, 3));
+    }
+
+    #[test]
+    fn values_past_three_standard_deviations_are_anomalies() {
+        let mut detector = AnomalyDetector::default();
+
+        for sample in [10.0, 12.0, 10.0, 12.0] {
+            detector.observe(sample, 4);
+        }
+
+        // Mean 11, standard deviation 1
+        assert!(detector.observe(14.1, 4));
+        assert!(!detector.observe(13.9, 4));
+
+        // Lower than usual is never a problem
+        assert!(!detector.observe(1.0, 4));
+    }
+
+    #[test]
+    fn anomalies_stay_out_of_the_baseline() {
+        let mut detector = AnomalyDetector::default();
+
+        for sample in [10.0, 12.0, 10.0, 12.0] {
+            detector.observe(sample, 4);
+        }
+
+        assert!(detector.observe(100.0, 4));
+        assert!(detector.observe(100.0, 4));
+        assert!((detector.mean() - 11.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn packet_loss_only_counts_once_complete() {
+        let mut info = NetworkInfo::default();
+        info.packet_loss_info.sent = 50;
+        info.packet_loss_info.received = 40;
+
+        assert!(numeric_metrics(&info).is_empty());
+
+        info.packet_loss_info.complete = true;
+        assert_eq!(numeric_metrics(&info), vec![("packet_loss_info", "loss_percent".to_string(), 20.0)]);
+    }
+}